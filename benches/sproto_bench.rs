@@ -274,6 +274,65 @@ fn bench_unpack(c: &mut Criterion) {
     group.finish();
 }
 
+/// Encoded messages of each size, as used by the pack/unpack groups.
+fn pack_inputs() -> Vec<(&'static str, Vec<u8>)> {
+    let numbers: Vec<i64> = (0..1000).collect();
+    let values: Vec<f64> = (0..1000).map(|i| i as f64 * 0.1).collect();
+    vec![
+        ("small", encode_person(&create_person_schema())),
+        ("medium", encode_user_profile(&create_user_profile_schema())),
+        (
+            "large",
+            encode_dataset(&create_dataset_schema(), &numbers, &values),
+        ),
+    ]
+}
+
+/// `pack_into` with a buffer reused across iterations, for comparison with
+/// the allocating `pack` group.
+fn bench_pack_into(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pack_into");
+
+    for (name, data) in pack_inputs() {
+        let mut buf = Vec::with_capacity(pack::packed_len_bound(data.len()));
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new(name, data.len()), &data, |b, data| {
+            b.iter(|| {
+                buf.clear();
+                pack::pack_into(black_box(data), &mut buf);
+                black_box(buf.len())
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// `unpack_into` with a buffer reused across iterations, for comparison
+/// with the allocating `unpack` group.
+fn bench_unpack_into(c: &mut Criterion) {
+    let mut group = c.benchmark_group("unpack_into");
+
+    for (name, data) in pack_inputs() {
+        let packed = pack::pack(&data);
+        let mut buf = Vec::with_capacity(pack::unpacked_len(&packed).unwrap());
+        group.throughput(Throughput::Bytes(packed.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new(name, packed.len()),
+            &packed,
+            |b, packed| {
+                b.iter(|| {
+                    buf.clear();
+                    pack::unpack_into(black_box(packed), &mut buf).unwrap();
+                    black_box(buf.len())
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
 // ============================================================================
 // Criterion Configuration
 // ============================================================================
@@ -284,6 +343,8 @@ criterion_group!(
     bench_decode,
     bench_pack,
     bench_unpack,
+    bench_pack_into,
+    bench_unpack_into,
 );
 
 criterion_main!(benches);
@@ -0,0 +1,28 @@
+//! Code generation errors.
+
+use std::io;
+use std::path::PathBuf;
+
+use sproto::error::ParseError;
+
+/// Errors from `Generator`.
+#[derive(Debug, thiserror::Error)]
+pub enum CodegenError {
+    #[error("{}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{}:{source}", path.display())]
+    Parse { path: PathBuf, source: ParseError },
+
+    /// Two schema names turn into the same Rust identifier.
+    #[error("{kind} '{first}' and '{second}' both map to the Rust name '{ident}'")]
+    NameCollision {
+        kind: String,
+        first: String,
+        second: String,
+        ident: String,
+    },
+
+    #[error("invalid derive path '{0}'")]
+    InvalidDerive(String),
+}
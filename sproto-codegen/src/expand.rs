@@ -0,0 +1,515 @@
+//! Token generation for structs, trait impls and protocol modules.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::quote;
+use sproto::types::{Field, FieldType, Protocol, Sproto, SprotoType};
+
+use crate::error::CodegenError;
+use crate::names;
+
+/// Rust names for every type of a schema.
+pub struct Expander<'a> {
+    sproto: &'a Sproto,
+    derives: &'a [syn::Path],
+    type_idents: Vec<Ident>,
+}
+
+impl<'a> Expander<'a> {
+    pub fn new(sproto: &'a Sproto, derives: &'a [syn::Path]) -> Result<Self, CodegenError> {
+        let mut seen: HashMap<String, &str> = HashMap::new();
+        let mut type_idents = Vec::with_capacity(sproto.types_list.len());
+        for st in &sproto.types_list {
+            let name = names::type_name(&st.name);
+            if let Some(first) = seen.insert(name.clone(), &st.name) {
+                return Err(collision("types", first, &st.name, &name));
+            }
+            type_idents.push(names::ident(&name));
+        }
+        Ok(Expander {
+            sproto,
+            derives,
+            type_idents,
+        })
+    }
+
+    pub fn expand(&self) -> Result<TokenStream, CodegenError> {
+        let mut tokens = TokenStream::new();
+        for (idx, st) in self.sproto.types_list.iter().enumerate() {
+            tokens.extend(self.expand_type(idx, st)?);
+        }
+
+        let mut seen: HashMap<String, &str> = HashMap::new();
+        for proto in &self.sproto.protocols {
+            let name = names::snake_name(&proto.name);
+            if let Some(first) = seen.insert(name.clone(), &proto.name) {
+                return Err(collision("protocols", first, &proto.name, &name));
+            }
+            tokens.extend(self.expand_protocol(proto, &name));
+        }
+        Ok(tokens)
+    }
+
+    fn expand_type(&self, idx: usize, st: &SprotoType) -> Result<TokenStream, CodegenError> {
+        let ident = &self.type_idents[idx];
+        let type_name = &st.name;
+        let doc = format!(" `.{}`", st.name);
+        let derives = self.derives;
+
+        let mut seen: HashMap<String, &str> = HashMap::new();
+        let mut fields = Vec::with_capacity(st.fields.len());
+        for field in &st.fields {
+            let name = names::snake_name(&field.name);
+            if let Some(first) = seen.insert(name.clone(), &field.name) {
+                return Err(collision(
+                    &format!("fields of '{}'", st.name),
+                    first,
+                    &field.name,
+                    &name,
+                ));
+            }
+            fields.push(FieldGen {
+                field,
+                ident: names::ident(&name),
+                boxed: self.needs_box(idx, field),
+                elem: self.elem_type(field),
+            });
+        }
+
+        let decls = fields.iter().map(|f| {
+            let doc = format!(
+                " `{} {} : {}`",
+                f.field.name,
+                f.field.tag,
+                self.type_spec(f.field)
+            );
+            let ident = &f.ident;
+            let ty = f.rust_type();
+            quote! {
+                #[doc = #doc]
+                pub #ident: ::core::option::Option<#ty>,
+            }
+        });
+        let encode = self.expand_encode(ident, &fields);
+        let decode = self.expand_decode(ident, &fields);
+        let schema = self.expand_schema(ident, type_name, &fields);
+
+        Ok(quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, Default, PartialEq #(, #derives)*)]
+            pub struct #ident {
+                #(#decls)*
+            }
+
+            #encode
+            #decode
+            #schema
+        })
+    }
+
+    fn expand_encode(&self, ident: &Ident, fields: &[FieldGen]) -> TokenStream {
+        let enc = if fields.is_empty() {
+            quote!(_enc)
+        } else {
+            quote!(enc)
+        };
+        let writes = fields.iter().map(|f| {
+            let ident = &f.ident;
+            let write = f.encode();
+            quote! {
+                if let ::core::option::Option::Some(v) = &self.#ident {
+                    #write
+                }
+            }
+        });
+        quote! {
+            impl ::sproto::codec::SprotoEncode for #ident {
+                fn encode_fields(
+                    &self,
+                    #enc: &mut ::sproto::codec::StructEncoder,
+                ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+                    #(#writes)*
+                    ::core::result::Result::Ok(())
+                }
+            }
+        }
+    }
+
+    fn expand_decode(&self, ident: &Ident, fields: &[FieldGen]) -> TokenStream {
+        let body = match fields {
+            [] => quote! {
+                while dec.next_field()?.is_some() {}
+                ::core::result::Result::Ok(Self {})
+            },
+            [f] => {
+                let tag = Literal::u16_unsuffixed(f.field.tag);
+                let read = f.decode();
+                quote! {
+                    let mut msg = Self::default();
+                    while let ::core::option::Option::Some(f) = dec.next_field()? {
+                        if f.tag() == #tag {
+                            #read
+                        }
+                    }
+                    ::core::result::Result::Ok(msg)
+                }
+            }
+            _ => {
+                let arms = fields.iter().map(|f| {
+                    let tag = Literal::u16_unsuffixed(f.field.tag);
+                    let read = f.decode();
+                    quote! { #tag => { #read } }
+                });
+                quote! {
+                    let mut msg = Self::default();
+                    while let ::core::option::Option::Some(f) = dec.next_field()? {
+                        match f.tag() {
+                            #(#arms)*
+                            _ => {}
+                        }
+                    }
+                    ::core::result::Result::Ok(msg)
+                }
+            }
+        };
+        quote! {
+            impl ::sproto::codec::SprotoDecode for #ident {
+                fn decode_fields(
+                    dec: &mut ::sproto::codec::StructDecoder,
+                ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+                    #body
+                }
+            }
+        }
+    }
+
+    fn expand_schema(&self, ident: &Ident, type_name: &str, fields: &[FieldGen]) -> TokenStream {
+        let defs = fields.iter().map(|f| {
+            let name = &*f.field.name;
+            let tag = Literal::u16_unsuffixed(f.field.tag);
+            let field_type = match &f.elem {
+                Elem::Integer | Elem::Decimal => quote!(::sproto::types::FieldType::Integer),
+                Elem::Boolean => quote!(::sproto::types::FieldType::Boolean),
+                Elem::Double => quote!(::sproto::types::FieldType::Double),
+                Elem::String => quote!(::sproto::types::FieldType::String),
+                Elem::Binary => quote!(::sproto::types::FieldType::Binary),
+                Elem::Struct(ty) => quote! {
+                    ::sproto::types::FieldType::Struct(
+                        <#ty as ::sproto::codec::SprotoSchema>::register_with(sproto, visiting)?
+                    )
+                },
+            };
+            let ctor = if f.field.is_array {
+                quote!(array)
+            } else {
+                quote!(new)
+            };
+            let mut extra = Vec::new();
+            if f.field.decimal_precision > 0 {
+                let precision = Literal::u32_unsuffixed(f.field.decimal_precision);
+                extra.push(quote! { field.decimal_precision = #precision; });
+            }
+            if f.field.key_tag >= 0 {
+                let key_tag = Literal::i32_unsuffixed(f.field.key_tag);
+                extra.push(quote! { field.key_tag = #key_tag; });
+            }
+            if f.field.is_map {
+                extra.push(quote! { field.is_map = true; });
+            }
+            if extra.is_empty() {
+                quote! { ::sproto::types::Field::#ctor(#name, #tag, #field_type) }
+            } else {
+                quote! {
+                    {
+                        let mut field = ::sproto::types::Field::#ctor(#name, #tag, #field_type);
+                        #(#extra)*
+                        field
+                    }
+                }
+            }
+        });
+        quote! {
+            impl ::sproto::codec::SprotoSchema for #ident {
+                const TYPE_NAME: &'static str = #type_name;
+
+                fn register(sproto: &mut ::sproto::Sproto) -> usize {
+                    <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+                        .unwrap_or_else(|err| ::core::panic!("{}", err))
+                }
+
+                fn register_with(
+                    sproto: &mut ::sproto::Sproto,
+                    visiting: &mut ::std::vec::Vec<&'static str>,
+                ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+                    let existing = sproto.get_type_index(#type_name);
+                    if let ::core::option::Option::Some(idx) = existing.filter(|_| visiting.contains(&#type_name)) {
+                        return ::core::result::Result::Ok(idx);
+                    }
+                    let idx = match existing {
+                        ::core::option::Option::Some(idx) => idx,
+                        ::core::option::Option::None => sproto.add_type(#type_name, ::std::vec::Vec::new()),
+                    };
+                    visiting.push(#type_name);
+                    let fields = ::std::vec![#(#defs),*];
+                    visiting.pop();
+                    match existing {
+                        ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+                        ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+                    }
+                    ::core::result::Result::Ok(idx)
+                }
+            }
+        }
+    }
+
+    fn expand_protocol(&self, proto: &Protocol, name: &str) -> TokenStream {
+        let ident = names::ident(name);
+        let doc = format!(" Protocol `{} {}`.", proto.name, proto.tag);
+        let proto_name = &proto.name;
+        let tag = Literal::u16_unsuffixed(proto.tag);
+        let alias = |idx: Option<usize>| match idx {
+            Some(idx) => {
+                let ty = &self.type_idents[idx];
+                quote!(super::#ty)
+            }
+            None => quote!(()),
+        };
+        let request = alias(proto.request);
+        let response = alias(proto.response);
+        let request_doc = match proto.request {
+            Some(idx) => format!(" Request type `{}`.", self.sproto.types_list[idx].name),
+            None => " No request body.".to_string(),
+        };
+        let response_doc = match proto.response {
+            Some(idx) => format!(" Response type `{}`.", self.sproto.types_list[idx].name),
+            None if proto.confirm => " Empty response (`response nil`).".to_string(),
+            None => " No response.".to_string(),
+        };
+        let expects_response = proto.expects_response();
+
+        quote! {
+            #[doc = #doc]
+            pub mod #ident {
+                pub const NAME: &str = #proto_name;
+                pub const TAG: u16 = #tag;
+                /// Whether the peer answers this protocol.
+                pub const EXPECTS_RESPONSE: bool = #expects_response;
+                #[doc = #request_doc]
+                pub type Request = #request;
+                #[doc = #response_doc]
+                pub type Response = #response;
+            }
+        }
+    }
+
+    /// Whether a struct field must be boxed: its type contains the field's
+    /// own type through non-array struct fields.
+    fn needs_box(&self, owner: usize, field: &Field) -> bool {
+        let FieldType::Struct(target) = field.field_type else {
+            return false;
+        };
+        if field.is_array {
+            return false;
+        }
+        let mut stack = vec![target];
+        let mut visited = vec![false; self.sproto.types_list.len()];
+        while let Some(idx) = stack.pop() {
+            if idx == owner {
+                return true;
+            }
+            if std::mem::replace(&mut visited[idx], true) {
+                continue;
+            }
+            for f in &self.sproto.types_list[idx].fields {
+                if let (FieldType::Struct(next), false) = (&f.field_type, f.is_array) {
+                    stack.push(*next);
+                }
+            }
+        }
+        false
+    }
+
+    fn elem_type(&self, field: &Field) -> Elem {
+        match &field.field_type {
+            FieldType::Integer if field.decimal_precision > 0 => Elem::Decimal,
+            FieldType::Integer => Elem::Integer,
+            FieldType::Boolean => Elem::Boolean,
+            FieldType::String => Elem::String,
+            FieldType::Binary => Elem::Binary,
+            FieldType::Double => Elem::Double,
+            FieldType::Struct(idx) => Elem::Struct(self.type_idents[*idx].clone()),
+        }
+    }
+
+    /// The field's type as written in a `.sproto` file, with full type names.
+    fn type_spec(&self, field: &Field) -> String {
+        let base = match &field.field_type {
+            FieldType::Integer if field.decimal_precision > 0 => {
+                format!("integer({})", field.decimal_precision.ilog10())
+            }
+            FieldType::Integer => "integer".to_string(),
+            FieldType::Boolean => "boolean".to_string(),
+            FieldType::String => "string".to_string(),
+            FieldType::Binary => "binary".to_string(),
+            FieldType::Double => "double".to_string(),
+            FieldType::Struct(idx) => {
+                let sub_type = &self.sproto.types_list[*idx];
+                let key = u16::try_from(field.key_tag)
+                    .ok()
+                    .and_then(|tag| sub_type.find_field_by_tag(tag));
+                match key {
+                    _ if field.is_map => format!("{}()", sub_type.name),
+                    Some(key) => format!("{}({})", sub_type.name, key.name),
+                    None => sub_type.name.clone(),
+                }
+            }
+        };
+        if field.is_array {
+            format!("*{}", base)
+        } else {
+            base
+        }
+    }
+}
+
+fn collision(kind: &str, first: &str, second: &str, ident: &str) -> CodegenError {
+    CodegenError::NameCollision {
+        kind: kind.to_string(),
+        first: first.to_string(),
+        second: second.to_string(),
+        ident: ident.to_string(),
+    }
+}
+
+/// Element type of a field.
+enum Elem {
+    Integer,
+    /// `integer(N)`, as `f64`.
+    Decimal,
+    Boolean,
+    Double,
+    String,
+    Binary,
+    Struct(Ident),
+}
+
+impl Elem {
+    fn rust_type(&self) -> TokenStream {
+        match self {
+            Elem::Integer => quote!(i64),
+            Elem::Decimal | Elem::Double => quote!(f64),
+            Elem::Boolean => quote!(bool),
+            Elem::String => quote!(::std::string::String),
+            Elem::Binary => quote!(::std::vec::Vec<u8>),
+            Elem::Struct(ty) => quote!(#ty),
+        }
+    }
+}
+
+struct FieldGen<'a> {
+    field: &'a Field,
+    ident: Ident,
+    /// Stored as `Box<T>` to break a recursive type.
+    boxed: bool,
+    elem: Elem,
+}
+
+impl FieldGen<'_> {
+    /// The type inside the field's `Option`.
+    fn rust_type(&self) -> TokenStream {
+        let elem = self.elem.rust_type();
+        if self.field.is_array {
+            quote!(::std::vec::Vec<#elem>)
+        } else if self.boxed {
+            quote!(::std::boxed::Box<#elem>)
+        } else {
+            elem
+        }
+    }
+
+    /// Write `v: &T` into `enc`.
+    fn encode(&self) -> TokenStream {
+        let tag = Literal::u16_unsuffixed(self.field.tag);
+        if self.field.is_array {
+            return match &self.elem {
+                Elem::Integer => quote! { enc.set_integer_array(#tag, v)?; },
+                Elem::Decimal => quote! { enc.set_decimal_array(#tag, v)?; },
+                Elem::Boolean => quote! { enc.set_bool_array(#tag, v)?; },
+                Elem::Double => quote! { enc.set_double_array(#tag, v)?; },
+                Elem::String => quote! { enc.set_string_array(#tag, v)?; },
+                Elem::Binary => quote! { enc.set_bytes_array(#tag, v)?; },
+                Elem::Struct(ty) => quote! {
+                    enc.encode_struct_array(#tag, |arr| {
+                        for x in v {
+                            arr.encode_element(|e| {
+                                <#ty as ::sproto::codec::SprotoEncode>::encode_fields(x, e)
+                            })?;
+                        }
+                        ::core::result::Result::Ok(())
+                    })?;
+                },
+            };
+        }
+        match &self.elem {
+            Elem::Integer => quote! { enc.set_integer(#tag, *v)?; },
+            Elem::Decimal => quote! { enc.set_decimal(#tag, *v)?; },
+            Elem::Boolean => quote! { enc.set_bool(#tag, *v)?; },
+            Elem::Double => quote! { enc.set_double(#tag, *v)?; },
+            Elem::String => quote! { enc.set_string(#tag, v)?; },
+            Elem::Binary => quote! { enc.set_bytes(#tag, v)?; },
+            Elem::Struct(ty) => quote! {
+                enc.encode_nested(#tag, |sub| {
+                    <#ty as ::sproto::codec::SprotoEncode>::encode_fields(v, sub)
+                })?;
+            },
+        }
+    }
+
+    /// Read `f: DecodedField` into `msg`.
+    fn decode(&self) -> TokenStream {
+        let ident = &self.ident;
+        let value = if self.field.is_array {
+            match &self.elem {
+                Elem::Integer => quote! { f.as_integer_array()? },
+                Elem::Decimal => quote! { f.as_decimal_array()? },
+                Elem::Boolean => quote! { f.as_bool_array() },
+                Elem::Double => quote! { f.as_double_array()? },
+                Elem::String => quote! {
+                    f.as_string_array()?
+                        .into_iter()
+                        .map(::std::borrow::ToOwned::to_owned)
+                        .collect()
+                },
+                Elem::Binary => quote! {
+                    f.as_bytes_array()?.into_iter().map(|b| b.to_vec()).collect()
+                },
+                Elem::Struct(ty) => quote! {
+                    f.as_struct_iter()?
+                        .map(|d| <#ty as ::sproto::codec::SprotoDecode>::decode_fields(&mut d?))
+                        .collect::<::core::result::Result<_, _>>()?
+                },
+            }
+        } else {
+            match &self.elem {
+                Elem::Integer => quote! { f.as_integer()? },
+                Elem::Decimal => quote! { f.as_decimal()? },
+                Elem::Boolean => quote! { f.as_bool()? },
+                Elem::Double => quote! { f.as_double()? },
+                Elem::String => quote! { ::std::borrow::ToOwned::to_owned(f.as_string()?) },
+                Elem::Binary => quote! { f.as_bytes().to_vec() },
+                Elem::Struct(ty) => {
+                    let value = quote! {
+                        <#ty as ::sproto::codec::SprotoDecode>::decode_fields(&mut f.as_struct()?)?
+                    };
+                    if self.boxed {
+                        quote!(::std::boxed::Box::new(#value))
+                    } else {
+                        value
+                    }
+                }
+            }
+        };
+        quote! { msg.#ident = ::core::option::Option::Some(#value); }
+    }
+}
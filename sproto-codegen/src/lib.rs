@@ -0,0 +1,137 @@
+//! Rust code generator for sproto schemas.
+//!
+//! Turns a `Sproto` into Rust source: one struct per type, with
+//! `SprotoEncode`, `SprotoDecode` and `SprotoSchema` impls that call
+//! `StructEncoder` / `StructDecoder` by tag, and one module per protocol
+//! holding its tag and request/response type aliases. Meant to be run from
+//! `build.rs`:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     println!("cargo:rerun-if-changed=proto/game.sproto");
+//!     let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("game.rs");
+//!     sproto_codegen::Generator::new()
+//!         .compile("proto/game.sproto", out)
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! ```ignore
+//! // src/game.rs
+//! include!(concat!(env!("OUT_DIR"), "/game.rs"));
+//! ```
+//!
+//! For a schema such as
+//!
+//! ```text
+//! .Person {
+//!     .PhoneNumber { number 0 : string  type 1 : integer }
+//!     name 0 : string
+//!     phones 1 : *PhoneNumber(number)
+//!     balance 2 : integer(2)
+//! }
+//! login 1 { request Person  response { ok 0 : boolean } }
+//! ```
+//!
+//! the output contains structs `Person`, `PersonPhoneNumber` and
+//! `LoginResponse` (type names with the dots dropped and each part
+//! capitalised) whose fields are all `Option`s, since any sproto field may
+//! be absent, and
+//!
+//! ```ignore
+//! pub mod login {
+//!     pub const NAME: &str = "login";
+//!     pub const TAG: u16 = 1;
+//!     pub const EXPECTS_RESPONSE: bool = true;
+//!     pub type Request = super::Person;
+//!     pub type Response = super::LoginResponse;
+//! }
+//! ```
+//!
+//! Field types map as `integer` → `i64`, `integer(N)` → `f64`, `double` →
+//! `f64`, `boolean` → `bool`, `string` → `String`, `binary` → `Vec<u8>`,
+//! `*T` → `Vec<T>`. Maps (`*T(key)`, `*T()`) are plain `Vec<T>`; their
+//! wire form is the same struct array. A struct field that would make a
+//! type contain itself is boxed. Protocols without a request or response
+//! use `()`.
+
+use std::fs;
+use std::path::Path;
+
+use sproto::Sproto;
+
+mod error;
+mod expand;
+mod names;
+
+pub use error::CodegenError;
+
+/// Code generator settings.
+#[derive(Debug, Clone, Default)]
+pub struct Generator {
+    derives: Vec<String>,
+}
+
+impl Generator {
+    /// A generator with the default derives, `Debug, Clone, Default,
+    /// PartialEq`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a derive to every generated struct, e.g. `serde::Serialize`.
+    pub fn with_derive(mut self, path: &str) -> Self {
+        self.derives.push(path.to_string());
+        self
+    }
+
+    /// Generate Rust source for `sproto`.
+    pub fn generate(&self, sproto: &Sproto) -> Result<String, CodegenError> {
+        let derives = self
+            .derives
+            .iter()
+            .map(|d| {
+                syn::parse_str::<syn::Path>(d).map_err(|_| CodegenError::InvalidDerive(d.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let tokens = expand::Expander::new(sproto, &derives)?.expand()?;
+        let file: syn::File = syn::parse2(tokens).expect("generated code is valid Rust syntax");
+        Ok(format!(
+            "// Generated by sproto-codegen. Do not edit.\n\n{}",
+            prettyplease::unparse(&file)
+        ))
+    }
+
+    /// Parse the `.sproto` file `schema` and write the generated source to
+    /// `output`. The file is left untouched if its contents are already up
+    /// to date, so dependent crates are not rebuilt needlessly.
+    pub fn compile(
+        &self,
+        schema: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> Result<(), CodegenError> {
+        let (schema, output) = (schema.as_ref(), output.as_ref());
+        let text = fs::read_to_string(schema).map_err(|source| CodegenError::Io {
+            path: schema.to_path_buf(),
+            source,
+        })?;
+        let sproto = sproto::parser::parse(&text).map_err(|source| CodegenError::Parse {
+            path: schema.to_path_buf(),
+            source,
+        })?;
+        let code = self.generate(&sproto)?;
+        if fs::read_to_string(output).is_ok_and(|old| old == code) {
+            return Ok(());
+        }
+        fs::write(output, code).map_err(|source| CodegenError::Io {
+            path: output.to_path_buf(),
+            source,
+        })
+    }
+}
+
+/// Generate Rust source for `sproto` with the default settings.
+pub fn generate(sproto: &Sproto) -> Result<String, CodegenError> {
+    Generator::new().generate(sproto)
+}
@@ -0,0 +1,75 @@
+//! Rust identifiers for schema names.
+
+use proc_macro2::{Ident, Span};
+
+/// Keywords that are only usable as raw identifiers.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Struct name for a type: `Person.PhoneNumber` → `PersonPhoneNumber`,
+/// `foobar_request` → `FoobarRequest`.
+pub fn type_name(name: &str) -> String {
+    name.split(['.', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Field or module name: `userId` → `user_id`, `HTTPCode` → `http_code`.
+pub fn snake_name(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+/// An identifier for `name`, raw if it is a keyword. `self`, `Self`,
+/// `super` and `crate` cannot be raw and get a trailing `_` instead.
+pub fn ident(name: &str) -> Ident {
+    match name {
+        "self" | "Self" | "super" | "crate" | "_" => {
+            Ident::new(&format!("{}_", name), Span::call_site())
+        }
+        _ if KEYWORDS.contains(&name) => Ident::new_raw(name, Span::call_site()),
+        _ => Ident::new(name, Span::call_site()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(type_name("Person.PhoneNumber"), "PersonPhoneNumber");
+        assert_eq!(type_name("foobar_request"), "FoobarRequest");
+        assert_eq!(type_name("login.request"), "LoginRequest");
+        assert_eq!(snake_name("userId"), "user_id");
+        assert_eq!(snake_name("HTTPCode"), "http_code");
+        assert_eq!(snake_name("phone2Number"), "phone2_number");
+        assert_eq!(snake_name("name"), "name");
+        assert_eq!(ident("type").to_string(), "r#type");
+        assert_eq!(ident("self").to_string(), "self_");
+        assert_eq!(ident("Self").to_string(), "Self_");
+        assert_eq!(ident("phones").to_string(), "phones");
+    }
+}
@@ -0,0 +1,350 @@
+//! Code generator tests.
+//!
+//! `tests/generated/` holds the generator output for `tests/testdata/schema.sproto`
+//! of the main crate and for `tests/game.sproto`; the first test checks it is
+//! current (run with `SPROTO_CODEGEN_BLESS=1` to rewrite it after changing the
+//! generator). The generated types are then compiled in and exercised against
+//! the C/Lua binary fixtures and the parsed schemas.
+
+use sproto::codec::{SprotoDecode, SprotoEncode, SprotoSchema};
+use sproto::error::SchemaError;
+use sproto::types::Sproto;
+use sproto::value::{decode_value, Value};
+use sproto::{binary_schema, compat, parser};
+use sproto_codegen::{CodegenError, Generator};
+
+#[allow(dead_code)]
+mod schema {
+    include!("generated/schema.rs");
+}
+
+#[allow(dead_code)]
+mod game {
+    include!("generated/game.rs");
+}
+
+fn path(relative: &str) -> String {
+    format!("{}/{}", env!("CARGO_MANIFEST_DIR"), relative)
+}
+
+fn read(relative: &str) -> Vec<u8> {
+    let path = path(relative);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+fn parse(relative: &str) -> Sproto {
+    parser::parse(&String::from_utf8(read(relative)).unwrap()).unwrap()
+}
+
+fn encode<T: SprotoEncode + SprotoSchema>(sproto: &Sproto, value: &T) -> Vec<u8> {
+    let st = sproto.get_type(T::TYPE_NAME).unwrap();
+    let mut buf = Vec::new();
+    value.encode_to(sproto, st, &mut buf).unwrap();
+    buf
+}
+
+fn decode<T: SprotoDecode + SprotoSchema>(sproto: &Sproto, data: &[u8]) -> T {
+    let st = sproto.get_type(T::TYPE_NAME).unwrap();
+    T::decode_from(sproto, st, data).unwrap()
+}
+
+// =============================================================================
+// Generated output
+// =============================================================================
+
+#[test]
+fn test_generated_code_is_up_to_date() {
+    for (schema, generated) in [
+        (
+            "../tests/testdata/schema.sproto",
+            "tests/generated/schema.rs",
+        ),
+        ("tests/game.sproto", "tests/generated/game.rs"),
+    ] {
+        let code = sproto_codegen::generate(&parse(schema)).unwrap();
+        if std::env::var_os("SPROTO_CODEGEN_BLESS").is_some() {
+            std::fs::write(path(generated), &code).unwrap();
+        }
+        assert!(
+            String::from_utf8(read(generated)).unwrap() == code,
+            "{} is stale; rerun with SPROTO_CODEGEN_BLESS=1",
+            generated
+        );
+    }
+}
+
+#[test]
+fn test_compile_writes_output() {
+    let dir = std::env::temp_dir().join(format!("sproto-codegen-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let out = dir.join("game.rs");
+
+    let generator = Generator::new()
+        .with_derive("Eq")
+        .with_derive("::std::hash::Hash");
+    generator.compile(path("tests/game.sproto"), &out).unwrap();
+    let code = std::fs::read_to_string(&out).unwrap();
+    assert_eq!(
+        code,
+        generator.generate(&parse("tests/game.sproto")).unwrap()
+    );
+    assert!(code.contains("#[derive(Debug, Clone, Default, PartialEq, Eq, ::std::hash::Hash)]"));
+
+    let bad = dir.join("bad.sproto");
+    std::fs::write(&bad, ".A {\n  x 0 : Missing\n}\n").unwrap();
+    let err = generator.compile(&bad, &out).unwrap_err();
+    assert!(matches!(err, CodegenError::Parse { .. }));
+    assert!(
+        err.to_string()
+            .starts_with(&format!("{}:2:9:", bad.display())),
+        "{}",
+        err
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_name_collisions() {
+    let err = sproto_codegen::generate(
+        &parser::parse(".LoginRequest {}\nlogin 1 { request { a 0 : integer } }").unwrap(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "types 'LoginRequest' and 'login.request' both map to the Rust name 'LoginRequest'"
+    );
+
+    let err = sproto_codegen::generate(
+        &parser::parse(".A { userId 0 : integer\n user_id 1 : string }").unwrap(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "fields of 'A' 'userId' and 'user_id' both map to the Rust name 'user_id'"
+    );
+
+    let err = Generator::new()
+        .with_derive("not a path")
+        .generate(&Sproto::new())
+        .unwrap_err();
+    assert!(matches!(err, CodegenError::InvalidDerive(ref d) if d == "not a path"));
+}
+
+// =============================================================================
+// Binary fixture cross-validation
+// =============================================================================
+
+fn phone(number: &str, kind: i64) -> schema::PhoneNumber {
+    schema::PhoneNumber {
+        number: Some(number.into()),
+        r#type: Some(kind),
+    }
+}
+
+fn full_person() -> schema::Person {
+    schema::Person {
+        name: Some("Alice".into()),
+        age: Some(30),
+        active: Some(true),
+        score: Some(0.01171875),
+        photo: Some(vec![0xDE, 0xAD, 0xBE, 0xEF]),
+        fpn: Some(1.82),
+        id: Some(10000),
+        phone: Some(phone("123456789", 1)),
+        phones: Some(vec![phone("123456789", 1), phone("87654321", 2)]),
+        children: Some(vec![schema::Person {
+            name: Some("Bob".into()),
+            age: Some(5),
+            ..Default::default()
+        }]),
+        tags: Some(vec![
+            "hello".into(),
+            "world".into(),
+            "\u{4f60}\u{597d}".into(),
+        ]),
+        numbers: Some(vec![1, 2, 3, 4, 5]),
+        flags: Some(vec![false, true, false]),
+        values: Some(vec![0.01171875, 23.0, 4.0]),
+    }
+}
+
+#[test]
+fn test_codegen_matches_c_fixtures() {
+    let sproto = binary_schema::load_binary(&read("../tests/testdata/schema.bin")).unwrap();
+    let expected = read("../tests/testdata/full_encoded.bin");
+    assert_eq!(encode(&sproto, &full_person()), expected);
+    assert_eq!(decode::<schema::Person>(&sproto, &expected), full_person());
+
+    // The registered schema encodes identically to the C-compiled one.
+    let mut registered = Sproto::new();
+    schema::Person::register(&mut registered);
+    assert!(compat::check(&sproto, &registered).changes.is_empty());
+    assert_eq!(encode(&registered, &full_person()), expected);
+}
+
+// =============================================================================
+// Generated types
+// =============================================================================
+
+fn player() -> game::Player {
+    game::Player {
+        name: Some("ann".into()),
+        level: Some(7),
+        gold: Some(12.5),
+        items: Some(vec![
+            game::PlayerItem {
+                id: Some(1),
+                count: Some(3),
+            },
+            game::PlayerItem {
+                id: Some(2),
+                count: None,
+            },
+        ]),
+        attrs: Some(vec![game::Attr {
+            key: Some("speed".into()),
+            value: Some(1.5),
+        }]),
+        guild: Some(Box::new(game::Guild {
+            name: Some("red".into()),
+            leader: Some(Box::new(game::Player {
+                name: Some("bob".into()),
+                ..Default::default()
+            })),
+            members: Some(vec![game::Player::default()]),
+        })),
+        mentor: Some(Box::new(game::Player {
+            level: Some(99),
+            ..Default::default()
+        })),
+        r#type: Some(2),
+        avatar_url: Some("https://example.com/a.png".into()),
+        prices: Some(vec![0.99, 100.0]),
+        blobs: Some(vec![vec![], vec![1, 2, 3]]),
+        online: Some(false),
+        ratio: Some(-0.25),
+    }
+}
+
+#[test]
+fn test_codegen_round_trip() {
+    let sproto = parse("tests/game.sproto");
+    let value = player();
+    let bytes = encode(&sproto, &value);
+    assert_eq!(decode::<game::Player>(&sproto, &bytes), value);
+
+    // Map fields are plain struct arrays on the wire.
+    let decoded = decode_value(&sproto, sproto.get_type("Player").unwrap(), &bytes).unwrap();
+    match decoded.get("items") {
+        Some(Value::Map(entries)) => assert_eq!(entries[1].0, Value::Integer(2)),
+        other => panic!("expected a map, got {:?}", other),
+    }
+    assert_eq!(
+        decoded.get("attrs"),
+        Some(&Value::Map(vec![(
+            Value::String("speed".into()),
+            Value::Double(1.5)
+        )]))
+    );
+
+    assert_eq!(
+        decode::<game::Empty>(&sproto, &bytes),
+        game::Empty {},
+        "unknown fields are skipped"
+    );
+}
+
+#[test]
+fn test_codegen_register_matches_parsed_schema() {
+    let parsed = parse("tests/game.sproto");
+    let mut registered = Sproto::new();
+    game::Player::register(&mut registered);
+    game::LoginRequest::register(&mut registered);
+    game::Empty::register(&mut registered);
+
+    // `register` covers types only, so the protocols are all that differ.
+    let report = compat::check(&parsed, &registered);
+    assert_eq!(report.changes.len(), 4, "{}", report);
+    assert!(report
+        .changes
+        .iter()
+        .all(|c| matches!(c, compat::Change::ProtocolRemoved { .. })));
+    assert_eq!(encode(&registered, &player()), encode(&parsed, &player()));
+}
+
+#[test]
+fn test_codegen_register_checks_existing_types() {
+    // Every type is already parsed, through the `Player` / `Guild` cycle too.
+    let mut parsed = parse("tests/game.sproto");
+    let count = parsed.types_list.len();
+    let idx = game::Player::try_register(&mut parsed).unwrap();
+    assert_eq!(parsed.types_list[idx].name, "Player");
+    assert_eq!(parsed.types_list.len(), count);
+
+    // A referenced type whose field drifted from the generated code.
+    let text = String::from_utf8(read("tests/game.sproto")).unwrap();
+    let drifted = text.replace("value 1 : double", "value 1 : string");
+    let mut drifted = parser::parse(&drifted).unwrap();
+    assert_eq!(
+        game::Player::try_register(&mut drifted).unwrap_err(),
+        SchemaError::FieldMismatch {
+            type_name: "Attr".into(),
+            field: "value".into(),
+            tag: 1,
+        }
+    );
+}
+
+#[test]
+fn test_codegen_protocol_modules() {
+    let parsed = parse("tests/game.sproto");
+    for (name, tag, expects_response) in [
+        (
+            game::login::NAME,
+            game::login::TAG,
+            game::login::EXPECTS_RESPONSE,
+        ),
+        (
+            game::logout::NAME,
+            game::logout::TAG,
+            game::logout::EXPECTS_RESPONSE,
+        ),
+        (
+            game::heartbeat::NAME,
+            game::heartbeat::TAG,
+            game::heartbeat::EXPECTS_RESPONSE,
+        ),
+        (
+            game::kick::NAME,
+            game::kick::TAG,
+            game::kick::EXPECTS_RESPONSE,
+        ),
+    ] {
+        let proto = parsed.get_protocol(name).unwrap();
+        assert_eq!(proto.tag, tag, "{}", name);
+        assert_eq!(proto.expects_response(), expects_response, "{}", name);
+    }
+
+    let request: game::login::Request = game::LoginRequest {
+        name: Some("ann".into()),
+        token: Some(vec![0xff]),
+    };
+    let response: game::login::Response = player();
+    let () = <game::logout::Request>::default();
+    let () = <game::logout::Response>::default();
+    let _: game::kick::Request = game::Empty {};
+
+    let request_type = &parsed.types_list[parsed.get_protocol("login").unwrap().request.unwrap()];
+    assert_eq!(
+        request_type.name,
+        <game::login::Request as SprotoSchema>::TYPE_NAME
+    );
+    let mut buf = Vec::new();
+    request.encode_to(&parsed, request_type, &mut buf).unwrap();
+    assert_eq!(
+        game::login::Request::decode_from(&parsed, request_type, &buf).unwrap(),
+        request
+    );
+    assert_eq!(encode(&parsed, &response), encode(&parsed, &player()));
+}
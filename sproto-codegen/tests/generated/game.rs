@@ -0,0 +1,700 @@
+// Generated by sproto-codegen. Do not edit.
+
+/// `.Attr`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attr {
+    /// `key 0 : string`
+    pub key: ::core::option::Option<::std::string::String>,
+    /// `value 1 : double`
+    pub value: ::core::option::Option<f64>,
+}
+impl ::sproto::codec::SprotoEncode for Attr {
+    fn encode_fields(
+        &self,
+        enc: &mut ::sproto::codec::StructEncoder,
+    ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+        if let ::core::option::Option::Some(v) = &self.key {
+            enc.set_string(0, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.value {
+            enc.set_double(1, *v)?;
+        }
+        ::core::result::Result::Ok(())
+    }
+}
+impl ::sproto::codec::SprotoDecode for Attr {
+    fn decode_fields(
+        dec: &mut ::sproto::codec::StructDecoder,
+    ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+        let mut msg = Self::default();
+        while let ::core::option::Option::Some(f) = dec.next_field()? {
+            match f.tag() {
+                0 => {
+                    msg.key = ::core::option::Option::Some(
+                        ::std::borrow::ToOwned::to_owned(f.as_string()?),
+                    );
+                }
+                1 => {
+                    msg.value = ::core::option::Option::Some(f.as_double()?);
+                }
+                _ => {}
+            }
+        }
+        ::core::result::Result::Ok(msg)
+    }
+}
+impl ::sproto::codec::SprotoSchema for Attr {
+    const TYPE_NAME: &'static str = "Attr";
+    fn register(sproto: &mut ::sproto::Sproto) -> usize {
+        <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+            .unwrap_or_else(|err| ::core::panic!("{}", err))
+    }
+    fn register_with(
+        sproto: &mut ::sproto::Sproto,
+        visiting: &mut ::std::vec::Vec<&'static str>,
+    ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+        let existing = sproto.get_type_index("Attr");
+        if let ::core::option::Option::Some(idx) = existing
+            .filter(|_| visiting.contains(&"Attr"))
+        {
+            return ::core::result::Result::Ok(idx);
+        }
+        let idx = match existing {
+            ::core::option::Option::Some(idx) => idx,
+            ::core::option::Option::None => {
+                sproto.add_type("Attr", ::std::vec::Vec::new())
+            }
+        };
+        visiting.push("Attr");
+        let fields = ::std::vec![
+            ::sproto::types::Field::new("key", 0, ::sproto::types::FieldType::String),
+            ::sproto::types::Field::new("value", 1, ::sproto::types::FieldType::Double)
+        ];
+        visiting.pop();
+        match existing {
+            ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+            ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+        }
+        ::core::result::Result::Ok(idx)
+    }
+}
+/// `.Empty`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Empty {}
+impl ::sproto::codec::SprotoEncode for Empty {
+    fn encode_fields(
+        &self,
+        _enc: &mut ::sproto::codec::StructEncoder,
+    ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+        ::core::result::Result::Ok(())
+    }
+}
+impl ::sproto::codec::SprotoDecode for Empty {
+    fn decode_fields(
+        dec: &mut ::sproto::codec::StructDecoder,
+    ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+        while dec.next_field()?.is_some() {}
+        ::core::result::Result::Ok(Self {})
+    }
+}
+impl ::sproto::codec::SprotoSchema for Empty {
+    const TYPE_NAME: &'static str = "Empty";
+    fn register(sproto: &mut ::sproto::Sproto) -> usize {
+        <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+            .unwrap_or_else(|err| ::core::panic!("{}", err))
+    }
+    fn register_with(
+        sproto: &mut ::sproto::Sproto,
+        visiting: &mut ::std::vec::Vec<&'static str>,
+    ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+        let existing = sproto.get_type_index("Empty");
+        if let ::core::option::Option::Some(idx) = existing
+            .filter(|_| visiting.contains(&"Empty"))
+        {
+            return ::core::result::Result::Ok(idx);
+        }
+        let idx = match existing {
+            ::core::option::Option::Some(idx) => idx,
+            ::core::option::Option::None => {
+                sproto.add_type("Empty", ::std::vec::Vec::new())
+            }
+        };
+        visiting.push("Empty");
+        let fields = ::std::vec![];
+        visiting.pop();
+        match existing {
+            ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+            ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+        }
+        ::core::result::Result::Ok(idx)
+    }
+}
+/// `.Guild`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Guild {
+    /// `name 0 : string`
+    pub name: ::core::option::Option<::std::string::String>,
+    /// `leader 1 : Player`
+    pub leader: ::core::option::Option<::std::boxed::Box<Player>>,
+    /// `members 2 : *Player`
+    pub members: ::core::option::Option<::std::vec::Vec<Player>>,
+}
+impl ::sproto::codec::SprotoEncode for Guild {
+    fn encode_fields(
+        &self,
+        enc: &mut ::sproto::codec::StructEncoder,
+    ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+        if let ::core::option::Option::Some(v) = &self.name {
+            enc.set_string(0, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.leader {
+            enc.encode_nested(
+                1,
+                |sub| {
+                    <Player as ::sproto::codec::SprotoEncode>::encode_fields(v, sub)
+                },
+            )?;
+        }
+        if let ::core::option::Option::Some(v) = &self.members {
+            enc.encode_struct_array(
+                2,
+                |arr| {
+                    for x in v {
+                        arr.encode_element(|e| {
+                            <Player as ::sproto::codec::SprotoEncode>::encode_fields(
+                                x,
+                                e,
+                            )
+                        })?;
+                    }
+                    ::core::result::Result::Ok(())
+                },
+            )?;
+        }
+        ::core::result::Result::Ok(())
+    }
+}
+impl ::sproto::codec::SprotoDecode for Guild {
+    fn decode_fields(
+        dec: &mut ::sproto::codec::StructDecoder,
+    ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+        let mut msg = Self::default();
+        while let ::core::option::Option::Some(f) = dec.next_field()? {
+            match f.tag() {
+                0 => {
+                    msg.name = ::core::option::Option::Some(
+                        ::std::borrow::ToOwned::to_owned(f.as_string()?),
+                    );
+                }
+                1 => {
+                    msg.leader = ::core::option::Option::Some(
+                        ::std::boxed::Box::new(
+                            <Player as ::sproto::codec::SprotoDecode>::decode_fields(
+                                &mut f.as_struct()?,
+                            )?,
+                        ),
+                    );
+                }
+                2 => {
+                    msg.members = ::core::option::Option::Some(
+                        f
+                            .as_struct_iter()?
+                            .map(|d| <Player as ::sproto::codec::SprotoDecode>::decode_fields(
+                                &mut d?,
+                            ))
+                            .collect::<::core::result::Result<_, _>>()?,
+                    );
+                }
+                _ => {}
+            }
+        }
+        ::core::result::Result::Ok(msg)
+    }
+}
+impl ::sproto::codec::SprotoSchema for Guild {
+    const TYPE_NAME: &'static str = "Guild";
+    fn register(sproto: &mut ::sproto::Sproto) -> usize {
+        <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+            .unwrap_or_else(|err| ::core::panic!("{}", err))
+    }
+    fn register_with(
+        sproto: &mut ::sproto::Sproto,
+        visiting: &mut ::std::vec::Vec<&'static str>,
+    ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+        let existing = sproto.get_type_index("Guild");
+        if let ::core::option::Option::Some(idx) = existing
+            .filter(|_| visiting.contains(&"Guild"))
+        {
+            return ::core::result::Result::Ok(idx);
+        }
+        let idx = match existing {
+            ::core::option::Option::Some(idx) => idx,
+            ::core::option::Option::None => {
+                sproto.add_type("Guild", ::std::vec::Vec::new())
+            }
+        };
+        visiting.push("Guild");
+        let fields = ::std::vec![
+            ::sproto::types::Field::new("name", 0, ::sproto::types::FieldType::String),
+            ::sproto::types::Field::new("leader", 1, ::sproto::types::FieldType::Struct(<
+            Player as ::sproto::codec::SprotoSchema > ::register_with(sproto, visiting)
+            ?)), ::sproto::types::Field::array("members", 2,
+            ::sproto::types::FieldType::Struct(< Player as ::sproto::codec::SprotoSchema
+            > ::register_with(sproto, visiting) ?))
+        ];
+        visiting.pop();
+        match existing {
+            ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+            ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+        }
+        ::core::result::Result::Ok(idx)
+    }
+}
+/// `.Player`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Player {
+    /// `name 0 : string`
+    pub name: ::core::option::Option<::std::string::String>,
+    /// `level 1 : integer`
+    pub level: ::core::option::Option<i64>,
+    /// `gold 2 : integer(2)`
+    pub gold: ::core::option::Option<f64>,
+    /// `items 3 : *Player.Item(id)`
+    pub items: ::core::option::Option<::std::vec::Vec<PlayerItem>>,
+    /// `attrs 4 : *Attr()`
+    pub attrs: ::core::option::Option<::std::vec::Vec<Attr>>,
+    /// `guild 5 : Guild`
+    pub guild: ::core::option::Option<::std::boxed::Box<Guild>>,
+    /// `mentor 6 : Player`
+    pub mentor: ::core::option::Option<::std::boxed::Box<Player>>,
+    /// `type 7 : integer`
+    pub r#type: ::core::option::Option<i64>,
+    /// `avatarUrl 8 : string`
+    pub avatar_url: ::core::option::Option<::std::string::String>,
+    /// `prices 9 : *integer(2)`
+    pub prices: ::core::option::Option<::std::vec::Vec<f64>>,
+    /// `blobs 10 : *binary`
+    pub blobs: ::core::option::Option<::std::vec::Vec<::std::vec::Vec<u8>>>,
+    /// `online 11 : boolean`
+    pub online: ::core::option::Option<bool>,
+    /// `ratio 12 : double`
+    pub ratio: ::core::option::Option<f64>,
+}
+impl ::sproto::codec::SprotoEncode for Player {
+    fn encode_fields(
+        &self,
+        enc: &mut ::sproto::codec::StructEncoder,
+    ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+        if let ::core::option::Option::Some(v) = &self.name {
+            enc.set_string(0, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.level {
+            enc.set_integer(1, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.gold {
+            enc.set_decimal(2, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.items {
+            enc.encode_struct_array(
+                3,
+                |arr| {
+                    for x in v {
+                        arr.encode_element(|e| {
+                            <PlayerItem as ::sproto::codec::SprotoEncode>::encode_fields(
+                                x,
+                                e,
+                            )
+                        })?;
+                    }
+                    ::core::result::Result::Ok(())
+                },
+            )?;
+        }
+        if let ::core::option::Option::Some(v) = &self.attrs {
+            enc.encode_struct_array(
+                4,
+                |arr| {
+                    for x in v {
+                        arr.encode_element(|e| {
+                            <Attr as ::sproto::codec::SprotoEncode>::encode_fields(x, e)
+                        })?;
+                    }
+                    ::core::result::Result::Ok(())
+                },
+            )?;
+        }
+        if let ::core::option::Option::Some(v) = &self.guild {
+            enc.encode_nested(
+                5,
+                |sub| { <Guild as ::sproto::codec::SprotoEncode>::encode_fields(v, sub) },
+            )?;
+        }
+        if let ::core::option::Option::Some(v) = &self.mentor {
+            enc.encode_nested(
+                6,
+                |sub| {
+                    <Player as ::sproto::codec::SprotoEncode>::encode_fields(v, sub)
+                },
+            )?;
+        }
+        if let ::core::option::Option::Some(v) = &self.r#type {
+            enc.set_integer(7, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.avatar_url {
+            enc.set_string(8, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.prices {
+            enc.set_decimal_array(9, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.blobs {
+            enc.set_bytes_array(10, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.online {
+            enc.set_bool(11, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.ratio {
+            enc.set_double(12, *v)?;
+        }
+        ::core::result::Result::Ok(())
+    }
+}
+impl ::sproto::codec::SprotoDecode for Player {
+    fn decode_fields(
+        dec: &mut ::sproto::codec::StructDecoder,
+    ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+        let mut msg = Self::default();
+        while let ::core::option::Option::Some(f) = dec.next_field()? {
+            match f.tag() {
+                0 => {
+                    msg.name = ::core::option::Option::Some(
+                        ::std::borrow::ToOwned::to_owned(f.as_string()?),
+                    );
+                }
+                1 => {
+                    msg.level = ::core::option::Option::Some(f.as_integer()?);
+                }
+                2 => {
+                    msg.gold = ::core::option::Option::Some(f.as_decimal()?);
+                }
+                3 => {
+                    msg.items = ::core::option::Option::Some(
+                        f
+                            .as_struct_iter()?
+                            .map(|d| <PlayerItem as ::sproto::codec::SprotoDecode>::decode_fields(
+                                &mut d?,
+                            ))
+                            .collect::<::core::result::Result<_, _>>()?,
+                    );
+                }
+                4 => {
+                    msg.attrs = ::core::option::Option::Some(
+                        f
+                            .as_struct_iter()?
+                            .map(|d| <Attr as ::sproto::codec::SprotoDecode>::decode_fields(
+                                &mut d?,
+                            ))
+                            .collect::<::core::result::Result<_, _>>()?,
+                    );
+                }
+                5 => {
+                    msg.guild = ::core::option::Option::Some(
+                        ::std::boxed::Box::new(
+                            <Guild as ::sproto::codec::SprotoDecode>::decode_fields(
+                                &mut f.as_struct()?,
+                            )?,
+                        ),
+                    );
+                }
+                6 => {
+                    msg.mentor = ::core::option::Option::Some(
+                        ::std::boxed::Box::new(
+                            <Player as ::sproto::codec::SprotoDecode>::decode_fields(
+                                &mut f.as_struct()?,
+                            )?,
+                        ),
+                    );
+                }
+                7 => {
+                    msg.r#type = ::core::option::Option::Some(f.as_integer()?);
+                }
+                8 => {
+                    msg.avatar_url = ::core::option::Option::Some(
+                        ::std::borrow::ToOwned::to_owned(f.as_string()?),
+                    );
+                }
+                9 => {
+                    msg.prices = ::core::option::Option::Some(f.as_decimal_array()?);
+                }
+                10 => {
+                    msg.blobs = ::core::option::Option::Some(
+                        f.as_bytes_array()?.into_iter().map(|b| b.to_vec()).collect(),
+                    );
+                }
+                11 => {
+                    msg.online = ::core::option::Option::Some(f.as_bool()?);
+                }
+                12 => {
+                    msg.ratio = ::core::option::Option::Some(f.as_double()?);
+                }
+                _ => {}
+            }
+        }
+        ::core::result::Result::Ok(msg)
+    }
+}
+impl ::sproto::codec::SprotoSchema for Player {
+    const TYPE_NAME: &'static str = "Player";
+    fn register(sproto: &mut ::sproto::Sproto) -> usize {
+        <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+            .unwrap_or_else(|err| ::core::panic!("{}", err))
+    }
+    fn register_with(
+        sproto: &mut ::sproto::Sproto,
+        visiting: &mut ::std::vec::Vec<&'static str>,
+    ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+        let existing = sproto.get_type_index("Player");
+        if let ::core::option::Option::Some(idx) = existing
+            .filter(|_| visiting.contains(&"Player"))
+        {
+            return ::core::result::Result::Ok(idx);
+        }
+        let idx = match existing {
+            ::core::option::Option::Some(idx) => idx,
+            ::core::option::Option::None => {
+                sproto.add_type("Player", ::std::vec::Vec::new())
+            }
+        };
+        visiting.push("Player");
+        let fields = ::std::vec![
+            ::sproto::types::Field::new("name", 0, ::sproto::types::FieldType::String),
+            ::sproto::types::Field::new("level", 1, ::sproto::types::FieldType::Integer),
+            { let mut field = ::sproto::types::Field::new("gold", 2,
+            ::sproto::types::FieldType::Integer); field.decimal_precision = 100; field },
+            { let mut field = ::sproto::types::Field::array("items", 3,
+            ::sproto::types::FieldType::Struct(< PlayerItem as
+            ::sproto::codec::SprotoSchema > ::register_with(sproto, visiting) ?)); field
+            .key_tag = 0; field }, { let mut field =
+            ::sproto::types::Field::array("attrs", 4,
+            ::sproto::types::FieldType::Struct(< Attr as ::sproto::codec::SprotoSchema >
+            ::register_with(sproto, visiting) ?)); field.key_tag = 0; field.is_map =
+            true; field }, ::sproto::types::Field::new("guild", 5,
+            ::sproto::types::FieldType::Struct(< Guild as ::sproto::codec::SprotoSchema >
+            ::register_with(sproto, visiting) ?)), ::sproto::types::Field::new("mentor",
+            6, ::sproto::types::FieldType::Struct(< Player as
+            ::sproto::codec::SprotoSchema > ::register_with(sproto, visiting) ?)),
+            ::sproto::types::Field::new("type", 7, ::sproto::types::FieldType::Integer),
+            ::sproto::types::Field::new("avatarUrl", 8,
+            ::sproto::types::FieldType::String), { let mut field =
+            ::sproto::types::Field::array("prices", 9,
+            ::sproto::types::FieldType::Integer); field.decimal_precision = 100; field },
+            ::sproto::types::Field::array("blobs", 10,
+            ::sproto::types::FieldType::Binary), ::sproto::types::Field::new("online",
+            11, ::sproto::types::FieldType::Boolean),
+            ::sproto::types::Field::new("ratio", 12, ::sproto::types::FieldType::Double)
+        ];
+        visiting.pop();
+        match existing {
+            ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+            ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+        }
+        ::core::result::Result::Ok(idx)
+    }
+}
+/// `.Player.Item`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerItem {
+    /// `id 0 : integer`
+    pub id: ::core::option::Option<i64>,
+    /// `count 1 : integer`
+    pub count: ::core::option::Option<i64>,
+}
+impl ::sproto::codec::SprotoEncode for PlayerItem {
+    fn encode_fields(
+        &self,
+        enc: &mut ::sproto::codec::StructEncoder,
+    ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+        if let ::core::option::Option::Some(v) = &self.id {
+            enc.set_integer(0, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.count {
+            enc.set_integer(1, *v)?;
+        }
+        ::core::result::Result::Ok(())
+    }
+}
+impl ::sproto::codec::SprotoDecode for PlayerItem {
+    fn decode_fields(
+        dec: &mut ::sproto::codec::StructDecoder,
+    ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+        let mut msg = Self::default();
+        while let ::core::option::Option::Some(f) = dec.next_field()? {
+            match f.tag() {
+                0 => {
+                    msg.id = ::core::option::Option::Some(f.as_integer()?);
+                }
+                1 => {
+                    msg.count = ::core::option::Option::Some(f.as_integer()?);
+                }
+                _ => {}
+            }
+        }
+        ::core::result::Result::Ok(msg)
+    }
+}
+impl ::sproto::codec::SprotoSchema for PlayerItem {
+    const TYPE_NAME: &'static str = "Player.Item";
+    fn register(sproto: &mut ::sproto::Sproto) -> usize {
+        <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+            .unwrap_or_else(|err| ::core::panic!("{}", err))
+    }
+    fn register_with(
+        sproto: &mut ::sproto::Sproto,
+        visiting: &mut ::std::vec::Vec<&'static str>,
+    ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+        let existing = sproto.get_type_index("Player.Item");
+        if let ::core::option::Option::Some(idx) = existing
+            .filter(|_| visiting.contains(&"Player.Item"))
+        {
+            return ::core::result::Result::Ok(idx);
+        }
+        let idx = match existing {
+            ::core::option::Option::Some(idx) => idx,
+            ::core::option::Option::None => {
+                sproto.add_type("Player.Item", ::std::vec::Vec::new())
+            }
+        };
+        visiting.push("Player.Item");
+        let fields = ::std::vec![
+            ::sproto::types::Field::new("id", 0, ::sproto::types::FieldType::Integer),
+            ::sproto::types::Field::new("count", 1, ::sproto::types::FieldType::Integer)
+        ];
+        visiting.pop();
+        match existing {
+            ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+            ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+        }
+        ::core::result::Result::Ok(idx)
+    }
+}
+/// `.login.request`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoginRequest {
+    /// `name 0 : string`
+    pub name: ::core::option::Option<::std::string::String>,
+    /// `token 1 : binary`
+    pub token: ::core::option::Option<::std::vec::Vec<u8>>,
+}
+impl ::sproto::codec::SprotoEncode for LoginRequest {
+    fn encode_fields(
+        &self,
+        enc: &mut ::sproto::codec::StructEncoder,
+    ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+        if let ::core::option::Option::Some(v) = &self.name {
+            enc.set_string(0, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.token {
+            enc.set_bytes(1, v)?;
+        }
+        ::core::result::Result::Ok(())
+    }
+}
+impl ::sproto::codec::SprotoDecode for LoginRequest {
+    fn decode_fields(
+        dec: &mut ::sproto::codec::StructDecoder,
+    ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+        let mut msg = Self::default();
+        while let ::core::option::Option::Some(f) = dec.next_field()? {
+            match f.tag() {
+                0 => {
+                    msg.name = ::core::option::Option::Some(
+                        ::std::borrow::ToOwned::to_owned(f.as_string()?),
+                    );
+                }
+                1 => {
+                    msg.token = ::core::option::Option::Some(f.as_bytes().to_vec());
+                }
+                _ => {}
+            }
+        }
+        ::core::result::Result::Ok(msg)
+    }
+}
+impl ::sproto::codec::SprotoSchema for LoginRequest {
+    const TYPE_NAME: &'static str = "login.request";
+    fn register(sproto: &mut ::sproto::Sproto) -> usize {
+        <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+            .unwrap_or_else(|err| ::core::panic!("{}", err))
+    }
+    fn register_with(
+        sproto: &mut ::sproto::Sproto,
+        visiting: &mut ::std::vec::Vec<&'static str>,
+    ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+        let existing = sproto.get_type_index("login.request");
+        if let ::core::option::Option::Some(idx) = existing
+            .filter(|_| visiting.contains(&"login.request"))
+        {
+            return ::core::result::Result::Ok(idx);
+        }
+        let idx = match existing {
+            ::core::option::Option::Some(idx) => idx,
+            ::core::option::Option::None => {
+                sproto.add_type("login.request", ::std::vec::Vec::new())
+            }
+        };
+        visiting.push("login.request");
+        let fields = ::std::vec![
+            ::sproto::types::Field::new("name", 0, ::sproto::types::FieldType::String),
+            ::sproto::types::Field::new("token", 1, ::sproto::types::FieldType::Binary)
+        ];
+        visiting.pop();
+        match existing {
+            ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+            ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+        }
+        ::core::result::Result::Ok(idx)
+    }
+}
+/// Protocol `login 1`.
+pub mod login {
+    pub const NAME: &str = "login";
+    pub const TAG: u16 = 1;
+    /// Whether the peer answers this protocol.
+    pub const EXPECTS_RESPONSE: bool = true;
+    /// Request type `login.request`.
+    pub type Request = super::LoginRequest;
+    /// Response type `Player`.
+    pub type Response = super::Player;
+}
+/// Protocol `logout 2`.
+pub mod logout {
+    pub const NAME: &str = "logout";
+    pub const TAG: u16 = 2;
+    /// Whether the peer answers this protocol.
+    pub const EXPECTS_RESPONSE: bool = true;
+    /// No request body.
+    pub type Request = ();
+    /// Empty response (`response nil`).
+    pub type Response = ();
+}
+/// Protocol `heartbeat 3`.
+pub mod heartbeat {
+    pub const NAME: &str = "heartbeat";
+    pub const TAG: u16 = 3;
+    /// Whether the peer answers this protocol.
+    pub const EXPECTS_RESPONSE: bool = false;
+    /// No request body.
+    pub type Request = ();
+    /// No response.
+    pub type Response = ();
+}
+/// Protocol `kick 4`.
+pub mod kick {
+    pub const NAME: &str = "kick";
+    pub const TAG: u16 = 4;
+    /// Whether the peer answers this protocol.
+    pub const EXPECTS_RESPONSE: bool = false;
+    /// Request type `Empty`.
+    pub type Request = super::Empty;
+    /// No response.
+    pub type Response = ();
+}
@@ -0,0 +1,330 @@
+// Generated by sproto-codegen. Do not edit.
+
+/// `.Person`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Person {
+    /// `name 0 : string`
+    pub name: ::core::option::Option<::std::string::String>,
+    /// `age 1 : integer`
+    pub age: ::core::option::Option<i64>,
+    /// `active 2 : boolean`
+    pub active: ::core::option::Option<bool>,
+    /// `score 3 : double`
+    pub score: ::core::option::Option<f64>,
+    /// `photo 4 : binary`
+    pub photo: ::core::option::Option<::std::vec::Vec<u8>>,
+    /// `fpn 5 : integer(2)`
+    pub fpn: ::core::option::Option<f64>,
+    /// `id 6 : integer`
+    pub id: ::core::option::Option<i64>,
+    /// `phone 7 : PhoneNumber`
+    pub phone: ::core::option::Option<PhoneNumber>,
+    /// `phones 8 : *PhoneNumber`
+    pub phones: ::core::option::Option<::std::vec::Vec<PhoneNumber>>,
+    /// `children 9 : *Person`
+    pub children: ::core::option::Option<::std::vec::Vec<Person>>,
+    /// `tags 10 : *string`
+    pub tags: ::core::option::Option<::std::vec::Vec<::std::string::String>>,
+    /// `numbers 11 : *integer`
+    pub numbers: ::core::option::Option<::std::vec::Vec<i64>>,
+    /// `flags 12 : *boolean`
+    pub flags: ::core::option::Option<::std::vec::Vec<bool>>,
+    /// `values 13 : *double`
+    pub values: ::core::option::Option<::std::vec::Vec<f64>>,
+}
+impl ::sproto::codec::SprotoEncode for Person {
+    fn encode_fields(
+        &self,
+        enc: &mut ::sproto::codec::StructEncoder,
+    ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+        if let ::core::option::Option::Some(v) = &self.name {
+            enc.set_string(0, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.age {
+            enc.set_integer(1, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.active {
+            enc.set_bool(2, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.score {
+            enc.set_double(3, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.photo {
+            enc.set_bytes(4, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.fpn {
+            enc.set_decimal(5, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.id {
+            enc.set_integer(6, *v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.phone {
+            enc.encode_nested(
+                7,
+                |sub| {
+                    <PhoneNumber as ::sproto::codec::SprotoEncode>::encode_fields(v, sub)
+                },
+            )?;
+        }
+        if let ::core::option::Option::Some(v) = &self.phones {
+            enc.encode_struct_array(
+                8,
+                |arr| {
+                    for x in v {
+                        arr.encode_element(|e| {
+                            <PhoneNumber as ::sproto::codec::SprotoEncode>::encode_fields(
+                                x,
+                                e,
+                            )
+                        })?;
+                    }
+                    ::core::result::Result::Ok(())
+                },
+            )?;
+        }
+        if let ::core::option::Option::Some(v) = &self.children {
+            enc.encode_struct_array(
+                9,
+                |arr| {
+                    for x in v {
+                        arr.encode_element(|e| {
+                            <Person as ::sproto::codec::SprotoEncode>::encode_fields(
+                                x,
+                                e,
+                            )
+                        })?;
+                    }
+                    ::core::result::Result::Ok(())
+                },
+            )?;
+        }
+        if let ::core::option::Option::Some(v) = &self.tags {
+            enc.set_string_array(10, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.numbers {
+            enc.set_integer_array(11, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.flags {
+            enc.set_bool_array(12, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.values {
+            enc.set_double_array(13, v)?;
+        }
+        ::core::result::Result::Ok(())
+    }
+}
+impl ::sproto::codec::SprotoDecode for Person {
+    fn decode_fields(
+        dec: &mut ::sproto::codec::StructDecoder,
+    ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+        let mut msg = Self::default();
+        while let ::core::option::Option::Some(f) = dec.next_field()? {
+            match f.tag() {
+                0 => {
+                    msg.name = ::core::option::Option::Some(
+                        ::std::borrow::ToOwned::to_owned(f.as_string()?),
+                    );
+                }
+                1 => {
+                    msg.age = ::core::option::Option::Some(f.as_integer()?);
+                }
+                2 => {
+                    msg.active = ::core::option::Option::Some(f.as_bool()?);
+                }
+                3 => {
+                    msg.score = ::core::option::Option::Some(f.as_double()?);
+                }
+                4 => {
+                    msg.photo = ::core::option::Option::Some(f.as_bytes().to_vec());
+                }
+                5 => {
+                    msg.fpn = ::core::option::Option::Some(f.as_decimal()?);
+                }
+                6 => {
+                    msg.id = ::core::option::Option::Some(f.as_integer()?);
+                }
+                7 => {
+                    msg.phone = ::core::option::Option::Some(
+                        <PhoneNumber as ::sproto::codec::SprotoDecode>::decode_fields(
+                            &mut f.as_struct()?,
+                        )?,
+                    );
+                }
+                8 => {
+                    msg.phones = ::core::option::Option::Some(
+                        f
+                            .as_struct_iter()?
+                            .map(|d| <PhoneNumber as ::sproto::codec::SprotoDecode>::decode_fields(
+                                &mut d?,
+                            ))
+                            .collect::<::core::result::Result<_, _>>()?,
+                    );
+                }
+                9 => {
+                    msg.children = ::core::option::Option::Some(
+                        f
+                            .as_struct_iter()?
+                            .map(|d| <Person as ::sproto::codec::SprotoDecode>::decode_fields(
+                                &mut d?,
+                            ))
+                            .collect::<::core::result::Result<_, _>>()?,
+                    );
+                }
+                10 => {
+                    msg.tags = ::core::option::Option::Some(
+                        f
+                            .as_string_array()?
+                            .into_iter()
+                            .map(::std::borrow::ToOwned::to_owned)
+                            .collect(),
+                    );
+                }
+                11 => {
+                    msg.numbers = ::core::option::Option::Some(f.as_integer_array()?);
+                }
+                12 => {
+                    msg.flags = ::core::option::Option::Some(f.as_bool_array());
+                }
+                13 => {
+                    msg.values = ::core::option::Option::Some(f.as_double_array()?);
+                }
+                _ => {}
+            }
+        }
+        ::core::result::Result::Ok(msg)
+    }
+}
+impl ::sproto::codec::SprotoSchema for Person {
+    const TYPE_NAME: &'static str = "Person";
+    fn register(sproto: &mut ::sproto::Sproto) -> usize {
+        <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+            .unwrap_or_else(|err| ::core::panic!("{}", err))
+    }
+    fn register_with(
+        sproto: &mut ::sproto::Sproto,
+        visiting: &mut ::std::vec::Vec<&'static str>,
+    ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+        let existing = sproto.get_type_index("Person");
+        if let ::core::option::Option::Some(idx) = existing
+            .filter(|_| visiting.contains(&"Person"))
+        {
+            return ::core::result::Result::Ok(idx);
+        }
+        let idx = match existing {
+            ::core::option::Option::Some(idx) => idx,
+            ::core::option::Option::None => {
+                sproto.add_type("Person", ::std::vec::Vec::new())
+            }
+        };
+        visiting.push("Person");
+        let fields = ::std::vec![
+            ::sproto::types::Field::new("name", 0, ::sproto::types::FieldType::String),
+            ::sproto::types::Field::new("age", 1, ::sproto::types::FieldType::Integer),
+            ::sproto::types::Field::new("active", 2,
+            ::sproto::types::FieldType::Boolean), ::sproto::types::Field::new("score", 3,
+            ::sproto::types::FieldType::Double), ::sproto::types::Field::new("photo", 4,
+            ::sproto::types::FieldType::Binary), { let mut field =
+            ::sproto::types::Field::new("fpn", 5, ::sproto::types::FieldType::Integer);
+            field.decimal_precision = 100; field }, ::sproto::types::Field::new("id", 6,
+            ::sproto::types::FieldType::Integer), ::sproto::types::Field::new("phone", 7,
+            ::sproto::types::FieldType::Struct(< PhoneNumber as
+            ::sproto::codec::SprotoSchema > ::register_with(sproto, visiting) ?)),
+            ::sproto::types::Field::array("phones", 8,
+            ::sproto::types::FieldType::Struct(< PhoneNumber as
+            ::sproto::codec::SprotoSchema > ::register_with(sproto, visiting) ?)),
+            ::sproto::types::Field::array("children", 9,
+            ::sproto::types::FieldType::Struct(< Person as ::sproto::codec::SprotoSchema
+            > ::register_with(sproto, visiting) ?)),
+            ::sproto::types::Field::array("tags", 10,
+            ::sproto::types::FieldType::String), ::sproto::types::Field::array("numbers",
+            11, ::sproto::types::FieldType::Integer),
+            ::sproto::types::Field::array("flags", 12,
+            ::sproto::types::FieldType::Boolean), ::sproto::types::Field::array("values",
+            13, ::sproto::types::FieldType::Double)
+        ];
+        visiting.pop();
+        match existing {
+            ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+            ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+        }
+        ::core::result::Result::Ok(idx)
+    }
+}
+/// `.PhoneNumber`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhoneNumber {
+    /// `number 0 : string`
+    pub number: ::core::option::Option<::std::string::String>,
+    /// `type 1 : integer`
+    pub r#type: ::core::option::Option<i64>,
+}
+impl ::sproto::codec::SprotoEncode for PhoneNumber {
+    fn encode_fields(
+        &self,
+        enc: &mut ::sproto::codec::StructEncoder,
+    ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+        if let ::core::option::Option::Some(v) = &self.number {
+            enc.set_string(0, v)?;
+        }
+        if let ::core::option::Option::Some(v) = &self.r#type {
+            enc.set_integer(1, *v)?;
+        }
+        ::core::result::Result::Ok(())
+    }
+}
+impl ::sproto::codec::SprotoDecode for PhoneNumber {
+    fn decode_fields(
+        dec: &mut ::sproto::codec::StructDecoder,
+    ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+        let mut msg = Self::default();
+        while let ::core::option::Option::Some(f) = dec.next_field()? {
+            match f.tag() {
+                0 => {
+                    msg.number = ::core::option::Option::Some(
+                        ::std::borrow::ToOwned::to_owned(f.as_string()?),
+                    );
+                }
+                1 => {
+                    msg.r#type = ::core::option::Option::Some(f.as_integer()?);
+                }
+                _ => {}
+            }
+        }
+        ::core::result::Result::Ok(msg)
+    }
+}
+impl ::sproto::codec::SprotoSchema for PhoneNumber {
+    const TYPE_NAME: &'static str = "PhoneNumber";
+    fn register(sproto: &mut ::sproto::Sproto) -> usize {
+        <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+            .unwrap_or_else(|err| ::core::panic!("{}", err))
+    }
+    fn register_with(
+        sproto: &mut ::sproto::Sproto,
+        visiting: &mut ::std::vec::Vec<&'static str>,
+    ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+        let existing = sproto.get_type_index("PhoneNumber");
+        if let ::core::option::Option::Some(idx) = existing
+            .filter(|_| visiting.contains(&"PhoneNumber"))
+        {
+            return ::core::result::Result::Ok(idx);
+        }
+        let idx = match existing {
+            ::core::option::Option::Some(idx) => idx,
+            ::core::option::Option::None => {
+                sproto.add_type("PhoneNumber", ::std::vec::Vec::new())
+            }
+        };
+        visiting.push("PhoneNumber");
+        let fields = ::std::vec![
+            ::sproto::types::Field::new("number", 0, ::sproto::types::FieldType::String),
+            ::sproto::types::Field::new("type", 1, ::sproto::types::FieldType::Integer)
+        ];
+        visiting.pop();
+        match existing {
+            ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+            ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+        }
+        ::core::result::Result::Ok(idx)
+    }
+}
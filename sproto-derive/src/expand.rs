@@ -0,0 +1,322 @@
+//! Code generation for the derive macros.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::DeriveInput;
+
+use crate::field::{Kind, SprotoField, SprotoStruct};
+
+pub fn encode(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let st = SprotoStruct::parse(input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = st.fields.iter().map(|f| {
+        let ident = &f.ident;
+        let write = if f.is_array {
+            encode_array(f)
+        } else {
+            encode_scalar(f)
+        };
+        if f.is_option {
+            quote! {
+                if let ::core::option::Option::Some(v) = &self.#ident {
+                    #write
+                }
+            }
+        } else {
+            quote! {
+                {
+                    let v = &self.#ident;
+                    #write
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::sproto::codec::SprotoEncode for #name #ty_generics #where_clause {
+            fn encode_fields(
+                &self,
+                enc: &mut ::sproto::codec::StructEncoder,
+            ) -> ::core::result::Result<(), ::sproto::error::EncodeError> {
+                #(#fields)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+/// Write `v: &T` for a scalar field.
+fn encode_scalar(f: &SprotoField) -> TokenStream {
+    let tag = f.tag;
+    match &f.kind {
+        Kind::Integer(ty) => {
+            let value = int_to_i64(f, ty, quote!(*v));
+            quote! { enc.set_integer(#tag, #value)?; }
+        }
+        Kind::Boolean => quote! { enc.set_bool(#tag, *v)?; },
+        Kind::Double(_) => quote! { enc.set_double(#tag, *v as f64)?; },
+        Kind::Decimal(..) => quote! { enc.set_decimal(#tag, *v as f64)?; },
+        Kind::String => quote! { enc.set_string(#tag, v)?; },
+        Kind::Binary => quote! { enc.set_bytes(#tag, v)?; },
+        Kind::Struct(_) => quote! {
+            enc.encode_nested(#tag, |sub| ::sproto::codec::SprotoEncode::encode_fields(v, sub))?;
+        },
+    }
+}
+
+/// Write `v: &Vec<T>` for an array field.
+fn encode_array(f: &SprotoField) -> TokenStream {
+    let tag = f.tag;
+    match &f.kind {
+        Kind::Integer(ty) if ty == "i64" => quote! { enc.set_integer_array(#tag, v)?; },
+        Kind::Integer(ty) => {
+            let value = int_to_i64(f, ty, quote!(*x));
+            quote! {
+                let mut values: ::std::vec::Vec<i64> = ::std::vec::Vec::with_capacity(v.len());
+                for x in v {
+                    values.push(#value);
+                }
+                enc.set_integer_array(#tag, &values)?;
+            }
+        }
+        Kind::Boolean => quote! { enc.set_bool_array(#tag, v)?; },
+        Kind::Double(ty) if ty == "f64" => quote! { enc.set_double_array(#tag, v)?; },
+        Kind::Double(_) => quote! {
+            let values: ::std::vec::Vec<f64> = v.iter().map(|x| *x as f64).collect();
+            enc.set_double_array(#tag, &values)?;
+        },
+        Kind::Decimal(ty, _) if ty == "f64" => quote! { enc.set_decimal_array(#tag, v)?; },
+        Kind::Decimal(..) => quote! {
+            let values: ::std::vec::Vec<f64> = v.iter().map(|x| *x as f64).collect();
+            enc.set_decimal_array(#tag, &values)?;
+        },
+        Kind::String => quote! { enc.set_string_array(#tag, v)?; },
+        Kind::Binary => quote! { enc.set_bytes_array(#tag, v)?; },
+        Kind::Struct(_) => quote! {
+            enc.encode_struct_array(#tag, |arr| {
+                for x in v {
+                    arr.encode_element(|e| ::sproto::codec::SprotoEncode::encode_fields(x, e))?;
+                }
+                ::core::result::Result::Ok(())
+            })?;
+        },
+    }
+}
+
+/// Convert an integer to `i64`, returning an `EncodeError` from the enclosing
+/// function if it does not fit.
+fn int_to_i64(f: &SprotoField, ty: &syn::Ident, value: TokenStream) -> TokenStream {
+    let name = &f.name;
+    match ty.to_string().as_str() {
+        "i64" => value,
+        "i8" | "i16" | "i32" | "u8" | "u16" | "u32" => {
+            quote! { <i64 as ::core::convert::From<#ty>>::from(#value) }
+        }
+        // u64 / usize / isize: reject values that would not decode back.
+        _ => quote! {
+            <i64 as ::core::convert::TryFrom<#ty>>::try_from(#value).map_err(|_| {
+                ::sproto::error::EncodeError::Other(::std::format!(
+                    "integer out of range for field '{}'",
+                    #name
+                ))
+            })?
+        },
+    }
+}
+
+pub fn decode(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let st = SprotoStruct::parse(input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let slots: Vec<_> = st
+        .fields
+        .iter()
+        .map(|f| format_ident!("__{}", f.ident.to_string().trim_start_matches("r#")))
+        .collect();
+    let arms = st.fields.iter().zip(&slots).map(|(f, slot)| {
+        let tag = f.tag;
+        let value = if f.is_array {
+            decode_array(f)
+        } else {
+            decode_scalar(f)
+        };
+        quote! { #tag => #slot = ::core::option::Option::Some(#value), }
+    });
+    let inits = st.fields.iter().zip(&slots).map(|(f, slot)| {
+        let ident = &f.ident;
+        if f.is_option {
+            quote! { #ident: #slot }
+        } else {
+            quote! { #ident: #slot.unwrap_or_default() }
+        }
+    });
+    let skipped = st.skipped.iter();
+
+    Ok(quote! {
+        impl #impl_generics ::sproto::codec::SprotoDecode for #name #ty_generics #where_clause {
+            fn decode_fields(
+                dec: &mut ::sproto::codec::StructDecoder,
+            ) -> ::core::result::Result<Self, ::sproto::error::DecodeError> {
+                #(let mut #slots = ::core::option::Option::None;)*
+                while let ::core::option::Option::Some(f) = dec.next_field()? {
+                    match f.tag() {
+                        #(#arms)*
+                        _ => {}
+                    }
+                }
+                ::core::result::Result::Ok(Self {
+                    #(#inits,)*
+                    #(#skipped: ::core::default::Default::default(),)*
+                })
+            }
+        }
+    })
+}
+
+/// Convert an `i64` to a narrower integer type, as a `Result` that fails on overflow.
+fn i64_to_int(f: &SprotoField, ty: &syn::Ident, value: TokenStream) -> TokenStream {
+    let name = &f.name;
+    quote! {
+        <#ty as ::core::convert::TryFrom<i64>>::try_from(#value).map_err(|_| {
+            ::sproto::error::DecodeError::InvalidData(::std::format!(
+                "integer out of range for field '{}'",
+                #name
+            ))
+        })
+    }
+}
+
+/// Read a scalar field value from `f: DecodedField`.
+fn decode_scalar(f: &SprotoField) -> TokenStream {
+    match &f.kind {
+        Kind::Integer(ty) if ty == "i64" => quote! { f.as_integer()? },
+        Kind::Integer(ty) => {
+            let value = i64_to_int(f, ty, quote!(f.as_integer()?));
+            quote! { #value? }
+        }
+        Kind::Boolean => quote! { f.as_bool()? },
+        Kind::Double(ty) => quote! { f.as_double()? as #ty },
+        Kind::Decimal(ty, _) => quote! { f.as_decimal()? as #ty },
+        Kind::String => quote! { ::std::borrow::ToOwned::to_owned(f.as_string()?) },
+        Kind::Binary => quote! { f.as_bytes().to_vec() },
+        Kind::Struct(ty) => quote! {
+            <#ty as ::sproto::codec::SprotoDecode>::decode_fields(&mut f.as_struct()?)?
+        },
+    }
+}
+
+/// Read an array field value from `f: DecodedField`.
+fn decode_array(f: &SprotoField) -> TokenStream {
+    match &f.kind {
+        Kind::Integer(ty) if ty == "i64" => quote! { f.as_integer_array()? },
+        Kind::Integer(ty) => {
+            let value = i64_to_int(f, ty, quote!(x));
+            quote! {
+                f.as_integer_array()?
+                    .into_iter()
+                    .map(|x| #value)
+                    .collect::<::core::result::Result<::std::vec::Vec<#ty>, ::sproto::error::DecodeError>>()?
+            }
+        }
+        Kind::Boolean => quote! { f.as_bool_array() },
+        Kind::Double(ty) if ty == "f64" => quote! { f.as_double_array()? },
+        Kind::Double(ty) => quote! {
+            f.as_double_array()?.into_iter().map(|x| x as #ty).collect()
+        },
+        Kind::Decimal(ty, _) if ty == "f64" => quote! { f.as_decimal_array()? },
+        Kind::Decimal(ty, _) => quote! {
+            f.as_decimal_array()?.into_iter().map(|x| x as #ty).collect()
+        },
+        Kind::String => quote! {
+            f.as_string_array()?
+                .into_iter()
+                .map(::std::borrow::ToOwned::to_owned)
+                .collect()
+        },
+        Kind::Binary => quote! {
+            f.as_bytes_array()?.into_iter().map(|b| b.to_vec()).collect()
+        },
+        Kind::Struct(ty) => quote! {
+            f.as_struct_iter()?
+                .map(|d| <#ty as ::sproto::codec::SprotoDecode>::decode_fields(&mut d?))
+                .collect::<::core::result::Result<::std::vec::Vec<#ty>, ::sproto::error::DecodeError>>()?
+        },
+    }
+}
+
+pub fn schema(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let st = SprotoStruct::parse(input)?;
+    let name = &input.ident;
+    let type_name = &st.type_name;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = st.fields.iter().map(|f| {
+        let field_name = &f.name;
+        let tag = f.tag;
+        let field_type = match &f.kind {
+            Kind::Integer(_) | Kind::Decimal(..) => quote!(::sproto::types::FieldType::Integer),
+            Kind::Boolean => quote!(::sproto::types::FieldType::Boolean),
+            Kind::Double(_) => quote!(::sproto::types::FieldType::Double),
+            Kind::String => quote!(::sproto::types::FieldType::String),
+            Kind::Binary => quote!(::sproto::types::FieldType::Binary),
+            Kind::Struct(ty) => quote! {
+                ::sproto::types::FieldType::Struct(
+                    <#ty as ::sproto::codec::SprotoSchema>::register_with(sproto, visiting)?
+                )
+            },
+        };
+        let ctor = if f.is_array {
+            quote!(array)
+        } else {
+            quote!(new)
+        };
+        let precision = match &f.kind {
+            Kind::Decimal(_, precision) => quote! { field.decimal_precision = #precision; },
+            _ => quote! {},
+        };
+        quote! {
+            {
+                #[allow(unused_mut)]
+                let mut field = ::sproto::types::Field::#ctor(#field_name, #tag, #field_type);
+                #precision
+                field
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::sproto::codec::SprotoSchema for #name #ty_generics #where_clause {
+            const TYPE_NAME: &'static str = #type_name;
+
+            fn register(sproto: &mut ::sproto::Sproto) -> usize {
+                <Self as ::sproto::codec::SprotoSchema>::try_register(sproto)
+                    .unwrap_or_else(|err| ::core::panic!("{}", err))
+            }
+
+            fn register_with(
+                sproto: &mut ::sproto::Sproto,
+                visiting: &mut ::std::vec::Vec<&'static str>,
+            ) -> ::core::result::Result<usize, ::sproto::error::SchemaError> {
+                let existing = sproto.get_type_index(#type_name);
+                if let ::core::option::Option::Some(idx) = existing.filter(|_| visiting.contains(&#type_name)) {
+                    return ::core::result::Result::Ok(idx);
+                }
+                let idx = match existing {
+                    ::core::option::Option::Some(idx) => idx,
+                    ::core::option::Option::None => sproto.add_type(#type_name, ::std::vec::Vec::new()),
+                };
+                visiting.push(#type_name);
+                let fields = ::std::vec![#(#fields),*];
+                visiting.pop();
+                match existing {
+                    ::core::option::Option::Some(_) => sproto.check_type_fields(idx, &fields)?,
+                    ::core::option::Option::None => sproto.set_type_fields(idx, fields),
+                }
+                ::core::result::Result::Ok(idx)
+            }
+        }
+    })
+}
@@ -0,0 +1,220 @@
+//! Parsing of `#[sproto(...)]` attributes and field types.
+
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields, GenericArgument, Ident, LitInt, LitStr, PathArguments, Type};
+
+/// Scalar kind of a field or array element.
+pub enum Kind {
+    /// An integer type, by name (`i64`, `u32`, ...).
+    Integer(Ident),
+    Boolean,
+    /// `f32` or `f64`.
+    Double(Ident),
+    /// `f32` or `f64` stored as `integer(N)`; the value is 10^N.
+    Decimal(Ident, u32),
+    String,
+    /// `Vec<u8>`.
+    Binary,
+    /// Any other type, which must implement the sproto traits itself.
+    Struct(Box<Type>),
+}
+
+/// A struct field that is encoded on the wire.
+pub struct SprotoField {
+    pub ident: Ident,
+    pub name: String,
+    pub tag: u16,
+    pub kind: Kind,
+    pub is_array: bool,
+    pub is_option: bool,
+}
+
+/// A parsed `#[derive(...)]` input.
+pub struct SprotoStruct {
+    pub type_name: String,
+    pub fields: Vec<SprotoField>,
+    /// Fields marked `#[sproto(skip)]`, filled with `Default::default()`.
+    pub skipped: Vec<Ident>,
+}
+
+impl SprotoStruct {
+    pub fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut type_name = input.ident.to_string();
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("sproto")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    type_name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown sproto attribute, expected `name`"))
+                }
+            })?;
+        }
+
+        let named = match &input.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(named) => &named.named,
+                _ => {
+                    return Err(syn::Error::new(
+                        input.ident.span(),
+                        "sproto derives require a struct with named fields",
+                    ))
+                }
+            },
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "sproto derives only support structs",
+                ))
+            }
+        };
+
+        let mut fields: Vec<SprotoField> = Vec::new();
+        let mut skipped = Vec::new();
+        for field in named {
+            let ident = field.ident.clone().expect("named field");
+            let mut tag = None;
+            let mut decimal = None;
+            let mut skip = false;
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("sproto")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("tag") {
+                        tag = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<u16>()?);
+                    } else if meta.path.is_ident("decimal") {
+                        let lit = meta.value()?.parse::<LitInt>()?;
+                        let digits = lit.base10_parse::<u32>()?;
+                        if digits > 9 {
+                            return Err(syn::Error::new(lit.span(), "decimal digits must be <= 9"));
+                        }
+                        decimal = Some(10u32.pow(digits));
+                    } else if meta.path.is_ident("skip") {
+                        skip = true;
+                    } else {
+                        return Err(meta.error(
+                            "unknown sproto attribute, expected `tag`, `decimal` or `skip`",
+                        ));
+                    }
+                    Ok(())
+                })?;
+            }
+            if skip {
+                skipped.push(ident);
+                continue;
+            }
+            let tag = tag.ok_or_else(|| {
+                syn::Error::new(ident.span(), "missing #[sproto(tag = N)] attribute")
+            })?;
+            if tag >= 0x7fff {
+                return Err(syn::Error::new(ident.span(), "tag must be < 32767"));
+            }
+            if let Some(prev) = fields.iter().find(|f| f.tag == tag) {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("tag {} is already used by field `{}`", tag, prev.ident),
+                ));
+            }
+
+            let (is_option, ty) = match generic_arg("Option", &field.ty) {
+                Some(inner) => (true, inner),
+                None => (false, &field.ty),
+            };
+            let (is_array, elem) = if is_bytes(ty) {
+                (false, ty)
+            } else {
+                match generic_arg("Vec", ty) {
+                    Some(inner) => (true, inner),
+                    None => (false, ty),
+                }
+            };
+            let mut kind = classify(elem)?;
+            if let Some(precision) = decimal {
+                kind = match kind {
+                    Kind::Double(float) => Kind::Decimal(float, precision),
+                    _ => {
+                        return Err(syn::Error::new(
+                            field.ty.span(),
+                            "decimal fields must be f32 or f64",
+                        ))
+                    }
+                };
+            }
+
+            fields.push(SprotoField {
+                name: ident.to_string().trim_start_matches("r#").to_string(),
+                ident,
+                tag,
+                kind,
+                is_array,
+                is_option,
+            });
+        }
+        fields.sort_by_key(|f| f.tag);
+
+        Ok(SprotoStruct {
+            type_name,
+            fields,
+            skipped,
+        })
+    }
+}
+
+/// The last path segment of `ty`, if it is a plain path type.
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+    match ty {
+        Type::Path(p) if p.qself.is_none() => p.path.segments.last(),
+        _ => None,
+    }
+}
+
+/// If `ty` is `wrapper<T>`, return `T`.
+fn generic_arg<'a>(wrapper: &str, ty: &'a Type) -> Option<&'a Type> {
+    let seg = last_segment(ty)?;
+    if seg.ident != wrapper {
+        return None;
+    }
+    match &seg.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_bytes(ty: &Type) -> bool {
+    generic_arg("Vec", ty)
+        .and_then(last_segment)
+        .is_some_and(|seg| seg.ident == "u8")
+}
+
+fn classify(ty: &Type) -> syn::Result<Kind> {
+    if is_bytes(ty) {
+        return Ok(Kind::Binary);
+    }
+    let seg = match last_segment(ty) {
+        Some(seg) => seg,
+        None => {
+            return Err(syn::Error::new(
+                ty.span(),
+                "unsupported sproto field type, expected a path type",
+            ))
+        }
+    };
+    let ident = &seg.ident;
+    let kind = match ident.to_string().as_str() {
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
+            Kind::Integer(ident.clone())
+        }
+        "bool" => Kind::Boolean,
+        "f32" | "f64" => Kind::Double(ident.clone()),
+        "String" => Kind::String,
+        "Option" | "Vec" => {
+            return Err(syn::Error::new(
+                ty.span(),
+                "unsupported nesting of Option/Vec in a sproto field",
+            ))
+        }
+        _ => Kind::Struct(Box::new(ty.clone())),
+    };
+    Ok(kind)
+}
@@ -0,0 +1,62 @@
+//! Derive macros for the `sproto` traits `SprotoEncode`, `SprotoDecode` and
+//! `SprotoSchema`.
+//!
+//! Every wire field carries an explicit tag:
+//!
+//! ```ignore
+//! #[derive(SprotoEncode, SprotoDecode, SprotoSchema)]
+//! #[sproto(name = "Person")]
+//! struct Person {
+//!     #[sproto(tag = 0)]
+//!     name: String,
+//!     #[sproto(tag = 1)]
+//!     age: Option<i64>,
+//!     #[sproto(tag = 2, decimal = 2)]
+//!     balance: f64,
+//!     #[sproto(tag = 3)]
+//!     phones: Vec<PhoneNumber>,
+//!     #[sproto(skip)]
+//!     cache: u64,
+//! }
+//! ```
+//!
+//! Field types map syntactically: integer types to `integer`, `bool` to
+//! `boolean`, `f32`/`f64` to `double` (or `integer(N)` with `decimal = N`),
+//! `String` to `string`, `Vec<u8>` to `binary`, `Vec<T>` to `*T`, and any
+//! other type to a nested struct implementing the same traits. `Option<T>`
+//! fields are omitted when `None`; absent non-`Option` fields decode as
+//! `Default::default()`. Integers that do not fit the wire's `i64`, or the
+//! field's own type on decode, are reported as errors rather than wrapped.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod expand;
+mod field;
+
+/// Derive `sproto::codec::SprotoEncode`.
+#[proc_macro_derive(SprotoEncode, attributes(sproto))]
+pub fn derive_encode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand::encode(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `sproto::codec::SprotoDecode`.
+#[proc_macro_derive(SprotoDecode, attributes(sproto))]
+pub fn derive_decode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand::decode(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `sproto::codec::SprotoSchema`.
+#[proc_macro_derive(SprotoSchema, attributes(sproto))]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand::schema(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,384 @@
+//! Derive macro tests.
+//!
+//! The derived types mirror `tests/testdata/schema.sproto` of the main crate
+//! and are cross-validated against its C/Lua binary fixtures.
+
+use sproto::binary_schema;
+use sproto::error::SchemaError;
+use sproto::types::{FieldType, Sproto};
+use sproto::{SprotoDecode, SprotoEncode, SprotoSchema};
+
+fn testdata(name: &str) -> Vec<u8> {
+    let path = format!("{}/../tests/testdata/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+fn load_sproto() -> Sproto {
+    binary_schema::load_binary(&testdata("schema.bin")).unwrap()
+}
+
+#[derive(SprotoEncode, SprotoDecode, SprotoSchema, Debug, Default, PartialEq)]
+struct PhoneNumber {
+    #[sproto(tag = 0)]
+    number: String,
+    #[sproto(tag = 1)]
+    r#type: i64,
+}
+
+#[derive(SprotoEncode, SprotoDecode, SprotoSchema, Debug, Default, PartialEq)]
+struct Person {
+    #[sproto(tag = 0)]
+    name: Option<String>,
+    #[sproto(tag = 1)]
+    age: Option<i64>,
+    #[sproto(tag = 2)]
+    active: Option<bool>,
+    #[sproto(tag = 3)]
+    score: Option<f64>,
+    #[sproto(tag = 4)]
+    photo: Option<Vec<u8>>,
+    #[sproto(tag = 5, decimal = 2)]
+    fpn: Option<f64>,
+    #[sproto(tag = 6)]
+    id: Option<i64>,
+    #[sproto(tag = 7)]
+    phone: Option<PhoneNumber>,
+    #[sproto(tag = 8)]
+    phones: Option<Vec<PhoneNumber>>,
+    #[sproto(tag = 9)]
+    children: Option<Vec<Person>>,
+    #[sproto(tag = 10)]
+    tags: Option<Vec<String>>,
+    #[sproto(tag = 11)]
+    numbers: Option<Vec<i64>>,
+    #[sproto(tag = 12)]
+    flags: Option<Vec<bool>>,
+    #[sproto(tag = 13)]
+    values: Option<Vec<f64>>,
+}
+
+fn phone(number: &str, kind: i64) -> PhoneNumber {
+    PhoneNumber {
+        number: number.into(),
+        r#type: kind,
+    }
+}
+
+fn full_person() -> Person {
+    Person {
+        name: Some("Alice".into()),
+        age: Some(30),
+        active: Some(true),
+        score: Some(0.01171875),
+        photo: Some(vec![0xDE, 0xAD, 0xBE, 0xEF]),
+        fpn: Some(1.82),
+        id: Some(10000),
+        phone: Some(phone("123456789", 1)),
+        phones: Some(vec![phone("123456789", 1), phone("87654321", 2)]),
+        children: Some(vec![Person {
+            name: Some("Bob".into()),
+            age: Some(5),
+            ..Default::default()
+        }]),
+        tags: Some(vec![
+            "hello".into(),
+            "world".into(),
+            "\u{4f60}\u{597d}".into(),
+        ]),
+        numbers: Some(vec![1, 2, 3, 4, 5]),
+        flags: Some(vec![false, true, false]),
+        values: Some(vec![0.01171875, 23.0, 4.0]),
+    }
+}
+
+fn encode<T: SprotoEncode + SprotoSchema>(sproto: &Sproto, value: &T) -> Vec<u8> {
+    let st = sproto.get_type(T::TYPE_NAME).unwrap();
+    let mut buf = Vec::new();
+    value.encode_to(sproto, st, &mut buf).unwrap();
+    buf
+}
+
+fn decode<T: SprotoDecode + SprotoSchema>(sproto: &Sproto, data: &[u8]) -> T {
+    let st = sproto.get_type(T::TYPE_NAME).unwrap();
+    T::decode_from(sproto, st, data).unwrap()
+}
+
+// =============================================================================
+// Binary fixture cross-validation
+// =============================================================================
+
+#[test]
+fn test_derive_encode_full() {
+    let sproto = load_sproto();
+    assert_eq!(
+        encode(&sproto, &full_person()),
+        testdata("full_encoded.bin")
+    );
+}
+
+#[test]
+fn test_derive_decode_full() {
+    let sproto = load_sproto();
+    let person: Person = decode(&sproto, &testdata("full_encoded.bin"));
+    assert_eq!(person, full_person());
+}
+
+#[test]
+fn test_derive_nested_struct() {
+    let sproto = load_sproto();
+    let person = Person {
+        name: Some("Alice".into()),
+        phone: Some(phone("123456789", 1)),
+        ..Default::default()
+    };
+    let expected = testdata("nested_struct_encoded.bin");
+    assert_eq!(encode(&sproto, &person), expected);
+    assert_eq!(decode::<Person>(&sproto, &expected), person);
+}
+
+// =============================================================================
+// Schema registration
+// =============================================================================
+
+#[test]
+fn test_derive_register_matches_binary_schema() {
+    let mut derived = Sproto::new();
+    let idx = Person::register(&mut derived);
+    assert_eq!(derived.types_list[idx].name, "Person");
+    // Registering again, directly or through a reference, is a no-op.
+    assert_eq!(Person::register(&mut derived), idx);
+    PhoneNumber::register(&mut derived);
+
+    let loaded = load_sproto();
+    assert_eq!(derived.types_list.len(), loaded.types_list.len());
+    for expected in &loaded.types_list {
+        let actual = derived.get_type(&expected.name).unwrap();
+        assert_eq!(actual.fields.len(), expected.fields.len());
+        for (a, e) in actual.fields.iter().zip(&expected.fields) {
+            let ctx = format!("{}.{}", expected.name, e.name);
+            assert_eq!(a.name, e.name, "{}", ctx);
+            assert_eq!(a.tag, e.tag, "{}", ctx);
+            assert_eq!(a.is_array, e.is_array, "{}", ctx);
+            assert_eq!(a.decimal_precision, e.decimal_precision, "{}", ctx);
+            match (&a.field_type, &e.field_type) {
+                (FieldType::Struct(ai), FieldType::Struct(ei)) => assert_eq!(
+                    derived.types_list[*ai].name, loaded.types_list[*ei].name,
+                    "{}",
+                    ctx
+                ),
+                (at, et) => assert_eq!(at, et, "{}", ctx),
+            }
+        }
+        assert_eq!(actual.maxn, expected.maxn);
+    }
+
+    // The derived schema encodes identically to the C-compiled one.
+    assert_eq!(
+        encode(&derived, &full_person()),
+        testdata("full_encoded.bin")
+    );
+}
+
+#[test]
+fn test_derive_register_checks_existing_type() {
+    // `Person` and its recursive `children` field are already loaded; the
+    // derived layout matches, so nothing is added.
+    let mut sproto = load_sproto();
+    let count = sproto.types_list.len();
+    let idx = Person::try_register(&mut sproto).unwrap();
+    assert_eq!(sproto.types_list[idx].name, "Person");
+    assert_eq!(sproto.types_list.len(), count);
+
+    // Field names are not part of the encoding.
+    #[derive(SprotoSchema)]
+    #[sproto(name = "PhoneNumber")]
+    #[allow(dead_code)]
+    struct Renamed {
+        #[sproto(tag = 0)]
+        digits: String,
+        #[sproto(tag = 1)]
+        kind: i64,
+    }
+    Renamed::try_register(&mut sproto).unwrap();
+
+    // A tag whose type drifted is reported instead of reusing the type.
+    #[derive(SprotoSchema)]
+    #[sproto(name = "PhoneNumber")]
+    #[allow(dead_code)]
+    struct Drifted {
+        #[sproto(tag = 0)]
+        number: String,
+        #[sproto(tag = 1)]
+        kind: String,
+    }
+    assert_eq!(
+        Drifted::try_register(&mut sproto).unwrap_err(),
+        SchemaError::FieldMismatch {
+            type_name: "PhoneNumber".into(),
+            field: "kind".into(),
+            tag: 1,
+        }
+    );
+
+    // So is one that the existing type lacks, including through a reference
+    // from a new type.
+    #[derive(SprotoSchema)]
+    #[sproto(name = "PhoneNumber")]
+    #[allow(dead_code)]
+    struct Extended {
+        #[sproto(tag = 0)]
+        number: String,
+        #[sproto(tag = 2)]
+        country: String,
+    }
+    #[derive(SprotoSchema)]
+    #[allow(dead_code)]
+    struct Contact {
+        #[sproto(tag = 0)]
+        phone: Extended,
+    }
+    assert_eq!(
+        Contact::try_register(&mut sproto).unwrap_err(),
+        SchemaError::FieldMismatch {
+            type_name: "PhoneNumber".into(),
+            field: "country".into(),
+            tag: 2,
+        }
+    );
+}
+
+// =============================================================================
+// Attributes and type conversions
+// =============================================================================
+
+#[derive(SprotoEncode, SprotoDecode, SprotoSchema, Debug, Default, PartialEq)]
+#[sproto(name = "Account.Entry")]
+struct Entry {
+    #[sproto(tag = 3)]
+    amounts: Vec<f32>,
+    #[sproto(tag = 0)]
+    id: u32,
+    #[sproto(tag = 1, decimal = 2)]
+    balance: f64,
+    #[sproto(tag = 2)]
+    small: Vec<i16>,
+    #[sproto(skip)]
+    cached: Option<String>,
+}
+
+#[test]
+fn test_derive_attributes_and_conversions() {
+    let mut sproto = Sproto::new();
+    Entry::register(&mut sproto);
+    let st = sproto.get_type("Account.Entry").unwrap();
+    let tags: Vec<u16> = st.fields.iter().map(|f| f.tag).collect();
+    assert_eq!(tags, vec![0, 1, 2, 3]);
+    assert_eq!(st.fields[1].decimal_precision, 100);
+
+    let entry = Entry {
+        amounts: vec![1.5, -2.25],
+        id: 4_000_000_000,
+        balance: 12.34,
+        small: vec![-1, 300],
+        cached: Some("not on the wire".into()),
+    };
+    let bytes = encode(&sproto, &entry);
+
+    let mut dec = sproto::codec::StructDecoder::new(&sproto, st, &bytes).unwrap();
+    let id = dec.next_field().unwrap().unwrap();
+    assert_eq!(id.as_integer().unwrap(), 4_000_000_000);
+    let balance = dec.next_field().unwrap().unwrap();
+    assert_eq!(balance.as_integer().unwrap(), 1234);
+
+    let decoded: Entry = decode(&sproto, &bytes);
+    assert_eq!(
+        decoded,
+        Entry {
+            cached: None,
+            ..entry
+        }
+    );
+}
+
+#[test]
+fn test_derive_absent_fields_default() {
+    let mut sproto = Sproto::new();
+    Entry::register(&mut sproto);
+    let decoded: Entry = decode(&sproto, &encode(&sproto, &Entry::default()));
+    assert_eq!(decoded, Entry::default());
+
+    // An empty struct on the wire decodes to all defaults.
+    let decoded: Entry = decode(&sproto, &[0, 0]);
+    assert_eq!(decoded, Entry::default());
+}
+
+#[test]
+fn test_derive_integer_out_of_range() {
+    #[derive(SprotoEncode, SprotoSchema)]
+    #[sproto(name = "Narrow")]
+    struct Wide {
+        #[sproto(tag = 0)]
+        v: i64,
+    }
+    #[derive(SprotoDecode, SprotoSchema, Debug)]
+    #[allow(dead_code)]
+    struct Narrow {
+        #[sproto(tag = 0)]
+        v: u8,
+    }
+
+    let mut sproto = Sproto::new();
+    Narrow::register(&mut sproto);
+    let bytes = encode(&sproto, &Wide { v: 256 });
+    let st = sproto.get_type("Narrow").unwrap();
+    let err = Narrow::decode_from(&sproto, st, &bytes).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid data: integer out of range for field 'v'"
+    );
+}
+
+#[test]
+fn test_derive_unsigned_out_of_range() {
+    #[derive(SprotoEncode, SprotoDecode, SprotoSchema, Debug, Default, PartialEq)]
+    struct Counter {
+        #[sproto(tag = 0)]
+        total: u64,
+        #[sproto(tag = 1)]
+        samples: Vec<usize>,
+    }
+
+    let mut sproto = Sproto::new();
+    Counter::register(&mut sproto);
+    let st = sproto.get_type("Counter").unwrap();
+
+    let counter = Counter {
+        total: i64::MAX as u64,
+        samples: vec![0, 7],
+    };
+    let decoded: Counter = decode(&sproto, &encode(&sproto, &counter));
+    assert_eq!(decoded, counter);
+
+    let mut out = Vec::new();
+    let err = Counter {
+        total: u64::MAX,
+        ..Default::default()
+    }
+    .encode_to(&sproto, st, &mut out)
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: integer out of range for field 'total'"
+    );
+
+    let err = Counter {
+        samples: vec![1, usize::MAX],
+        ..Default::default()
+    }
+    .encode_to(&sproto, st, &mut out)
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: integer out of range for field 'samples'"
+    );
+}
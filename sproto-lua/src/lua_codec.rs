@@ -5,8 +5,9 @@
 //! type conversion while delegating all wire-format work to the engine.
 
 use mlua::prelude::*;
-use sproto::codec::decoder::{DecodedField, StructDecoder};
+use sproto::codec::decoder::{DecodeLimits, DecodedField, StructDecoder};
 use sproto::codec::encoder::StructEncoder;
+use sproto::codec::MapKey;
 use sproto::error::EncodeError;
 use sproto::types::{Field, FieldType, Sproto, SprotoType};
 
@@ -60,7 +61,9 @@ fn lua_fill_encoder(
             continue;
         }
 
-        if field.is_array {
+        if field.is_array && field.key_tag >= 0 {
+            encode_map_field(lua, sproto, field, &val, enc)?;
+        } else if field.is_array {
             encode_array_field(lua, sproto, field, &val, enc)?;
         } else {
             encode_scalar_field(lua, sproto, field, &val, enc)?;
@@ -170,6 +173,81 @@ fn encode_array_field(
     Ok(())
 }
 
+/// Encode a `*T(key)` or `*T()` field from a Lua table of any shape.
+///
+/// As in the C binding, every value of the table is written: `*T(key)` values
+/// are element tables carrying their own key, and `*T()` entries become
+/// two-field elements built from the table key and value.
+fn encode_map_field(
+    lua: &Lua,
+    sproto: &Sproto,
+    field: &Field,
+    val: &LuaValue,
+    enc: &mut StructEncoder,
+) -> Result<(), EncodeError> {
+    let table = lua_to_table(val, &field.name)?;
+    // Defensive only: `load_binary` already rejects malformed map fields.
+    let sub_type = match &field.field_type {
+        FieldType::Struct(type_idx) => &sproto.types_list[*type_idx],
+        _ => {
+            return Err(EncodeError::Other(format!(
+                "map field '{}' is not a struct array",
+                field.name
+            )))
+        }
+    };
+    let entries = table
+        .pairs::<LuaValue, LuaValue>()
+        .collect::<LuaResult<Vec<_>>>()
+        .map_err(|e| EncodeError::Other(e.to_string()))?;
+
+    if !field.is_map {
+        return enc.encode_struct_map(field.tag, &entries, |elem_enc, (_, v)| {
+            let sub_table = lua_to_table(v, &field.name)?;
+            lua_fill_encoder(lua, sproto, sub_type, sub_table, elem_enc)
+        });
+    }
+
+    let key_field = sub_type
+        .find_field_by_tag(field.key_tag as u16)
+        .ok_or_else(|| {
+            EncodeError::Other(format!(
+                "map key tag {} of field '{}' is not a field of '{}'",
+                field.key_tag, field.name, sub_type.name
+            ))
+        })?;
+    let value_field = match sub_type.fields.as_slice() {
+        [a, b] => {
+            if a.tag == key_field.tag {
+                b
+            } else {
+                a
+            }
+        }
+        _ => {
+            return Err(EncodeError::Other(format!(
+                "map field '{}' needs a two-field element type, '{}' has {}",
+                field.name,
+                sub_type.name,
+                sub_type.fields.len()
+            )))
+        }
+    };
+    enc.encode_struct_array(field.tag, |arr_enc| {
+        for (k, v) in &entries {
+            arr_enc.encode_element(|elem_enc| {
+                encode_scalar_field(lua, sproto, key_field, k, elem_enc)?;
+                if value_field.is_array {
+                    encode_array_field(lua, sproto, value_field, v, elem_enc)
+                } else {
+                    encode_scalar_field(lua, sproto, value_field, v, elem_enc)
+                }
+            })?;
+        }
+        Ok(())
+    })
+}
+
 // ---------------------------------------------------------------------------
 // Decoder: wire bytes -> LuaTable
 // ---------------------------------------------------------------------------
@@ -180,11 +258,32 @@ pub fn lua_decode(
     sproto: &Sproto,
     sproto_type: &SprotoType,
     data: &[u8],
+    limits: DecodeLimits,
 ) -> LuaResult<LuaTable> {
-    let dec = StructDecoder::new(sproto, sproto_type, data).map_err(decode_error_to_lua)?;
+    let dec = StructDecoder::with_limits(sproto, sproto_type, data, limits)
+        .map_err(decode_error_to_lua)?;
     lua_decode_fields(lua, dec)
 }
 
+/// Read decode limits from an optional Lua table such as
+/// `{ max_depth = 16, max_array_len = 1000 }`; absent keys keep their default.
+pub fn lua_to_decode_limits(table: Option<LuaTable>) -> LuaResult<DecodeLimits> {
+    let mut limits = DecodeLimits::default();
+    if let Some(table) = table {
+        let read = |key: &str, value: &mut usize| -> LuaResult<()> {
+            if let Some(v) = table.get::<Option<usize>>(key)? {
+                *value = v;
+            }
+            Ok(())
+        };
+        read("max_depth", &mut limits.max_depth)?;
+        read("max_array_len", &mut limits.max_array_len)?;
+        read("max_string_len", &mut limits.max_string_len)?;
+        read("max_fields", &mut limits.max_fields)?;
+    }
+    Ok(limits)
+}
+
 fn lua_decode_fields(lua: &Lua, mut dec: StructDecoder) -> LuaResult<LuaTable> {
     let result = lua.create_table()?;
     while let Some(f) = dec.next_field().map_err(decode_error_to_lua)? {
@@ -260,6 +359,28 @@ fn decode_array_field(lua: &Lua, f: &DecodedField) -> LuaResult<LuaValue> {
             }
             Ok(LuaValue::Table(table))
         }
+        FieldType::Struct(_) if f.field().is_map => {
+            let map = f.as_pair_map().map_err(decode_error_to_lua)?;
+            let table = lua.create_table_with_capacity(0, map.len())?;
+            for (key, value) in map {
+                let lua_val = if value.field().is_array {
+                    decode_array_field(lua, &value)?
+                } else {
+                    decode_scalar_field(lua, &value)?
+                };
+                table.set(map_key_to_lua(lua, key)?, lua_val)?;
+            }
+            Ok(LuaValue::Table(table))
+        }
+        FieldType::Struct(_) if f.field().key_tag >= 0 => {
+            let map = f.as_struct_map().map_err(decode_error_to_lua)?;
+            let table = lua.create_table_with_capacity(0, map.len())?;
+            for (key, sub_dec) in map {
+                let sub_table = lua_decode_fields(lua, sub_dec)?;
+                table.set(map_key_to_lua(lua, key)?, sub_table)?;
+            }
+            Ok(LuaValue::Table(table))
+        }
         FieldType::Struct(_) => {
             let iter = f.as_struct_iter().map_err(decode_error_to_lua)?;
             let table = lua.create_table()?;
@@ -286,7 +407,7 @@ fn lua_to_i64(val: &LuaValue, field: &Field) -> Result<i64, EncodeError> {
         LuaValue::Integer(i) => Ok(*i),
         LuaValue::Number(n) => {
             if field.decimal_precision > 0 {
-                Ok((*n * field.decimal_precision as f64).round() as i64)
+                Ok(field.scale_decimal(*n))
             } else {
                 Ok(*n as i64)
             }
@@ -323,6 +444,16 @@ fn lua_to_bytes(val: &LuaValue, field_name: &str) -> Result<Vec<u8>, EncodeError
     }
 }
 
+fn map_key_to_lua(lua: &Lua, key: MapKey) -> LuaResult<LuaValue> {
+    Ok(match key {
+        MapKey::Integer(v) => LuaValue::Integer(v),
+        MapKey::Boolean(v) => LuaValue::Boolean(v),
+        MapKey::Double(v) => LuaValue::Number(v),
+        MapKey::String(v) => LuaValue::String(lua.create_string(v)?),
+        MapKey::Binary(v) => LuaValue::String(lua.create_string(v)?),
+    })
+}
+
 fn lua_to_table<'a>(val: &'a LuaValue, field_name: &str) -> Result<&'a LuaTable, EncodeError> {
     match val {
         LuaValue::Table(t) => Ok(t),
@@ -28,15 +28,18 @@ impl LuaUserData for SprotoUserData {
             },
         );
 
-        // decode(type_name, data) -> table
+        // decode(type_name, data, limits?) -> table
+        // limits: { max_depth, max_array_len, max_string_len, max_fields }
         methods.add_method(
             "decode",
-            |lua, this, (type_name, data): (String, LuaString)| {
+            |lua, this, (type_name, data, limits): (String, LuaString, Option<LuaTable>)| {
                 let sproto_type = this.inner.get_type(&type_name).ok_or_else(|| {
                     LuaError::RuntimeError(format!("unknown type: {}", type_name))
                 })?;
 
-                let table = lua_codec::lua_decode(lua, &this.inner, sproto_type, &data.as_bytes())?;
+                let limits = lua_codec::lua_to_decode_limits(limits)?;
+                let table =
+                    lua_codec::lua_decode(lua, &this.inner, sproto_type, &data.as_bytes(), limits)?;
                 Ok(LuaValue::Table(table))
             },
         );
@@ -138,10 +141,18 @@ impl LuaUserData for HostUserData {
 
                     Ok(table)
                 }
-                rpc::DispatchResult::Response { session, body, ud } => {
+                rpc::DispatchResult::Response {
+                    session,
+                    protocol,
+                    body,
+                    ud,
+                } => {
                     let table = lua.create_table()?;
                     table.set("type", "response")?;
                     table.set("session", session as i64)?;
+                    if let Some(proto) = protocol {
+                        table.set("name", proto.name.as_str())?;
+                    }
                     // Return raw body as Lua string for caller to decode
                     table.set("body", lua.create_string(&body)?)?;
                     if let Some(u) = ud {
@@ -220,7 +231,6 @@ impl LuaUserData for ResponderUserData {
 }
 
 /// Load binary schema and create Sproto userdata.
-#[allow(clippy::arc_with_non_send_sync)]
 pub fn lua_load_binary(_lua: &Lua, data: LuaString) -> LuaResult<SprotoUserData> {
     let sproto = binary_schema::load_binary(&data.as_bytes()).map_err(decode_error_to_lua)?;
     Ok(SprotoUserData {
@@ -25,6 +25,10 @@ local function load_rpc_schema()
     return sproto.load_binary(read_file(testdata .. "rpc_schema.bin"))
 end
 
+local function load_map_schema()
+    return sproto.load_binary(read_file(testdata .. "map_schema.bin"))
+end
+
 -- =============================================================================
 -- Basic Functionality Tests
 -- =============================================================================
@@ -450,6 +454,96 @@ describe("empty arrays", function()
     end)
 end)
 
+describe("maps", function()
+    local sp
+
+    before_each(function()
+        -- map_schema.bin Inventory: items 0 : *Item(id), counts 1 : *Count()
+        sp = load_map_schema()
+    end)
+
+    it("encodes and decodes *T(key) keyed by the element's key field", function()
+        local data = {items = {
+            [3] = {id = 3, name = "sword"},
+            [7] = {id = 7, name = "shield"},
+        }}
+        local encoded = sp:encode("Inventory", data)
+        local decoded = sp:decode("Inventory", encoded)
+
+        assert.is_table(decoded.items)
+        assert.are.equal("sword", decoded.items[3].name)
+        assert.are.equal(3, decoded.items[3].id)
+        assert.are.equal("shield", decoded.items[7].name)
+        assert.are.equal(7, decoded.items[7].id)
+    end)
+
+    it("encodes every value of a *T(key) table regardless of its keys", function()
+        local data = {items = {{id = 10, name = "bow"}, {id = 20, name = "arrow"}}}
+        local decoded = sp:decode("Inventory", sp:encode("Inventory", data))
+
+        assert.are.equal("bow", decoded.items[10].name)
+        assert.are.equal("arrow", decoded.items[20].name)
+        assert.is_nil(decoded.items[1])
+    end)
+
+    it("encodes and decodes *T() as a plain key/value table", function()
+        local data = {counts = {gold = 120, gems = 3}}
+        local encoded = sp:encode("Inventory", data)
+        local decoded = sp:decode("Inventory", encoded)
+
+        assert.are.same({gold = 120, gems = 3}, decoded.counts)
+    end)
+
+    it("round-trips both map kinds together", function()
+        local data = {
+            items = {[1] = {id = 1, name = "potion"}},
+            counts = {potion = 5},
+        }
+        local decoded = sp:decode("Inventory", sp:encode("Inventory", data))
+
+        assert.are.same(data, decoded)
+    end)
+
+    it("decodes empty maps as empty tables", function()
+        local decoded = sp:decode("Inventory", sp:encode("Inventory", {items = {}, counts = {}}))
+
+        assert.are.same({}, decoded.items)
+        assert.are.same({}, decoded.counts)
+    end)
+end)
+
+describe("decode limits", function()
+    local sp
+
+    before_each(function()
+        sp = load_schema()
+    end)
+
+    it("rejects nesting deeper than max_depth", function()
+        local data = {name = "root", children = {{name = "child", children = {{name = "leaf"}}}}}
+        local encoded = sp:encode("Person", data)
+
+        local decoded = sp:decode("Person", encoded, {max_depth = 2})
+        assert.are.equal("leaf", decoded.children[1].children[1].name)
+
+        local ok, err = pcall(sp.decode, sp, "Person", encoded, {max_depth = 1})
+        assert.is_false(ok)
+        assert.truthy(tostring(err):find("depth limit of 1", 1, true))
+    end)
+
+    it("rejects long arrays and strings", function()
+        local encoded = sp:encode("Person", {name = "Alice", numbers = {1, 2, 3}})
+
+        local ok, err = pcall(sp.decode, sp, "Person", encoded, {max_array_len = 2})
+        assert.is_false(ok)
+        assert.truthy(tostring(err):find("limit of 2 elements", 1, true))
+
+        ok, err = pcall(sp.decode, sp, "Person", encoded, {max_string_len = 4})
+        assert.is_false(ok)
+        assert.truthy(tostring(err):find("4-byte string limit", 1, true))
+    end)
+end)
+
 -- =============================================================================
 -- Cross-Compatibility Tests (using testdata from C/Lua reference implementation)
 -- =============================================================================
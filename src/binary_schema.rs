@@ -1,4 +1,4 @@
-//! Binary schema loader: loads pre-compiled sproto binary schemas.
+//! Binary schema loader and writer for pre-compiled sproto binary schemas.
 //!
 //! The binary schema format is a self-describing sproto message with this structure:
 //! ```text
@@ -31,12 +31,15 @@
 use std::collections::HashMap;
 
 use crate::codec::wire::*;
-use crate::error::DecodeError;
+use crate::error::{DecodeError, EncodeError};
 use crate::types::*;
 
 /// Load a pre-compiled binary schema into a `Sproto` object.
 ///
-/// This is equivalent to the C function `sproto_create()`.
+/// This is equivalent to the C function `sproto_create()`. The loaded schema
+/// is checked with `Sproto::validate`, so malformed map keys or duplicate
+/// names are rejected here rather than at encode time; the error describes
+/// the first problem found.
 pub fn load_binary(data: &[u8]) -> Result<Sproto, DecodeError> {
     let sz = data.len();
     if sz < SIZEOF_HEADER {
@@ -104,8 +107,16 @@ pub fn load_binary(data: &[u8]) -> Result<Sproto, DecodeError> {
         raw_protocols = decode_protocol_array(arr_data)?;
     }
 
-    // Build Sproto from raw data
-    build_sproto(raw_types, raw_protocols)
+    // Build Sproto from raw data; the format itself does not check map keys
+    // or duplicate names, so validate like a hand-built schema.
+    let sproto = build_sproto(raw_types, raw_protocols)?;
+    if let Err(errors) = sproto.validate() {
+        return Err(DecodeError::InvalidData(format!(
+            "invalid schema: {}",
+            errors[0]
+        )));
+    }
+    Ok(sproto)
 }
 
 // --- Internal types ---
@@ -524,3 +535,183 @@ fn build_sproto(
         protocols_by_tag,
     })
 }
+
+// --- Dump ---
+
+/// Serialize a `Sproto` into the binary schema format read by `load_binary`.
+///
+/// This is the inverse of `load_binary` and produces the same bytes as the
+/// C `sprotoparser.dump` for the same schema: protocols are written in tag
+/// order, and types in `types_list` order (the reference parser sorts them by
+/// name, as `parser::parse` does).
+///
+/// Fails if a tag, type index or decimal exponent does not fit an inline
+/// header value (`0x7fff` or more), which the reference format cannot express.
+pub fn dump_binary(sproto: &Sproto) -> Result<Vec<u8>, EncodeError> {
+    if sproto.types_list.is_empty() && sproto.protocols.is_empty() {
+        return Ok(vec![0, 0]);
+    }
+
+    let mut types = Vec::new();
+    for st in &sproto.types_list {
+        dump_type(st, &mut types)?;
+    }
+
+    let mut out = Vec::new();
+    if sproto.protocols.is_empty() {
+        push_header(&mut out, &[DATA]);
+        push_bytes(&mut out, &types);
+    } else {
+        let mut ordered: Vec<&Protocol> = sproto.protocols.iter().collect();
+        ordered.sort_by_key(|p| p.tag);
+        let mut protocols = Vec::new();
+        for p in ordered {
+            dump_protocol(p, &mut protocols)?;
+        }
+        push_header(&mut out, &[DATA, DATA]);
+        push_bytes(&mut out, &types);
+        push_bytes(&mut out, &protocols);
+    }
+    Ok(out)
+}
+
+/// Header slot for a value stored in the data part.
+const DATA: u16 = 0;
+/// Header slot skipping exactly one tag.
+const SKIP: u16 = 1;
+/// Header slot for the inline value 1 (`true`).
+const ONE: u16 = 4;
+
+/// Header slot for a small inline integer; `what` names the value in the
+/// error if it does not fit.
+fn inline(v: usize, what: impl FnOnce() -> String) -> Result<u16, EncodeError> {
+    if v >= 0x7fff {
+        return Err(EncodeError::Other(format!(
+            "{} {} is too large for a binary schema",
+            what(),
+            v
+        )));
+    }
+    Ok(((v + 1) * 2) as u16)
+}
+
+fn push_header(out: &mut Vec<u8>, slots: &[u16]) {
+    let start = out.len();
+    out.resize(start + SIZEOF_HEADER + slots.len() * SIZEOF_FIELD, 0);
+    write_u16_le(&mut out[start..], slots.len() as u16);
+    for (i, &slot) in slots.iter().enumerate() {
+        write_u16_le(&mut out[start + SIZEOF_HEADER + i * SIZEOF_FIELD..], slot);
+    }
+}
+
+fn push_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    let start = out.len();
+    out.resize(start + SIZEOF_LENGTH, 0);
+    write_u32_le(&mut out[start..], data.len() as u32);
+    out.extend_from_slice(data);
+}
+
+fn dump_type(st: &SprotoType, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let mut encoded = Vec::new();
+    if st.fields.is_empty() {
+        push_header(&mut encoded, &[DATA]);
+        push_bytes(&mut encoded, st.name.as_bytes());
+    } else {
+        let mut fields = Vec::new();
+        for f in &st.fields {
+            dump_field(st, f, &mut fields)?;
+        }
+        push_header(&mut encoded, &[DATA, DATA]);
+        push_bytes(&mut encoded, st.name.as_bytes());
+        push_bytes(&mut encoded, &fields);
+    }
+    push_bytes(out, &encoded);
+    Ok(())
+}
+
+fn dump_field(st: &SprotoType, f: &Field, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let what = |attr: &str| format!("type '{}': field '{}' {}", st.name, f.name, attr);
+    // name 0, buildin 1, type 2, tag 3, array 4, key 5, map 6
+    let mut slots = vec![DATA];
+    let builtin = match f.field_type {
+        FieldType::Integer => Some(0),
+        FieldType::Boolean => Some(1),
+        FieldType::String | FieldType::Binary => Some(2),
+        FieldType::Double => Some(3),
+        FieldType::Struct(_) => None,
+    };
+    match (builtin, &f.field_type) {
+        (Some(b), _) => {
+            slots.push((b + 1) * 2);
+            // `type` carries the builtin's extra: 1 for binary (a string
+            // subtype), N for `integer(N)`.
+            let extra = match f.field_type {
+                FieldType::Binary => ONE,
+                FieldType::Integer if f.decimal_precision > 0 => {
+                    inline(decimal_exponent(f.decimal_precision), || {
+                        what("decimal exponent")
+                    })?
+                }
+                _ => SKIP,
+            };
+            slots.push(extra);
+        }
+        (None, FieldType::Struct(idx)) => {
+            slots.push(SKIP);
+            slots.push(inline(*idx, || what("type index"))?);
+        }
+        (None, _) => unreachable!("only struct fields have no builtin type"),
+    }
+    slots.push(inline(f.tag as usize, || what("tag"))?);
+    if f.is_array {
+        slots.push(ONE);
+        if f.key_tag >= 0 {
+            slots.push(inline(f.key_tag as usize, || what("key tag"))?);
+            if f.is_map {
+                slots.push(ONE);
+            }
+        }
+    }
+
+    let mut encoded = Vec::new();
+    push_header(&mut encoded, &slots);
+    push_bytes(&mut encoded, f.name.as_bytes());
+    push_bytes(out, &encoded);
+    Ok(())
+}
+
+/// Inverse of `calc_pow(10, n)`: the `N` of an `integer(N)` field.
+fn decimal_exponent(precision: u32) -> usize {
+    let mut n = 0;
+    let mut p = precision;
+    while p >= 10 {
+        p /= 10;
+        n += 1;
+    }
+    n
+}
+
+fn dump_protocol(p: &Protocol, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let what = |attr: &str| format!("protocol '{}' {}", p.name, attr);
+    // name 0, tag 1, request 2, response 3, confirm 4
+    let mut slots = vec![DATA, inline(p.tag as usize, || what("tag"))?];
+    if p.request.is_some() || p.response.is_some() || p.confirm {
+        let request = match p.request {
+            Some(req) => inline(req, || what("request type index"))?,
+            None => SKIP,
+        };
+        slots.push(request);
+        if let Some(resp) = p.response {
+            slots.push(inline(resp, || what("response type index"))?);
+        } else if p.confirm {
+            slots.push(SKIP);
+            slots.push(ONE);
+        }
+    }
+
+    let mut encoded = Vec::new();
+    push_header(&mut encoded, &slots);
+    push_bytes(&mut encoded, p.name.as_bytes());
+    push_bytes(out, &encoded);
+    Ok(())
+}
@@ -0,0 +1,350 @@
+//! Fluent schema builder that refers to types by name.
+//!
+//! `Sproto::add_type` takes `FieldType::Struct(index)`, so types have to be
+//! added in dependency order and recursive types need a placeholder.
+//! `SchemaBuilder` takes type names instead, as written in a `.sproto` file,
+//! and resolves them all in `build()`:
+//!
+//! ```
+//! use sproto::builder::SchemaBuilder;
+//!
+//! let schema = SchemaBuilder::new()
+//!     .ty("Person", |t| {
+//!         t.field("name", 0, "string")
+//!             .array("children", 1, "Person") // self-reference
+//!             .array("phones", 2, "PhoneNumber") // resolves Person.PhoneNumber
+//!             .map_by("number")
+//!             .decimal("balance", 3, 2)
+//!     })
+//!     .ty("Person.PhoneNumber", |t| {
+//!         t.field("number", 0, "string").field("type", 1, "integer")
+//!     })
+//!     .protocol("login", 1, |p| {
+//!         p.request_type(|t| t.field("name", 0, "string"))
+//!             .response("Person")
+//!     })
+//!     .protocol("logout", 2, |p| p.response_nil())
+//!     .build()
+//!     .unwrap();
+//!
+//! assert!(schema.get_type("login.request").is_some());
+//! ```
+//!
+//! Names resolve like in the text parser: a reference from inside
+//! `Person.PhoneNumber` looks for `Person.PhoneNumber.X`, then `Person.X`,
+//! then `X`. The result is laid out like `parser::parse` output for the same
+//! schema: types sorted by full name, fields by tag, protocols by tag.
+
+use std::collections::HashMap;
+
+use crate::error::SchemaError;
+use crate::parser::resolve_type;
+use crate::types::{Field, FieldType, Sproto};
+
+/// Builds a `Sproto` from types and protocols that refer to each other by
+/// name.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaBuilder {
+    types: Vec<TypeBuilder>,
+    protocols: Vec<ProtocolBuilder>,
+}
+
+/// The fields of one type, passed to the closure of `SchemaBuilder::ty`.
+#[derive(Debug, Clone)]
+pub struct TypeBuilder {
+    name: String,
+    fields: Vec<FieldDef>,
+}
+
+/// The request and response of one protocol, passed to the closure of
+/// `SchemaBuilder::protocol`.
+#[derive(Debug, Clone)]
+pub struct ProtocolBuilder {
+    name: String,
+    tag: u16,
+    request: Option<String>,
+    response: Option<String>,
+    confirm: bool,
+    inline: Vec<TypeBuilder>,
+}
+
+#[derive(Debug, Clone)]
+struct FieldDef {
+    name: String,
+    tag: u16,
+    type_name: String,
+    array: bool,
+    /// `Some("")` for a `*T()` pair map.
+    key: Option<String>,
+    digits: Option<u32>,
+}
+
+impl SchemaBuilder {
+    /// Create an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define a type. `name` is the full, possibly dotted name, e.g.
+    /// `Person.PhoneNumber`.
+    pub fn ty(mut self, name: &str, f: impl FnOnce(TypeBuilder) -> TypeBuilder) -> Self {
+        self.types.push(f(TypeBuilder::new(name)));
+        self
+    }
+
+    /// Define a protocol.
+    pub fn protocol(
+        mut self,
+        name: &str,
+        tag: u16,
+        f: impl FnOnce(ProtocolBuilder) -> ProtocolBuilder,
+    ) -> Self {
+        let mut proto = f(ProtocolBuilder {
+            name: name.to_string(),
+            tag,
+            request: None,
+            response: None,
+            confirm: false,
+            inline: Vec::new(),
+        });
+        self.types.append(&mut proto.inline);
+        self.protocols.push(proto);
+        self
+    }
+
+    /// Resolve every type name and build the schema.
+    ///
+    /// Fails on references to undefined types, invalid map keys, and
+    /// anything `Sproto::validate` rejects, such as duplicate names or tags.
+    /// Only the first problem is returned, even if `validate` finds several.
+    pub fn build(mut self) -> Result<Sproto, SchemaError> {
+        self.types.sort_by(|a, b| a.name.cmp(&b.name));
+        if let Some(pair) = self.types.windows(2).find(|w| w[0].name == w[1].name) {
+            return Err(SchemaError::DuplicateType(pair[0].name.clone()));
+        }
+        let index: HashMap<&str, usize> = self
+            .types
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.name.as_str(), i))
+            .collect();
+
+        // Field types first, so map keys can look at any subtype.
+        let mut resolved = Vec::with_capacity(self.types.len());
+        for ty in &self.types {
+            let fields = ty
+                .fields
+                .iter()
+                .map(|fd| ty.resolve_field(&index, fd))
+                .collect::<Result<Vec<_>, _>>()?;
+            resolved.push(fields);
+        }
+
+        for (ti, ty) in self.types.iter().enumerate() {
+            for (fi, fd) in ty.fields.iter().enumerate() {
+                let Some(key) = &fd.key else { continue };
+                let FieldType::Struct(sub_idx) = resolved[ti][fi].field_type else {
+                    unreachable!("checked in resolve_field")
+                };
+                let sub_fields: &Vec<Field> = &resolved[sub_idx];
+                let key_field = if key.is_empty() {
+                    if sub_fields.len() != 2 {
+                        return Err(SchemaError::PairMapArity {
+                            type_name: ty.name.clone(),
+                            field: fd.name.clone(),
+                            sub_type: self.types[sub_idx].name.clone(),
+                        });
+                    }
+                    sub_fields.iter().min_by_key(|f| f.tag)
+                } else {
+                    sub_fields.iter().find(|f| *f.name == **key)
+                };
+                let Some(key_field) = key_field else {
+                    return Err(SchemaError::UnknownKey {
+                        type_name: ty.name.clone(),
+                        field: fd.name.clone(),
+                        key: key.clone(),
+                    });
+                };
+                if key_field.is_array || matches!(key_field.field_type, FieldType::Struct(_)) {
+                    return Err(SchemaError::NonScalarKey {
+                        type_name: ty.name.clone(),
+                        field: fd.name.clone(),
+                        key: key_field.name.to_string(),
+                    });
+                }
+                let key_tag = key_field.tag as i32;
+                let field = &mut resolved[ti][fi];
+                field.key_tag = key_tag;
+                field.is_map = key.is_empty();
+            }
+        }
+
+        let mut sproto = Sproto::new();
+        for (ty, mut fields) in self.types.iter().zip(resolved) {
+            fields.sort_by_key(|f| f.tag);
+            sproto.add_type(&ty.name, fields);
+        }
+
+        self.protocols.sort_by_key(|p| p.tag);
+        for proto in &self.protocols {
+            let lookup = |name: &Option<String>| match name {
+                None => Ok(None),
+                Some(name) => index.get(name.as_str()).map(|&i| Some(i)).ok_or_else(|| {
+                    SchemaError::UndefinedProtocolType {
+                        protocol: proto.name.clone(),
+                        name: name.clone(),
+                    }
+                }),
+            };
+            let request = lookup(&proto.request)?;
+            let response = lookup(&proto.response)?;
+            sproto.add_protocol(&proto.name, proto.tag, request, response, proto.confirm);
+        }
+
+        sproto
+            .validate()
+            .map_err(|errors| errors.into_iter().next().expect("validate found errors"))?;
+        Ok(sproto)
+    }
+}
+
+impl TypeBuilder {
+    fn new(name: &str) -> Self {
+        TypeBuilder {
+            name: name.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    fn push(
+        mut self,
+        name: &str,
+        tag: u16,
+        type_name: &str,
+        array: bool,
+        digits: Option<u32>,
+    ) -> Self {
+        self.fields.push(FieldDef {
+            name: name.to_string(),
+            tag,
+            type_name: type_name.to_string(),
+            array,
+            key: None,
+            digits,
+        });
+        self
+    }
+
+    /// Add a field of a builtin (`integer`, `boolean`, `string`, `binary`,
+    /// `double`) or user-defined type.
+    pub fn field(self, name: &str, tag: u16, type_name: &str) -> Self {
+        self.push(name, tag, type_name, false, None)
+    }
+
+    /// Add an array field, `*type_name`.
+    pub fn array(self, name: &str, tag: u16, type_name: &str) -> Self {
+        self.push(name, tag, type_name, true, None)
+    }
+
+    /// Add a fixed-point decimal field, `integer(digits)`.
+    pub fn decimal(self, name: &str, tag: u16, digits: u32) -> Self {
+        self.push(name, tag, "integer", false, Some(digits))
+    }
+
+    /// Make the last field a map keyed by the `key` field of its element
+    /// type, `*T(key)`. An empty `key` makes it a `*T()` map of a
+    /// two-field type, keyed by its first field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no field has been added yet.
+    pub fn map_by(mut self, key: &str) -> Self {
+        let field = self.fields.last_mut().expect("map_by must follow a field");
+        field.key = Some(key.to_string());
+        self
+    }
+
+    fn resolve_field(
+        &self,
+        index: &HashMap<&str, usize>,
+        fd: &FieldDef,
+    ) -> Result<Field, SchemaError> {
+        let field_type = match fd.type_name.as_str() {
+            "integer" => FieldType::Integer,
+            "boolean" => FieldType::Boolean,
+            "string" => FieldType::String,
+            "binary" => FieldType::Binary,
+            "double" => FieldType::Double,
+            name => match resolve_type(index, &self.name, name) {
+                Some(idx) => FieldType::Struct(idx),
+                None => {
+                    return Err(SchemaError::UndefinedType {
+                        type_name: self.name.clone(),
+                        field: fd.name.clone(),
+                        name: name.to_string(),
+                    })
+                }
+            },
+        };
+        if fd.key.is_some() && !(fd.array && matches!(field_type, FieldType::Struct(_))) {
+            return Err(SchemaError::KeyOnNonStructArray {
+                type_name: self.name.clone(),
+                field: fd.name.clone(),
+            });
+        }
+
+        let mut field = Field::new(&fd.name, fd.tag, field_type);
+        field.is_array = fd.array;
+        if let Some(digits) = fd.digits {
+            field.decimal_precision =
+                10u32
+                    .checked_pow(digits)
+                    .ok_or_else(|| SchemaError::DecimalOutOfRange {
+                        type_name: self.name.clone(),
+                        field: fd.name.clone(),
+                        digits,
+                    })?;
+        }
+        Ok(field)
+    }
+}
+
+impl ProtocolBuilder {
+    /// Use the named type as the request.
+    pub fn request(mut self, type_name: &str) -> Self {
+        self.request = Some(type_name.to_string());
+        self
+    }
+
+    /// Define the request inline, as the type `<protocol>.request`.
+    pub fn request_type(mut self, f: impl FnOnce(TypeBuilder) -> TypeBuilder) -> Self {
+        let name = format!("{}.request", self.name);
+        self.inline.push(f(TypeBuilder::new(&name)));
+        self.request = Some(name);
+        self
+    }
+
+    /// Use the named type as the response.
+    pub fn response(mut self, type_name: &str) -> Self {
+        self.response = Some(type_name.to_string());
+        self.confirm = false;
+        self
+    }
+
+    /// Define the response inline, as the type `<protocol>.response`.
+    pub fn response_type(mut self, f: impl FnOnce(TypeBuilder) -> TypeBuilder) -> Self {
+        let name = format!("{}.response", self.name);
+        self.inline.push(f(TypeBuilder::new(&name)));
+        self.response = Some(name);
+        self.confirm = false;
+        self
+    }
+
+    /// Expect an empty response, `response nil`.
+    pub fn response_nil(mut self) -> Self {
+        self.response = None;
+        self.confirm = true;
+        self
+    }
+}
@@ -3,14 +3,170 @@
 //! `StructDecoder` lazily iterates the wire header, yielding `DecodedField`
 //! values with typed accessors. It is the core decoding engine shared by the
 //! Direct API and the Serde adapter.
+//!
+//! Every decoder carries `DecodeLimits`, which nested decoders inherit, so
+//! one configuration bounds the nesting depth, element counts and sizes of
+//! a whole message, whichever front end walks it.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
+use crate::codec::map::MapKey;
 use crate::codec::wire::*;
 use crate::error::DecodeError;
 use crate::types::{Field, FieldType, Sproto, SprotoType};
 
+/// Nesting depth allowed by `DecodeLimits::default()`.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Resource limits applied while decoding a message.
+///
+/// Element counts and sizes are already bounded by the input length, so the
+/// default only bounds the struct nesting depth, which would otherwise let a
+/// recursive type such as `children 9 : *Person` exhaust the stack. Servers
+/// decoding untrusted input should lower the other limits to what their
+/// protocol needs:
+///
+/// ```
+/// use sproto::codec::DecodeLimits;
+///
+/// let limits = DecodeLimits {
+///     max_array_len: 1024,
+///     max_string_len: 64 * 1024,
+///     ..Default::default()
+/// };
+/// assert_eq!(limits.max_depth, sproto::codec::decoder::DEFAULT_MAX_DEPTH);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Levels of struct nesting below the message; `0` allows no nested
+    /// structs. Elements of a struct array are one level down.
+    pub max_depth: usize,
+    /// Elements in any one array field.
+    pub max_array_len: usize,
+    /// Bytes in a string or binary value, including array elements.
+    pub max_string_len: usize,
+    /// Header entries of every struct in the message, counted as each
+    /// struct is opened.
+    pub max_fields: usize,
+}
+
+impl DecodeLimits {
+    /// No limits at all, as before limits existed.
+    pub const fn unlimited() -> Self {
+        DecodeLimits {
+            max_depth: usize::MAX,
+            max_array_len: usize::MAX,
+            max_string_len: usize::MAX,
+            max_fields: usize::MAX,
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_depth: DEFAULT_MAX_DEPTH,
+            ..DecodeLimits::unlimited()
+        }
+    }
+}
+
+/// The limits of a message and the part of them already used, shared by
+/// every decoder of the message.
+#[derive(Debug, Clone)]
+struct Budget {
+    limits: DecodeLimits,
+    depth: usize,
+    /// Header entries left under `max_fields`; `None` when unlimited.
+    fields_left: Option<Arc<AtomicUsize>>,
+}
+
+impl Budget {
+    fn new(limits: DecodeLimits) -> Self {
+        Budget {
+            limits,
+            depth: 0,
+            fields_left: (limits.max_fields != usize::MAX)
+                .then(|| Arc::new(AtomicUsize::new(limits.max_fields))),
+        }
+    }
+
+    /// The budget for a struct one level down.
+    fn nested(&self) -> Result<Self, DecodeError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(DecodeError::DepthExceeded {
+                max: self.limits.max_depth,
+            });
+        }
+        Ok(Budget {
+            depth: self.depth + 1,
+            ..self.clone()
+        })
+    }
+
+    /// Charge `count` header entries against `max_fields`.
+    fn take_fields(&self, count: usize) -> Result<(), DecodeError> {
+        if let Some(left) = &self.fields_left {
+            // One atomic step, so decoders sharing the budget cannot lose
+            // each other's charges.
+            left.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |available| {
+                available.checked_sub(count)
+            })
+            .map_err(|_| DecodeError::TooManyFields {
+                max: self.limits.max_fields,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn check_array_len(&self, field: &Field, len: usize) -> Result<(), DecodeError> {
+        if len > self.limits.max_array_len {
+            return Err(DecodeError::TooManyElements {
+                field: field.name.to_string(),
+                max: self.limits.max_array_len,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_string_len(&self, field: &Field, len: usize) -> Result<(), DecodeError> {
+        if len > self.limits.max_string_len {
+            return Err(DecodeError::StringTooLong {
+                field: field.name.to_string(),
+                len,
+                max: self.limits.max_string_len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the limits that follow from a field's size alone: the length
+    /// of a string or binary, and the element count of a fixed-width array.
+    /// Variable-width arrays are checked as they are walked.
+    fn check_field(&self, field: &Field, data: &[u8]) -> Result<(), DecodeError> {
+        match (&field.field_type, field.is_array) {
+            (FieldType::String | FieldType::Binary, false) => {
+                self.check_string_len(field, data.len())
+            }
+            (FieldType::Boolean, true) => self.check_array_len(field, data.len()),
+            (FieldType::Integer | FieldType::Double, true) => match data.first() {
+                Some(&size) if size as usize == SIZEOF_INT32 || size as usize == SIZEOF_INT64 => {
+                    self.check_array_len(field, (data.len() - 1) / size as usize)
+                }
+                // Empty, or an element size the accessor rejects.
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Lazy wire-header decoder for a single sproto struct.
 ///
 /// Call `next_field()` repeatedly to iterate over encoded fields.
+#[derive(Clone)]
 pub struct StructDecoder<'a> {
     sproto: &'a Sproto,
     sproto_type: &'a SprotoType,
@@ -19,14 +175,35 @@ pub struct StructDecoder<'a> {
     header_idx: usize,
     tag: i32,
     data_offset: usize,
+    budget: Budget,
 }
 
 impl<'a> StructDecoder<'a> {
-    /// Create a decoder for the given wire bytes.
+    /// Create a decoder for the given wire bytes, with the default limits.
     pub fn new(
         sproto: &'a Sproto,
         sproto_type: &'a SprotoType,
         data: &'a [u8],
+    ) -> Result<Self, DecodeError> {
+        Self::with_limits(sproto, sproto_type, data, DecodeLimits::default())
+    }
+
+    /// Create a decoder that enforces `limits` on this struct and every
+    /// struct decoded through it.
+    pub fn with_limits(
+        sproto: &'a Sproto,
+        sproto_type: &'a SprotoType,
+        data: &'a [u8],
+        limits: DecodeLimits,
+    ) -> Result<Self, DecodeError> {
+        Self::with_budget(sproto, sproto_type, data, Budget::new(limits))
+    }
+
+    fn with_budget(
+        sproto: &'a Sproto,
+        sproto_type: &'a SprotoType,
+        data: &'a [u8],
+        budget: Budget,
     ) -> Result<Self, DecodeError> {
         let size = data.len();
         if size < SIZEOF_HEADER {
@@ -43,6 +220,7 @@ impl<'a> StructDecoder<'a> {
                 have: size,
             });
         }
+        budget.take_fields(fn_count)?;
         Ok(StructDecoder {
             sproto,
             sproto_type,
@@ -51,9 +229,16 @@ impl<'a> StructDecoder<'a> {
             header_idx: 0,
             tag: -1,
             data_offset: field_part_end,
+            budget,
         })
     }
 
+    /// The limits this decoder enforces.
+    #[inline]
+    pub fn limits(&self) -> &DecodeLimits {
+        &self.budget.limits
+    }
+
     /// Yield the next decoded field, or `None` when all fields are consumed.
     pub fn next_field(&mut self) -> Result<Option<DecodedField<'a>>, DecodeError> {
         let size = self.data.len();
@@ -98,12 +283,14 @@ impl<'a> StructDecoder<'a> {
                 Some(f) => f,
                 None => continue,
             };
+            self.budget.check_field(field, field_data)?;
 
             return Ok(Some(DecodedField {
                 sproto: self.sproto,
                 field,
                 inline_value: decoded_value,
                 data: field_data,
+                budget: self.budget.clone(),
             }));
         }
     }
@@ -118,6 +305,7 @@ pub struct DecodedField<'a> {
     field: &'a Field,
     inline_value: i32,
     data: &'a [u8],
+    budget: Budget,
 }
 
 impl<'a> DecodedField<'a> {
@@ -166,6 +354,12 @@ impl<'a> DecodedField<'a> {
         }
     }
 
+    /// Decode an `integer(N)` fixed-point field, divided by its decimal precision.
+    pub fn as_decimal(&self) -> Result<f64, DecodeError> {
+        self.check_decimal()?;
+        Ok(self.field.unscale_decimal(self.as_integer()?))
+    }
+
     /// Decode as a boolean value.
     pub fn as_bool(&self) -> Result<bool, DecodeError> {
         if self.inline_value >= 0 {
@@ -212,7 +406,7 @@ impl<'a> DecodedField<'a> {
         match &self.field.field_type {
             FieldType::Struct(idx) => {
                 let sub_type = &self.sproto.types_list[*idx];
-                StructDecoder::new(self.sproto, sub_type, self.data)
+                StructDecoder::with_budget(self.sproto, sub_type, self.data, self.budget.nested()?)
             }
             _ => Err(DecodeError::InvalidData(format!(
                 "field '{}' is not a struct type",
@@ -252,6 +446,26 @@ impl<'a> DecodedField<'a> {
         Ok(result)
     }
 
+    /// Decode an `integer(N)` fixed-point array field.
+    pub fn as_decimal_array(&self) -> Result<Vec<f64>, DecodeError> {
+        self.check_decimal()?;
+        Ok(self
+            .as_integer_array()?
+            .into_iter()
+            .map(|v| self.field.unscale_decimal(v))
+            .collect())
+    }
+
+    fn check_decimal(&self) -> Result<(), DecodeError> {
+        if self.field.decimal_precision == 0 {
+            return Err(DecodeError::InvalidData(format!(
+                "field '{}' is not an integer(N) decimal",
+                self.field.name
+            )));
+        }
+        Ok(())
+    }
+
     /// Decode as a boolean array.
     pub fn as_bool_array(&self) -> Vec<bool> {
         self.data.iter().map(|&b| b != 0).collect()
@@ -307,6 +521,8 @@ impl<'a> DecodedField<'a> {
                     have: self.data.len(),
                 });
             }
+            self.budget.check_array_len(self.field, result.len() + 1)?;
+            self.budget.check_string_len(self.field, esz)?;
             let s = std::str::from_utf8(&self.data[start..start + esz]).map_err(|e| {
                 DecodeError::InvalidData(format!(
                     "invalid utf-8 in array field '{}': {}",
@@ -338,6 +554,8 @@ impl<'a> DecodedField<'a> {
                     have: self.data.len(),
                 });
             }
+            self.budget.check_array_len(self.field, result.len() + 1)?;
+            self.budget.check_string_len(self.field, esz)?;
             result.push(&self.data[start..start + esz]);
             off = start + esz;
         }
@@ -352,8 +570,11 @@ impl<'a> DecodedField<'a> {
                 Ok(StructArrayIter {
                     sproto: self.sproto,
                     sub_type,
+                    field: self.field,
                     data: self.data,
                     offset: 0,
+                    count: 0,
+                    budget: self.budget.nested()?,
                 })
             }
             _ => Err(DecodeError::InvalidData(format!(
@@ -362,14 +583,106 @@ impl<'a> DecodedField<'a> {
             ))),
         }
     }
+
+    /// Decode a scalar field as a map key.
+    pub fn as_map_key(&self) -> Result<MapKey<'a>, DecodeError> {
+        if self.field.is_array {
+            return Err(DecodeError::InvalidData(format!(
+                "array field '{}' cannot be a map key",
+                self.field.name
+            )));
+        }
+        match &self.field.field_type {
+            FieldType::Integer => Ok(MapKey::Integer(self.as_integer()?)),
+            FieldType::Boolean => Ok(MapKey::Boolean(self.as_bool()?)),
+            FieldType::Double => Ok(MapKey::Double(self.as_double()?)),
+            FieldType::String => Ok(MapKey::String(self.as_string()?)),
+            FieldType::Binary => Ok(MapKey::Binary(self.as_bytes())),
+            FieldType::Struct(_) => Err(DecodeError::InvalidData(format!(
+                "struct field '{}' cannot be a map key",
+                self.field.name
+            ))),
+        }
+    }
+
+    /// Decode a `*T(key)` field as a map from each element's key to the element.
+    ///
+    /// As in C, a later element replaces an earlier one with the same key.
+    pub fn as_struct_map(&self) -> Result<HashMap<MapKey<'a>, StructDecoder<'a>>, DecodeError> {
+        if self.field.key_tag < 0 || self.field.is_map {
+            return Err(DecodeError::InvalidData(format!(
+                "field '{}' is not a *T(key) map",
+                self.field.name
+            )));
+        }
+        let mut map = HashMap::new();
+        for dec in self.as_struct_iter()? {
+            let dec = dec?;
+            let mut fields = dec.clone();
+            let mut key = None;
+            while let Some(f) = fields.next_field()? {
+                if f.tag() as i32 == self.field.key_tag {
+                    key = Some(f.as_map_key()?);
+                    break;
+                }
+            }
+            map.insert(self.map_key(key)?, dec);
+        }
+        Ok(map)
+    }
+
+    /// Decode a two-field `*T()` field as a map from key to value field.
+    pub fn as_pair_map(&self) -> Result<HashMap<MapKey<'a>, DecodedField<'a>>, DecodeError> {
+        if !self.field.is_map {
+            return Err(DecodeError::InvalidData(format!(
+                "field '{}' is not a *T() map",
+                self.field.name
+            )));
+        }
+        let mut map = HashMap::new();
+        for dec in self.as_struct_iter()? {
+            let mut dec = dec?;
+            let mut key = None;
+            let mut value = None;
+            while let Some(f) = dec.next_field()? {
+                if f.tag() as i32 == self.field.key_tag {
+                    key = Some(f.as_map_key()?);
+                } else if value.is_none() {
+                    value = Some(f);
+                }
+            }
+            let key = self.map_key(key)?;
+            let value = value.ok_or_else(|| {
+                DecodeError::InvalidData(format!(
+                    "map element in field '{}' is missing its value",
+                    self.field.name
+                ))
+            })?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+
+    fn map_key(&self, key: Option<MapKey<'a>>) -> Result<MapKey<'a>, DecodeError> {
+        key.ok_or_else(|| {
+            DecodeError::InvalidData(format!(
+                "map element in field '{}' is missing key tag {}",
+                self.field.name, self.field.key_tag
+            ))
+        })
+    }
 }
 
 /// Iterator over elements of a struct array.
 pub struct StructArrayIter<'a> {
     sproto: &'a Sproto,
     sub_type: &'a SprotoType,
+    field: &'a Field,
     data: &'a [u8],
     offset: usize,
+    count: usize,
+    /// The budget of the elements, one level below the array's struct.
+    budget: Budget,
 }
 
 impl<'a> Iterator for StructArrayIter<'a> {
@@ -393,9 +706,18 @@ impl<'a> Iterator for StructArrayIter<'a> {
                 have: self.data.len(),
             }));
         }
+        self.count += 1;
+        if let Err(e) = self.budget.check_array_len(self.field, self.count) {
+            return Some(Err(e));
+        }
         let elem_data = &self.data[start..start + esz];
         self.offset = start + esz;
-        Some(StructDecoder::new(self.sproto, self.sub_type, elem_data))
+        Some(StructDecoder::with_budget(
+            self.sproto,
+            self.sub_type,
+            elem_data,
+            self.budget.clone(),
+        ))
     }
 }
 
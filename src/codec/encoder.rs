@@ -4,9 +4,10 @@
 //! the Serde adapter. It accepts field values by tag number and assembles
 //! the sproto wire header + data section on `finish()`.
 
+use crate::codec::map::MapKey;
 use crate::codec::wire::*;
 use crate::error::EncodeError;
-use crate::types::{FieldType, Sproto, SprotoType};
+use crate::types::{Field, FieldType, Sproto, SprotoType};
 
 const MAX_STACK_FIELDS: usize = 32;
 
@@ -122,6 +123,12 @@ impl<'a> StructEncoder<'a> {
         Ok(())
     }
 
+    /// Encode an `integer(N)` fixed-point field, scaled by its decimal precision.
+    pub fn set_decimal(&mut self, tag: u16, value: f64) -> Result<(), EncodeError> {
+        let field = self.decimal_field(tag)?;
+        self.set_integer(tag, field.scale_decimal(value))
+    }
+
     /// Encode a boolean field (wire-encoded as integer 0/1).
     #[inline]
     pub fn set_bool(&mut self, tag: u16, value: bool) -> Result<(), EncodeError> {
@@ -244,6 +251,13 @@ impl<'a> StructEncoder<'a> {
         Ok(())
     }
 
+    /// Encode an `integer(N)` fixed-point array field.
+    pub fn set_decimal_array(&mut self, tag: u16, values: &[f64]) -> Result<(), EncodeError> {
+        let field = self.decimal_field(tag)?;
+        let scaled: Vec<i64> = values.iter().map(|&v| field.scale_decimal(v)).collect();
+        self.set_integer_array(tag, &scaled)
+    }
+
     /// Encode a boolean array field.
     pub fn set_bool_array(&mut self, tag: u16, values: &[bool]) -> Result<(), EncodeError> {
         let idx = self.resolve_tag(tag)?;
@@ -373,6 +387,115 @@ impl<'a> StructEncoder<'a> {
         Ok(())
     }
 
+    /// Encode a `*T(key)` map field from its values.
+    ///
+    /// `f` writes each value as one element, including its key field; as in
+    /// C, map keys are taken from the elements themselves.
+    pub fn encode_struct_map<I, F>(
+        &mut self,
+        tag: u16,
+        values: I,
+        mut f: F,
+    ) -> Result<(), EncodeError>
+    where
+        I: IntoIterator,
+        F: FnMut(&mut StructEncoder, I::Item) -> Result<(), EncodeError>,
+    {
+        self.check_map(tag, false)?;
+        self.encode_struct_array(tag, |arr| {
+            for value in values {
+                arr.encode_element(|e| f(e, value))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Encode a two-field `*T()` map field from key/value pairs.
+    ///
+    /// Each entry becomes one element: the key is written to the key field and
+    /// `f` writes the value, given the tag of the value field.
+    pub fn encode_pair_map<'k, I, K, V, F>(
+        &mut self,
+        tag: u16,
+        entries: I,
+        mut f: F,
+    ) -> Result<(), EncodeError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<MapKey<'k>>,
+        F: FnMut(&mut StructEncoder, u16, V) -> Result<(), EncodeError>,
+    {
+        let field = self.check_map(tag, true)?;
+        let key_tag = field.key_tag as u16;
+        let value_tag = match &field.field_type {
+            FieldType::Struct(i) => self.sproto.types_list[*i]
+                .fields
+                .iter()
+                .map(|f| f.tag)
+                .find(|&t| t != key_tag),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            EncodeError::Other(format!("map field '{}' has no value field", field.name))
+        })?;
+        self.encode_struct_array(tag, |arr| {
+            for (key, value) in entries {
+                arr.encode_element(|e| {
+                    e.set_map_key(key_tag, key.into())?;
+                    f(e, value_tag, value)
+                })?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Encode a map key into the scalar field it belongs to.
+    fn set_map_key(&mut self, tag: u16, key: MapKey) -> Result<(), EncodeError> {
+        let idx = self.resolve_tag(tag)?;
+        let field = &self.sproto_type.fields[idx];
+        match (&field.field_type, key) {
+            (FieldType::Integer, MapKey::Integer(v)) => self.set_integer(tag, v),
+            (FieldType::Boolean, MapKey::Boolean(v)) => self.set_bool(tag, v),
+            (FieldType::Double, MapKey::Double(v)) => self.set_double(tag, v),
+            (FieldType::Double, MapKey::Integer(v)) => self.set_double(tag, v as f64),
+            (FieldType::String | FieldType::Binary, MapKey::String(v)) => self.set_string(tag, v),
+            (FieldType::String | FieldType::Binary, MapKey::Binary(v)) => self.set_bytes(tag, v),
+            (_, key) => Err(EncodeError::TypeMismatch {
+                field: field.name.to_string(),
+                expected: field.schema_type_name(self.sproto),
+                actual: key.type_name().into(),
+            }),
+        }
+    }
+
+    /// Check that `tag` is an `integer(N)` field.
+    fn decimal_field(&self, tag: u16) -> Result<&'a Field, EncodeError> {
+        let idx = self.resolve_tag(tag)?;
+        let field = &self.sproto_type.fields[idx];
+        if field.decimal_precision == 0 {
+            return Err(EncodeError::TypeMismatch {
+                field: field.name.to_string(),
+                expected: "integer(N) decimal".into(),
+                actual: field.schema_type_name(self.sproto),
+            });
+        }
+        Ok(field)
+    }
+
+    /// Check that `tag` is a `*T()` map (`pair`) or a `*T(key)` map field.
+    fn check_map(&self, tag: u16, pair: bool) -> Result<&'a Field, EncodeError> {
+        let idx = self.resolve_tag(tag)?;
+        let field = &self.sproto_type.fields[idx];
+        if field.key_tag < 0 || field.is_map != pair {
+            return Err(EncodeError::TypeMismatch {
+                field: field.name.to_string(),
+                expected: if pair { "*T() map" } else { "*T(key) map" }.into(),
+                actual: field.schema_type_name(self.sproto),
+            });
+        }
+        Ok(field)
+    }
+
     /// Assemble the wire header + data and finalize the encoded bytes.
     ///
     /// Returns the output buffer so callers (e.g. nested struct serializers)
@@ -0,0 +1,121 @@
+//! Keys of `*T(key)` and `*T()` map fields.
+//!
+//! On the wire a map is a plain struct array; the schema marks one field of
+//! the element type as its key. `MapKey` holds that field's scalar value so
+//! decoded elements can be looked up by it.
+
+use std::hash::{Hash, Hasher};
+
+/// A scalar map key, borrowing strings and bytes from the wire buffer.
+///
+/// Doubles compare and hash by their bit pattern, so a `MapKey` can always be
+/// used in a `HashMap`.
+#[derive(Debug, Clone, Copy)]
+pub enum MapKey<'a> {
+    Integer(i64),
+    Boolean(bool),
+    Double(f64),
+    String(&'a str),
+    Binary(&'a [u8]),
+}
+
+impl MapKey<'_> {
+    /// Name of the variant, for error messages.
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            MapKey::Integer(_) => "integer",
+            MapKey::Boolean(_) => "boolean",
+            MapKey::Double(_) => "double",
+            MapKey::String(_) => "string",
+            MapKey::Binary(_) => "binary",
+        }
+    }
+}
+
+impl PartialEq for MapKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MapKey::Integer(a), MapKey::Integer(b)) => a == b,
+            (MapKey::Boolean(a), MapKey::Boolean(b)) => a == b,
+            (MapKey::Double(a), MapKey::Double(b)) => a.to_bits() == b.to_bits(),
+            (MapKey::String(a), MapKey::String(b)) => a == b,
+            (MapKey::Binary(a), MapKey::Binary(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MapKey<'_> {}
+
+impl Hash for MapKey<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            MapKey::Integer(v) => v.hash(state),
+            MapKey::Boolean(v) => v.hash(state),
+            MapKey::Double(v) => v.to_bits().hash(state),
+            MapKey::String(v) => v.hash(state),
+            MapKey::Binary(v) => v.hash(state),
+        }
+    }
+}
+
+impl From<i64> for MapKey<'_> {
+    fn from(v: i64) -> Self {
+        MapKey::Integer(v)
+    }
+}
+
+impl From<&i64> for MapKey<'_> {
+    fn from(v: &i64) -> Self {
+        MapKey::Integer(*v)
+    }
+}
+
+impl From<bool> for MapKey<'_> {
+    fn from(v: bool) -> Self {
+        MapKey::Boolean(v)
+    }
+}
+
+impl From<&bool> for MapKey<'_> {
+    fn from(v: &bool) -> Self {
+        MapKey::Boolean(*v)
+    }
+}
+
+impl From<f64> for MapKey<'_> {
+    fn from(v: f64) -> Self {
+        MapKey::Double(v)
+    }
+}
+
+impl From<&f64> for MapKey<'_> {
+    fn from(v: &f64) -> Self {
+        MapKey::Double(*v)
+    }
+}
+
+impl<'a> From<&'a str> for MapKey<'a> {
+    fn from(v: &'a str) -> Self {
+        MapKey::String(v)
+    }
+}
+
+impl<'a> From<&'a String> for MapKey<'a> {
+    fn from(v: &'a String) -> Self {
+        MapKey::String(v)
+    }
+}
+
+impl<'a> From<&'a [u8]> for MapKey<'a> {
+    fn from(v: &'a [u8]) -> Self {
+        MapKey::Binary(v)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for MapKey<'a> {
+    fn from(v: &'a Vec<u8>) -> Self {
+        MapKey::Binary(v)
+    }
+}
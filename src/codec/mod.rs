@@ -2,6 +2,10 @@ pub mod wire;
 
 pub mod decoder;
 pub mod encoder;
+pub mod map;
+pub mod traits;
 
-pub use decoder::{DecodedField, StructArrayIter, StructDecoder};
+pub use decoder::{DecodeLimits, DecodedField, StructArrayIter, StructDecoder};
 pub use encoder::{StructArrayEncoder, StructEncoder};
+pub use map::MapKey;
+pub use traits::{SprotoDecode, SprotoEncode, SprotoSchema};
@@ -0,0 +1,107 @@
+//! Traits for Rust types with a fixed sproto layout.
+//!
+//! These are normally implemented with `#[derive(SprotoEncode, SprotoDecode,
+//! SprotoSchema)]` from the `sproto-derive` crate (re-exported under the
+//! `derive` feature). The generated code calls `StructEncoder` /
+//! `StructDecoder` by tag directly, without looking fields up by name.
+
+use crate::codec::{DecodeLimits, StructDecoder, StructEncoder};
+use crate::error::{DecodeError, EncodeError, SchemaError};
+use crate::types::{Sproto, SprotoType};
+
+/// A type that writes itself into a `StructEncoder` by tag.
+pub trait SprotoEncode {
+    /// Write every present field into `enc`.
+    fn encode_fields(&self, enc: &mut StructEncoder) -> Result<(), EncodeError>;
+
+    /// Encode as `sproto_type`, appending to `output`.
+    fn encode_to(
+        &self,
+        sproto: &Sproto,
+        sproto_type: &SprotoType,
+        output: &mut Vec<u8>,
+    ) -> Result<(), EncodeError> {
+        let mut enc = StructEncoder::new(sproto, sproto_type, output);
+        self.encode_fields(&mut enc)?;
+        enc.finish();
+        Ok(())
+    }
+}
+
+/// A type that reads itself from a `StructDecoder` by tag.
+pub trait SprotoDecode: Sized {
+    /// Consume the fields of `dec`; absent fields take their default value.
+    fn decode_fields(dec: &mut StructDecoder) -> Result<Self, DecodeError>;
+
+    /// Decode `data` encoded as `sproto_type`.
+    fn decode_from(
+        sproto: &Sproto,
+        sproto_type: &SprotoType,
+        data: &[u8],
+    ) -> Result<Self, DecodeError> {
+        let mut dec = StructDecoder::new(sproto, sproto_type, data)?;
+        Self::decode_fields(&mut dec)
+    }
+
+    /// Decode `data` encoded as `sproto_type`, enforcing `limits`.
+    fn decode_from_with_limits(
+        sproto: &Sproto,
+        sproto_type: &SprotoType,
+        data: &[u8],
+        limits: DecodeLimits,
+    ) -> Result<Self, DecodeError> {
+        let mut dec = StructDecoder::with_limits(sproto, sproto_type, data, limits)?;
+        Self::decode_fields(&mut dec)
+    }
+}
+
+/// `()` is the empty message, for protocols without a request or response type.
+impl SprotoEncode for () {
+    fn encode_fields(&self, _enc: &mut StructEncoder) -> Result<(), EncodeError> {
+        Ok(())
+    }
+}
+
+impl SprotoDecode for () {
+    fn decode_fields(_dec: &mut StructDecoder) -> Result<Self, DecodeError> {
+        Ok(())
+    }
+}
+
+/// A type that can describe its own sproto type.
+pub trait SprotoSchema {
+    /// The sproto type name, e.g. `Person` or `Person.PhoneNumber`.
+    const TYPE_NAME: &'static str;
+
+    /// Add this type, and every type it references, to `sproto`.
+    ///
+    /// Returns the type index. Registering an already present name returns
+    /// the existing index, so shared and recursive types are added once.
+    ///
+    /// # Panics
+    ///
+    /// Derived impls panic if an already present type does not match; see
+    /// [`try_register`](Self::try_register).
+    fn register(sproto: &mut Sproto) -> usize;
+
+    /// Like [`register`](Self::register), but checks any already present type
+    /// of the same name with [`Sproto::check_type_fields`], returning
+    /// `SchemaError::FieldMismatch` instead of reusing a type whose fields
+    /// have drifted from the Rust definition.
+    ///
+    /// On error, `sproto` may keep the types added before the mismatch.
+    fn try_register(sproto: &mut Sproto) -> Result<usize, SchemaError> {
+        Self::register_with(sproto, &mut Vec::new())
+    }
+
+    /// Implementation of `try_register`. `visiting` names the types whose
+    /// fields are being built, so a recursive reference returns its index
+    /// instead of checking the type again. The default only calls `register`.
+    #[doc(hidden)]
+    fn register_with(
+        sproto: &mut Sproto,
+        _visiting: &mut Vec<&'static str>,
+    ) -> Result<usize, SchemaError> {
+        Ok(Self::register(sproto))
+    }
+}
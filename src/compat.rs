@@ -0,0 +1,521 @@
+//! Wire-compatibility check between two versions of a schema.
+//!
+//! Clients and servers are often deployed independently, so a peer built
+//! from the old schema keeps talking to one built from the new schema.
+//! `check` compares the two and lists every difference, marking the ones
+//! that make the peers misread each other:
+//!
+//! ```
+//! use sproto::{compat, parser};
+//!
+//! let old = parser::parse(".Person { name 0 : string\n age 1 : integer }").unwrap();
+//! let new = parser::parse(".Person { name 0 : string\n age 1 : string }").unwrap();
+//!
+//! let report = compat::check(&old, &new);
+//! assert!(!report.is_compatible());
+//! assert_eq!(
+//!     report.to_string(),
+//!     "breaking: Person.age (tag 1): type changed from integer to string\n"
+//! );
+//! ```
+//!
+//! The wire format identifies fields by tag and protocols by tag, so fields
+//! are matched by tag and types and protocols by name. Adding, removing or
+//! renaming a field is safe: decoders skip unknown tags and leave missing
+//! fields absent. Giving an existing tag a different meaning is not.
+
+use std::fmt;
+
+use crate::types::{Field, FieldType, Protocol, Sproto, SprotoType};
+
+/// A difference between the old and the new schema.
+///
+/// Field changes name the type, the field (as named in the new schema) and
+/// its tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A tag holds a different type, e.g. `integer` → `string`, a different
+    /// struct, or a `*T(key)` map turned into a `*T()` pair map.
+    FieldTypeChanged {
+        type_name: String,
+        field: String,
+        tag: u16,
+        old: String,
+        new: String,
+    },
+    /// A scalar field became an array or the reverse.
+    FieldArrayChanged {
+        type_name: String,
+        field: String,
+        tag: u16,
+        old: String,
+        new: String,
+    },
+    /// The key of a `*T(key)` map moved to another tag of `T`.
+    KeyTagChanged {
+        type_name: String,
+        field: String,
+        tag: u16,
+        old: i32,
+        new: i32,
+    },
+    /// The precision of an `integer(N)` field changed, rescaling its values.
+    DecimalPrecisionChanged {
+        type_name: String,
+        field: String,
+        tag: u16,
+        old: u32,
+        new: u32,
+    },
+    /// A field kept its name but moved to another tag; old peers still use
+    /// the old tag.
+    FieldTagChanged {
+        type_name: String,
+        field: String,
+        old: u16,
+        new: u16,
+    },
+    /// A protocol was removed.
+    ProtocolRemoved { name: String, tag: u16 },
+    /// A protocol kept its name but moved to another tag.
+    ProtocolTagChanged { name: String, old: u16, new: u16 },
+    /// A protocol's request type changed; `None` is no request.
+    RequestTypeChanged {
+        protocol: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// A protocol's response type changed; `None` is no response and
+    /// `Some("nil")` is `response nil`.
+    ResponseTypeChanged {
+        protocol: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+
+    /// A type was added.
+    TypeAdded { name: String },
+    /// A type was removed. Fields and protocols that used it are reported
+    /// separately.
+    TypeRemoved { name: String },
+    /// A field was added under a new tag.
+    FieldAdded {
+        type_name: String,
+        field: String,
+        tag: u16,
+    },
+    /// A field was removed; peers on the old schema still send it and it
+    /// is skipped.
+    FieldRemoved {
+        type_name: String,
+        field: String,
+        tag: u16,
+    },
+    /// A field was renamed without changing its tag or type.
+    FieldRenamed {
+        type_name: String,
+        tag: u16,
+        old: String,
+        new: String,
+    },
+    /// A protocol was added.
+    ProtocolAdded { name: String, tag: u16 },
+    /// A protocol was renamed without changing its tag.
+    ProtocolRenamed { tag: u16, old: String, new: String },
+}
+
+impl Change {
+    /// Whether peers on the old and the new schema would misread each other.
+    pub fn is_breaking(&self) -> bool {
+        matches!(
+            self,
+            Change::FieldTypeChanged { .. }
+                | Change::FieldArrayChanged { .. }
+                | Change::KeyTagChanged { .. }
+                | Change::DecimalPrecisionChanged { .. }
+                | Change::FieldTagChanged { .. }
+                | Change::ProtocolRemoved { .. }
+                | Change::ProtocolTagChanged { .. }
+                | Change::RequestTypeChanged { .. }
+                | Change::ResponseTypeChanged { .. }
+        )
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::FieldTypeChanged {
+                type_name,
+                field,
+                tag,
+                old,
+                new,
+            } => write!(
+                f,
+                "{}.{} (tag {}): type changed from {} to {}",
+                type_name, field, tag, old, new
+            ),
+            Change::FieldArrayChanged {
+                type_name,
+                field,
+                tag,
+                old,
+                new,
+            } => write!(
+                f,
+                "{}.{} (tag {}): changed from {} to {}",
+                type_name, field, tag, old, new
+            ),
+            Change::KeyTagChanged {
+                type_name,
+                field,
+                tag,
+                old,
+                new,
+            } => write!(
+                f,
+                "{}.{} (tag {}): map key tag changed from {} to {}",
+                type_name, field, tag, old, new
+            ),
+            Change::DecimalPrecisionChanged {
+                type_name,
+                field,
+                tag,
+                old,
+                new,
+            } => write!(
+                f,
+                "{}.{} (tag {}): decimal precision changed from {} to {}",
+                type_name, field, tag, old, new
+            ),
+            Change::FieldTagChanged {
+                type_name,
+                field,
+                old,
+                new,
+            } => write!(
+                f,
+                "{}.{}: tag changed from {} to {}",
+                type_name, field, old, new
+            ),
+            Change::ProtocolRemoved { name, tag } => {
+                write!(f, "protocol {} (tag {}) removed", name, tag)
+            }
+            Change::ProtocolTagChanged { name, old, new } => {
+                write!(f, "protocol {}: tag changed from {} to {}", name, old, new)
+            }
+            Change::RequestTypeChanged { protocol, old, new } => write!(
+                f,
+                "protocol {}: request changed from {} to {}",
+                protocol,
+                or_none(old),
+                or_none(new)
+            ),
+            Change::ResponseTypeChanged { protocol, old, new } => write!(
+                f,
+                "protocol {}: response changed from {} to {}",
+                protocol,
+                or_none(old),
+                or_none(new)
+            ),
+            Change::TypeAdded { name } => write!(f, "type {} added", name),
+            Change::TypeRemoved { name } => write!(f, "type {} removed", name),
+            Change::FieldAdded {
+                type_name,
+                field,
+                tag,
+            } => write!(f, "{}.{} (tag {}) added", type_name, field, tag),
+            Change::FieldRemoved {
+                type_name,
+                field,
+                tag,
+            } => write!(f, "{}.{} (tag {}) removed", type_name, field, tag),
+            Change::FieldRenamed {
+                type_name,
+                tag,
+                old,
+                new,
+            } => write!(
+                f,
+                "{} (tag {}): field renamed from {} to {}",
+                type_name, tag, old, new
+            ),
+            Change::ProtocolAdded { name, tag } => {
+                write!(f, "protocol {} (tag {}) added", name, tag)
+            }
+            Change::ProtocolRenamed { tag, old, new } => {
+                write!(f, "protocol tag {}: renamed from {} to {}", tag, old, new)
+            }
+        }
+    }
+}
+
+fn or_none(name: &Option<String>) -> &str {
+    name.as_deref().unwrap_or("none")
+}
+
+/// The result of `check`: every change, types first, in schema order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub changes: Vec<Change>,
+}
+
+impl Report {
+    /// Whether no change is breaking.
+    pub fn is_compatible(&self) -> bool {
+        !self.changes.iter().any(Change::is_breaking)
+    }
+
+    /// The breaking changes.
+    pub fn breaking(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(|c| c.is_breaking())
+    }
+
+    /// The changes that keep old and new peers compatible.
+    pub fn safe(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(|c| !c.is_breaking())
+    }
+}
+
+/// One change per line, prefixed with `breaking:` or `safe:`.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            let kind = if change.is_breaking() {
+                "breaking"
+            } else {
+                "safe"
+            };
+            writeln!(f, "{}: {}", kind, change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare `new` against `old`.
+pub fn check(old: &Sproto, new: &Sproto) -> Report {
+    let mut changes = Vec::new();
+
+    for old_type in &old.types_list {
+        match new.get_type(&old_type.name) {
+            Some(new_type) => check_type(old, old_type, new, new_type, &mut changes),
+            None => changes.push(Change::TypeRemoved {
+                name: old_type.name.clone(),
+            }),
+        }
+    }
+    for new_type in &new.types_list {
+        if old.get_type(&new_type.name).is_none() {
+            changes.push(Change::TypeAdded {
+                name: new_type.name.clone(),
+            });
+        }
+    }
+
+    check_protocols(old, new, &mut changes);
+    Report { changes }
+}
+
+fn check_type(
+    old: &Sproto,
+    old_type: &SprotoType,
+    new: &Sproto,
+    new_type: &SprotoType,
+    changes: &mut Vec<Change>,
+) {
+    let type_name = &new_type.name;
+    for old_field in &old_type.fields {
+        let Some(new_field) = new_type.find_field_by_tag(old_field.tag) else {
+            // Same name under another tag: old peers still send the old tag.
+            match new_type.find_field_by_name(&old_field.name) {
+                Some(moved) => changes.push(Change::FieldTagChanged {
+                    type_name: type_name.clone(),
+                    field: moved.name.to_string(),
+                    old: old_field.tag,
+                    new: moved.tag,
+                }),
+                None => changes.push(Change::FieldRemoved {
+                    type_name: type_name.clone(),
+                    field: old_field.name.to_string(),
+                    tag: old_field.tag,
+                }),
+            }
+            continue;
+        };
+        if old_field.name != new_field.name {
+            changes.push(Change::FieldRenamed {
+                type_name: type_name.clone(),
+                tag: old_field.tag,
+                old: old_field.name.to_string(),
+                new: new_field.name.to_string(),
+            });
+        }
+        if let Some(change) = check_field(type_name, old, old_field, new, new_field) {
+            changes.push(change);
+        }
+    }
+    for new_field in &new_type.fields {
+        let moved = old_type
+            .find_field_by_name(&new_field.name)
+            .is_some_and(|f| new_type.find_field_by_tag(f.tag).is_none());
+        if old_type.find_field_by_tag(new_field.tag).is_none() && !moved {
+            changes.push(Change::FieldAdded {
+                type_name: type_name.clone(),
+                field: new_field.name.to_string(),
+                tag: new_field.tag,
+            });
+        }
+    }
+}
+
+/// The first breaking difference between two fields with the same tag.
+fn check_field(
+    type_name: &str,
+    old: &Sproto,
+    old_field: &Field,
+    new: &Sproto,
+    new_field: &Field,
+) -> Option<Change> {
+    let type_name = type_name.to_string();
+    let field = new_field.name.to_string();
+    let tag = new_field.tag;
+
+    if old_field.is_array != new_field.is_array {
+        return Some(Change::FieldArrayChanged {
+            type_name,
+            field,
+            tag,
+            old: field_type_spec(old, old_field),
+            new: field_type_spec(new, new_field),
+        });
+    }
+    let same_base = match (&old_field.field_type, &new_field.field_type) {
+        (FieldType::Struct(a), FieldType::Struct(b)) => {
+            old.types_list[*a].name == new.types_list[*b].name
+        }
+        (a, b) => a == b,
+    };
+    if !same_base || old_field.is_map != new_field.is_map {
+        return Some(Change::FieldTypeChanged {
+            type_name,
+            field,
+            tag,
+            old: field_type_spec(old, old_field),
+            new: field_type_spec(new, new_field),
+        });
+    }
+    if old_field.decimal_precision != new_field.decimal_precision {
+        return Some(Change::DecimalPrecisionChanged {
+            type_name,
+            field,
+            tag,
+            old: old_field.decimal_precision,
+            new: new_field.decimal_precision,
+        });
+    }
+    if old_field.key_tag != new_field.key_tag {
+        return Some(Change::KeyTagChanged {
+            type_name,
+            field,
+            tag,
+            old: old_field.key_tag,
+            new: new_field.key_tag,
+        });
+    }
+    None
+}
+
+/// The field's type as written in a `.sproto` file, with its map key.
+fn field_type_spec(sproto: &Sproto, field: &Field) -> String {
+    let name = field.schema_type_name(sproto);
+    match &field.field_type {
+        FieldType::Struct(_) if field.is_map => format!("{}()", name),
+        FieldType::Struct(idx) if field.key_tag >= 0 => {
+            match sproto.types_list[*idx].find_field_by_tag(field.key_tag as u16) {
+                Some(key) => format!("{}({})", name, key.name),
+                None => format!("{}(tag {})", name, field.key_tag),
+            }
+        }
+        _ => name,
+    }
+}
+
+fn check_protocols(old: &Sproto, new: &Sproto, changes: &mut Vec<Change>) {
+    for old_proto in &old.protocols {
+        let Some(new_proto) = new.get_protocol(&old_proto.name) else {
+            match new.get_protocol_by_tag(old_proto.tag) {
+                Some(renamed) if old.get_protocol(&renamed.name).is_none() => {
+                    changes.push(Change::ProtocolRenamed {
+                        tag: old_proto.tag,
+                        old: old_proto.name.clone(),
+                        new: renamed.name.clone(),
+                    });
+                    check_messages(old, old_proto, new, renamed, changes);
+                }
+                _ => changes.push(Change::ProtocolRemoved {
+                    name: old_proto.name.clone(),
+                    tag: old_proto.tag,
+                }),
+            }
+            continue;
+        };
+        if old_proto.tag != new_proto.tag {
+            changes.push(Change::ProtocolTagChanged {
+                name: new_proto.name.clone(),
+                old: old_proto.tag,
+                new: new_proto.tag,
+            });
+        }
+        check_messages(old, old_proto, new, new_proto, changes);
+    }
+    for new_proto in &new.protocols {
+        let renamed = old
+            .get_protocol_by_tag(new_proto.tag)
+            .is_some_and(|p| new.get_protocol(&p.name).is_none());
+        if old.get_protocol(&new_proto.name).is_none() && !renamed {
+            changes.push(Change::ProtocolAdded {
+                name: new_proto.name.clone(),
+                tag: new_proto.tag,
+            });
+        }
+    }
+}
+
+fn check_messages(
+    old: &Sproto,
+    old_proto: &Protocol,
+    new: &Sproto,
+    new_proto: &Protocol,
+    changes: &mut Vec<Change>,
+) {
+    let type_name =
+        |sproto: &Sproto, idx: Option<usize>| idx.map(|i| sproto.types_list[i].name.clone());
+    let response_name = |sproto: &Sproto, proto: &Protocol| {
+        if proto.confirm {
+            Some("nil".to_string())
+        } else {
+            type_name(sproto, proto.response)
+        }
+    };
+
+    let (old_req, new_req) = (
+        type_name(old, old_proto.request),
+        type_name(new, new_proto.request),
+    );
+    if old_req != new_req {
+        changes.push(Change::RequestTypeChanged {
+            protocol: new_proto.name.clone(),
+            old: old_req,
+            new: new_req,
+        });
+    }
+    let (old_resp, new_resp) = (response_name(old, old_proto), response_name(new, new_proto));
+    if old_resp != new_resp {
+        changes.push(Change::ResponseTypeChanged {
+            protocol: new_proto.name.clone(),
+            old: old_resp,
+            new: new_resp,
+        });
+    }
+}
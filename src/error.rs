@@ -38,6 +38,26 @@ pub enum DecodeError {
         field: String,
         source: std::string::FromUtf8Error,
     },
+
+    /// Structs are nested deeper than `DecodeLimits::max_depth`.
+    #[error("struct nesting exceeds the depth limit of {max}")]
+    DepthExceeded { max: usize },
+
+    /// An array has more elements than `DecodeLimits::max_array_len`.
+    #[error("array field '{field}' exceeds the limit of {max} elements")]
+    TooManyElements { field: String, max: usize },
+
+    /// A string or binary is longer than `DecodeLimits::max_string_len`.
+    #[error("field '{field}' of {len} bytes exceeds the {max}-byte string limit")]
+    StringTooLong {
+        field: String,
+        len: usize,
+        max: usize,
+    },
+
+    /// The message has more fields than `DecodeLimits::max_fields`.
+    #[error("message exceeds the limit of {max} fields")]
+    TooManyFields { max: usize },
 }
 
 /// Errors from the pack/unpack compression.
@@ -45,6 +65,121 @@ pub enum DecodeError {
 pub enum PackError {
     #[error("invalid packed data: {0}")]
     InvalidData(String),
+
+    /// The unpacked data would exceed the limit given to
+    /// `unpack_with_limit`.
+    #[error("unpacked data exceeds the limit of {max} bytes")]
+    TooLarge { max: usize },
+}
+
+/// Errors from the text schema parser, with a 1-based source position.
+#[derive(Debug, thiserror::Error)]
+#[error("{line}:{column}: {message}")]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// Problems in a hand-built schema, from `Sproto::validate` and the
+/// `try_add_*` builder methods.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SchemaError {
+    #[error("type '{0}' is defined more than once")]
+    DuplicateType(String),
+
+    #[error("type '{type_name}': field tag {tag} is out of order")]
+    UnsortedTags { type_name: String, tag: u16 },
+
+    #[error("type '{type_name}': field tag {tag} is used more than once")]
+    DuplicateTag { type_name: String, tag: u16 },
+
+    #[error("type '{type_name}': field name '{field}' is used more than once")]
+    DuplicateFieldName { type_name: String, field: String },
+
+    #[error("type '{type_name}': field '{field}' refers to type index {index}, but there are {count} types")]
+    DanglingTypeRef {
+        type_name: String,
+        field: String,
+        index: usize,
+        count: usize,
+    },
+
+    #[error("type '{type_name}': field '{field}' has a map key but is not a struct array")]
+    KeyOnNonStructArray { type_name: String, field: String },
+
+    #[error("type '{type_name}': map field '{field}' has key tag {key_tag}, which its element type lacks")]
+    MissingKey {
+        type_name: String,
+        field: String,
+        key_tag: i32,
+    },
+
+    #[error("type '{type_name}': map field '{field}' is keyed by '{key}', which is not a scalar")]
+    NonScalarKey {
+        type_name: String,
+        field: String,
+        key: String,
+    },
+
+    #[error("type '{type_name}': field '{field}' refers to undefined type '{name}'")]
+    UndefinedType {
+        type_name: String,
+        field: String,
+        name: String,
+    },
+
+    #[error(
+        "type '{type_name}': map field '{field}' is keyed by '{key}', which its element type lacks"
+    )]
+    UnknownKey {
+        type_name: String,
+        field: String,
+        key: String,
+    },
+
+    #[error("type '{type_name}': map field '{field}' requires type '{sub_type}' to have exactly two fields")]
+    PairMapArity {
+        type_name: String,
+        field: String,
+        sub_type: String,
+    },
+
+    #[error("type '{type_name}': decimal precision {digits} of field '{field}' is out of range")]
+    DecimalOutOfRange {
+        type_name: String,
+        field: String,
+        digits: u32,
+    },
+
+    #[error(
+        "type '{type_name}': field '{field}' (tag {tag}) does not match the existing definition"
+    )]
+    FieldMismatch {
+        type_name: String,
+        field: String,
+        tag: u16,
+    },
+
+    #[error("protocol '{0}' is defined more than once")]
+    DuplicateProtocol(String),
+
+    #[error("protocol tag {tag} is used by both '{first}' and '{second}'")]
+    DuplicateProtocolTag {
+        tag: u16,
+        first: String,
+        second: String,
+    },
+
+    #[error("protocol '{protocol}' refers to type index {index}, but there are {count} types")]
+    DanglingProtocolType {
+        protocol: String,
+        index: usize,
+        count: usize,
+    },
+
+    #[error("protocol '{protocol}' refers to undefined type '{name}'")]
+    UndefinedProtocolType { protocol: String, name: String },
 }
 
 /// Errors from the RPC module.
@@ -56,6 +191,44 @@ pub enum RpcError {
     #[error("unknown session {0}")]
     UnknownSession(u64),
 
+    #[error("session {0} is already pending")]
+    DuplicateSession(u64),
+
+    #[error("frame of {size} bytes exceeds the {max}-byte limit")]
+    FrameTooLarge { size: usize, max: usize },
+
+    #[error("session {0} timed out")]
+    Timeout(u64),
+
+    #[error("connection closed")]
+    Closed,
+
+    #[error("no handler for protocol '{0}'")]
+    Unhandled(String),
+
+    #[error("handler error: {0}")]
+    Handler(String),
+
+    #[error("protocol '{0}' is one-way and cannot carry a session")]
+    OneWay(String),
+
+    #[error("invalid {kind} body for protocol '{protocol}': {reason}")]
+    InvalidBody {
+        protocol: String,
+        kind: &'static str,
+        reason: String,
+    },
+
+    #[error("invalid response body for session {session} of protocol '{protocol}': {reason}")]
+    InvalidResponse {
+        session: u64,
+        protocol: String,
+        reason: String,
+    },
+
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+
     #[error("decode error: {0}")]
     Decode(#[from] DecodeError),
 
@@ -77,6 +250,12 @@ pub enum SprotoError {
 
     #[error(transparent)]
     Rpc(#[from] RpcError),
+
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
 }
 
 /// Result type alias for sproto operations.
@@ -18,11 +18,21 @@
 //! ```
 
 pub mod binary_schema;
+pub mod builder;
 pub mod codec;
+pub mod compat;
 pub mod error;
 pub mod pack;
+pub mod parser;
+pub mod printer;
 pub mod rpc;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod types;
+pub mod value;
 
+pub use codec::{SprotoDecode, SprotoEncode, SprotoSchema};
 pub use error::SprotoError;
+#[cfg(feature = "derive")]
+pub use sproto_derive::{SprotoDecode, SprotoEncode, SprotoSchema};
 pub use types::Sproto;
@@ -0,0 +1,569 @@
+mod simd;
+mod stream;
+
+use crate::error::PackError;
+use simd::Kernel;
+
+pub use stream::{PackWriter, UnpackReader};
+
+/// Pack (compress) sproto binary data using the zero-packing algorithm.
+///
+/// Similar to Cap'n Proto packing. Each 8-byte word is reduced to a tag byte
+/// followed by non-zero content bytes. Tag 0xFF handles the case where most
+/// bytes are non-zero.
+pub fn pack(src: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(packed_len_bound(src.len()));
+    simd::pack(&src, &mut result);
+    result
+}
+
+/// Pack `src`, appending the output to `out`.
+///
+/// Reserves [`packed_len_bound`] bytes up front, so a buffer that is cleared
+/// and reused between calls stops reallocating once it has grown to fit
+/// the largest message.
+pub fn pack_into(src: &[u8], out: &mut Vec<u8>) {
+    out.reserve(packed_len_bound(src.len()));
+    simd::pack(&src, out);
+}
+
+/// Upper bound on the packed size of `len` unpacked bytes.
+///
+/// Words with at most 7 non-zero bytes pack to at most 8 bytes. A run of
+/// full words costs 2 extra bytes, but a run that ends before the input
+/// does is followed by a word of at most 6 bytes, so only runs cut at 256
+/// words and the final run add to the unpacked size.
+pub fn packed_len_bound(len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    let words = len.div_ceil(8);
+    words * 8 + 2 * (words / 256 + 1)
+}
+
+/// Pack the concatenation of `head` and `tail`, appending to `out`.
+///
+/// Produces the same bytes as packing `[head, tail].concat()`, without
+/// building the concatenation; used to pack an RPC header and its body.
+pub(crate) fn pack_chain_append(head: &[u8], tail: &[u8], out: &mut Vec<u8>) {
+    out.reserve(packed_len_bound(head.len() + tail.len()));
+    simd::pack(&Chain(head, tail), out);
+}
+
+/// Unpacked input to the packer.
+trait PackSource {
+    fn len(&self) -> usize;
+
+    /// Load the 8-byte word at `offset`, zero-padding past the end.
+    fn load_word(&self, offset: usize) -> u64;
+
+    /// Append the bytes in `start..end` (clamped to the end) to `out`.
+    fn copy_to(&self, start: usize, end: usize, out: &mut Vec<u8>);
+
+    /// Tag bytes of the `tags.len()` words starting at `offset`.
+    fn tags<K: Kernel>(&self, offset: usize, tags: &mut [u8]);
+}
+
+impl PackSource for &[u8] {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    #[inline(always)]
+    fn load_word(&self, offset: usize) -> u64 {
+        load_word(self, offset)
+    }
+
+    #[inline(always)]
+    fn copy_to(&self, start: usize, end: usize, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self[start..end.min(<[u8]>::len(self))]);
+    }
+
+    #[inline(always)]
+    fn tags<K: Kernel>(&self, offset: usize, tags: &mut [u8]) {
+        let full = tags.len().min((<[u8]>::len(self) - offset) / 8);
+        K::tags(&self[offset..offset + full * 8], &mut tags[..full]);
+        if full < tags.len() {
+            // The last, partial word.
+            tags[full] = compute_tag_word(load_word(self, offset + full * 8)).0;
+        }
+    }
+}
+
+/// Two slices packed as one.
+struct Chain<'a>(&'a [u8], &'a [u8]);
+
+impl PackSource for Chain<'_> {
+    fn len(&self) -> usize {
+        self.0.len() + self.1.len()
+    }
+
+    #[inline(always)]
+    fn load_word(&self, offset: usize) -> u64 {
+        let head = self.0.len();
+        if offset + 8 <= head {
+            load_word(self.0, offset)
+        } else if offset >= head {
+            load_word(self.1, offset - head)
+        } else {
+            // The word straddles both slices.
+            let mut buf = [0u8; 8];
+            let n = head - offset;
+            buf[..n].copy_from_slice(&self.0[offset..]);
+            let m = self.1.len().min(8 - n);
+            buf[n..n + m].copy_from_slice(&self.1[..m]);
+            u64::from_le_bytes(buf)
+        }
+    }
+
+    fn copy_to(&self, start: usize, end: usize, out: &mut Vec<u8>) {
+        let head = self.0.len();
+        if start < head {
+            out.extend_from_slice(&self.0[start..end.min(head)]);
+        }
+        if end > head {
+            let tail_start = start.max(head) - head;
+            let tail_end = (end - head).min(self.1.len());
+            if tail_start < tail_end {
+                out.extend_from_slice(&self.1[tail_start..tail_end]);
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn tags<K: Kernel>(&self, offset: usize, tags: &mut [u8]) {
+        let head = self.0.len();
+        let mut w = 0;
+        while w < tags.len() {
+            let off = offset + w * 8;
+            let (slice, start) = if off >= head {
+                (self.1, off - head)
+            } else {
+                (self.0, off)
+            };
+            let n = (tags.len() - w).min((slice.len() - start) / 8);
+            if n > 0 {
+                K::tags(&slice[start..start + n * 8], &mut tags[w..w + n]);
+                w += n;
+            } else {
+                // A word straddling both slices, or the last, partial word.
+                tags[w] = compute_tag_word(self.load_word(off)).0;
+                w += 1;
+            }
+        }
+    }
+}
+
+/// Words whose tags are computed at a time.
+const TAG_BLOCK: usize = 32;
+
+/// Pack `src`, appending to `result`, with the tag and compaction
+/// primitives of `K`.
+#[inline(always)]
+fn pack_source<S: PackSource, K: Kernel>(src: &S, result: &mut Vec<u8>) {
+    let srcsz = src.len();
+    let mut i = 0;
+
+    // State for 0xFF run batching
+    let mut ff_src_start: usize = 0;
+    let mut ff_n: usize = 0;
+
+    let mut tags = [0u8; TAG_BLOCK];
+    while i < srcsz {
+        let words = TAG_BLOCK.min((srcsz - i).div_ceil(8));
+        src.tags::<K>(i, &mut tags[..words]);
+
+        for &tag in &tags[..words] {
+            let notzero = tag.count_ones();
+
+            // Promote 6/7 non-zero to 8 ONLY when already in an FF run
+            let effective = if (notzero == 6 || notzero == 7) && ff_n > 0 {
+                8
+            } else {
+                notzero
+            };
+
+            if effective == 8 {
+                if ff_n == 0 {
+                    ff_src_start = i;
+                }
+                ff_n += 1;
+                if ff_n == 256 {
+                    flush_ff(src, result, ff_src_start, ff_n);
+                    ff_n = 0;
+                }
+            } else {
+                if ff_n > 0 {
+                    flush_ff(src, result, ff_src_start, ff_n);
+                    ff_n = 0;
+                }
+
+                // Normal pack: tag byte + non-zero bytes in one batch
+                result.push(tag);
+                if tag != 0 {
+                    K::compact(result, src.load_word(i), tag);
+                }
+            }
+
+            i += 8;
+        }
+    }
+
+    if ff_n > 0 {
+        flush_ff(src, result, ff_src_start, ff_n);
+    }
+}
+
+/// Load an 8-byte word from src at offset, zero-padding if past end.
+#[inline(always)]
+fn load_word(src: &[u8], offset: usize) -> u64 {
+    if offset + 8 <= src.len() {
+        let bytes: [u8; 8] = src[offset..offset + 8].try_into().unwrap();
+        u64::from_le_bytes(bytes)
+    } else {
+        let mut buf = [0u8; 8];
+        buf[..src.len() - offset].copy_from_slice(&src[offset..]);
+        u64::from_le_bytes(buf)
+    }
+}
+
+/// Compute tag byte and non-zero count from an 8-byte word (branchless).
+#[inline(always)]
+fn compute_tag_word(word: u64) -> (u8, u32) {
+    if word == 0 {
+        return (0, 0);
+    }
+    let b = word.to_le_bytes();
+    let tag = ((b[0] != 0) as u8)
+        | (((b[1] != 0) as u8) << 1)
+        | (((b[2] != 0) as u8) << 2)
+        | (((b[3] != 0) as u8) << 3)
+        | (((b[4] != 0) as u8) << 4)
+        | (((b[5] != 0) as u8) << 5)
+        | (((b[6] != 0) as u8) << 6)
+        | (((b[7] != 0) as u8) << 7);
+    (tag, tag.count_ones())
+}
+
+/// Write non-zero bytes from a word into result, guided by tag bits.
+#[inline(always)]
+fn pack_nonzero(result: &mut Vec<u8>, word: u64, tag: u8) {
+    let b = word.to_le_bytes();
+    let mut buf = [0u8; 8];
+    let mut n = 0usize;
+    if tag & 0x01 != 0 {
+        buf[n] = b[0];
+        n += 1;
+    }
+    if tag & 0x02 != 0 {
+        buf[n] = b[1];
+        n += 1;
+    }
+    if tag & 0x04 != 0 {
+        buf[n] = b[2];
+        n += 1;
+    }
+    if tag & 0x08 != 0 {
+        buf[n] = b[3];
+        n += 1;
+    }
+    if tag & 0x10 != 0 {
+        buf[n] = b[4];
+        n += 1;
+    }
+    if tag & 0x20 != 0 {
+        buf[n] = b[5];
+        n += 1;
+    }
+    if tag & 0x40 != 0 {
+        buf[n] = b[6];
+        n += 1;
+    }
+    if tag & 0x80 != 0 {
+        buf[n] = b[7];
+        n += 1;
+    }
+    result.extend_from_slice(&buf[..n]);
+}
+
+/// Flush an 0xFF run: write tag, count, and raw source data directly.
+fn flush_ff<S: PackSource>(src: &S, result: &mut Vec<u8>, src_start: usize, n: usize) {
+    let total_bytes = n * 8;
+    result.push(0xFF);
+    result.push((n - 1) as u8);
+    let start = result.len();
+    src.copy_to(src_start, src_start + total_bytes, result);
+    // Zero-pad if source was shorter than total_bytes (last partial word)
+    result.resize(start + total_bytes, 0);
+}
+
+/// Unpack (decompress) sproto packed data.
+///
+/// Packed data can expand up to 8 times (a zero tag byte is a whole zero
+/// word), or up to 2048 bytes per 0xFF run header; use
+/// [`unpack_with_limit`] for untrusted input.
+pub fn unpack(src: &[u8]) -> Result<Vec<u8>, PackError> {
+    unpack_with_limit(src, usize::MAX)
+}
+
+/// Unpack `src`, failing with `PackError::TooLarge` instead of producing
+/// more than `max_len` bytes.
+///
+/// The unpacked size includes the zero padding of the last word, so it is
+/// always a multiple of 8. Input is rejected as soon as a segment would
+/// cross the limit, before the output grows past it.
+pub fn unpack_with_limit(src: &[u8], max_len: usize) -> Result<Vec<u8>, PackError> {
+    // Pre-allocate: each tag byte produces 8 output bytes
+    let mut result = Vec::with_capacity(src.len().saturating_mul(2).min(max_len));
+    unpack_append(src, &mut result, max_len)?;
+    Ok(result)
+}
+
+/// Unpack `src`, appending the output to `out`.
+///
+/// On error `out` is left as it was. Call `out.clear()` first to reuse a
+/// buffer across messages; [`unpacked_len`] gives the exact size if the
+/// buffer should be sized up front.
+pub fn unpack_into(src: &[u8], out: &mut Vec<u8>) -> Result<(), PackError> {
+    unpack_into_with_limit(src, out, usize::MAX)
+}
+
+/// Like [`unpack_into`], appending at most `max_len` bytes to `out`; see
+/// [`unpack_with_limit`].
+pub fn unpack_into_with_limit(
+    src: &[u8],
+    out: &mut Vec<u8>,
+    max_len: usize,
+) -> Result<(), PackError> {
+    let start = out.len();
+    unpack_append(src, out, max_len).inspect_err(|_| out.truncate(start))
+}
+
+/// Size of the unpacked output of `src`, without unpacking it.
+///
+/// Fails on the same malformed input as [`unpack`].
+pub fn unpacked_len(src: &[u8]) -> Result<usize, PackError> {
+    let len = src.len();
+    let mut i = 0;
+    let mut total = 0usize;
+    while i < len {
+        let header = src[i];
+        i += 1;
+        let (out, consumed) = if header == 0xFF {
+            if i >= len {
+                return Err(ff_without_count());
+            }
+            let n = (src[i] as usize + 1) * 8;
+            i += 1;
+            if i + n > len {
+                return Err(ff_truncated(n, len - i));
+            }
+            (n, n)
+        } else {
+            let notzero = header.count_ones() as usize;
+            if i + notzero > len {
+                return Err(normal_truncated());
+            }
+            (8, notzero)
+        };
+        total += out;
+        i += consumed;
+    }
+    Ok(total)
+}
+
+fn ff_without_count() -> PackError {
+    PackError::InvalidData("0xFF tag at end of data without count byte".into())
+}
+
+fn ff_truncated(need: usize, have: usize) -> PackError {
+    PackError::InvalidData(format!(
+        "0xFF run needs {} bytes but only {} available",
+        need, have
+    ))
+}
+
+fn normal_truncated() -> PackError {
+    PackError::InvalidData("truncated packed data in normal segment".into())
+}
+
+/// Unpack `src`, appending at most `max_len` bytes to `result`.
+///
+/// On error, `result` may hold part of the output.
+fn unpack_append(src: &[u8], result: &mut Vec<u8>, max_len: usize) -> Result<(), PackError> {
+    let len = src.len();
+    let mut i = 0;
+    let end = result.len().saturating_add(max_len);
+
+    while i < len {
+        let header = src[i];
+        i += 1;
+
+        if header == 0xFF {
+            if i >= len {
+                return Err(ff_without_count());
+            }
+            let n = (src[i] as usize + 1) * 8;
+            i += 1;
+            if i + n > len {
+                return Err(ff_truncated(n, len - i));
+            }
+            if result.len() + n > end {
+                return Err(PackError::TooLarge { max: max_len });
+            }
+            result.extend_from_slice(&src[i..i + n]);
+            i += n;
+        } else if result.len() + 8 > end {
+            return Err(PackError::TooLarge { max: max_len });
+        } else if header == 0x00 {
+            // All-zero word: write 8 zeros at once
+            result.extend_from_slice(&[0u8; 8]);
+        } else {
+            // Single bounds check: count non-zero bytes needed from source
+            let notzero = header.count_ones() as usize;
+            if i + notzero > len {
+                return Err(normal_truncated());
+            }
+            // Write 8 bytes: zero-initialized, then fill non-zero positions
+            let out_start = result.len();
+            result.resize(out_start + 8, 0);
+            let out = &mut result[out_start..];
+            let data = &src[i..i + notzero];
+            let mut si = 0;
+            if header & 0x01 != 0 {
+                out[0] = data[si];
+                si += 1;
+            }
+            if header & 0x02 != 0 {
+                out[1] = data[si];
+                si += 1;
+            }
+            if header & 0x04 != 0 {
+                out[2] = data[si];
+                si += 1;
+            }
+            if header & 0x08 != 0 {
+                out[3] = data[si];
+                si += 1;
+            }
+            if header & 0x10 != 0 {
+                out[4] = data[si];
+                si += 1;
+            }
+            if header & 0x20 != 0 {
+                out[5] = data[si];
+                si += 1;
+            }
+            if header & 0x40 != 0 {
+                out[6] = data[si];
+                si += 1;
+            }
+            if header & 0x80 != 0 {
+                out[7] = data[si];
+            }
+            i += notzero;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack_basic() {
+        // Example from README:
+        // unpacked: 08 00 00 00 03 00 02 00  19 00 00 00 aa 01 00 00
+        // packed:   51 08 03 02  31 19 aa 01
+        let unpacked = vec![
+            0x08, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0x19, 0x00, 0x00, 0x00, 0xaa, 0x01,
+            0x00, 0x00,
+        ];
+        let expected_packed = vec![0x51, 0x08, 0x03, 0x02, 0x31, 0x19, 0xaa, 0x01];
+
+        let packed = pack(&unpacked);
+        assert_eq!(packed, expected_packed);
+
+        let roundtrip = unpack(&packed).unwrap();
+        assert_eq!(roundtrip, unpacked);
+    }
+
+    #[test]
+    fn test_pack_unpack_ff() {
+        // 30 bytes of 0x8a -> pads to 32 bytes (4 words)
+        // First 3 words (24 bytes) all non-zero -> FF run
+        // Last word: 8a 8a 8a 8a 8a 8a 00 00 -> normal
+        let mut unpacked = vec![0x8a; 30];
+        let packed = pack(&unpacked);
+
+        // Should start with FF tag
+        assert_eq!(packed[0], 0xFF);
+
+        let roundtrip = unpack(&packed).unwrap();
+        // Roundtrip pads to 8-byte boundary
+        unpacked.extend_from_slice(&[0, 0]);
+        assert_eq!(roundtrip, unpacked);
+    }
+
+    #[test]
+    fn test_pack_empty() {
+        assert_eq!(pack(&[]), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_pack_all_zeros() {
+        let zeros = vec![0u8; 16];
+        let packed = pack(&zeros);
+        // Two 0x00 tag bytes, no data bytes
+        assert_eq!(packed, vec![0x00, 0x00]);
+        let roundtrip = unpack(&packed).unwrap();
+        assert_eq!(roundtrip, zeros);
+    }
+
+    #[test]
+    fn test_pack_chain_matches_concat() {
+        // Splits inside words, inside FF runs, and at word boundaries.
+        let mut data = vec![0x8au8; 40];
+        data.extend_from_slice(&[0, 1, 0, 2, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend((1..=30u8).map(|b| b.wrapping_mul(37)));
+        for split in 0..=data.len() {
+            let (head, tail) = data.split_at(split);
+            let mut out = vec![0xEE];
+            pack_chain_append(head, tail, &mut out);
+            assert_eq!(&out[1..], &pack(&data)[..], "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn test_unpack_with_limit() {
+        let packed = pack(&[0x8a; 30]);
+        assert_eq!(unpack_with_limit(&packed, 32).unwrap().len(), 32);
+        assert!(matches!(
+            unpack_with_limit(&packed, 31),
+            Err(PackError::TooLarge { max: 31 })
+        ));
+
+        // 1000 zero tag bytes would expand to 8000 bytes.
+        let bomb = vec![0u8; 1000];
+        let err = unpack_with_limit(&bomb, 64).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unpacked data exceeds the limit of 64 bytes"
+        );
+
+        let mut out = vec![1, 2];
+        assert!(unpack_into_with_limit(&bomb, &mut out, 64).is_err());
+        assert_eq!(out, vec![1, 2]);
+        unpack_into_with_limit(&bomb[..8], &mut out, 64).unwrap();
+        assert_eq!(out.len(), 66);
+    }
+
+    #[test]
+    fn test_unpack_invalid() {
+        // 0xFF at end without count byte
+        assert!(unpack(&[0xFF]).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! Vectorised primitives for `pack`, selected at runtime.
+//!
+//! Packing spends its time on two steps per 8-byte word: computing the tag
+//! byte (a bitmask of the non-zero bytes) and copying the non-zero bytes
+//! out. On x86_64 the tags of 2 (SSE2) or 4 (AVX2) words are computed with
+//! one byte compare and `movemask`, and with SSSE3 the non-zero bytes are
+//! gathered by a single `pshufb` driven by a 256-entry shuffle table. Other
+//! targets use the portable scalar code. Every kernel produces exactly the
+//! bytes of the scalar one; only the speed differs.
+
+use super::{compute_tag_word, pack_nonzero, pack_source, PackSource};
+
+/// Tag and compaction primitives used by `pack_source`.
+pub(super) trait Kernel {
+    /// Write the tag of each word of `src` to `tags`; `src` holds exactly
+    /// `tags.len()` whole words.
+    fn tags(src: &[u8], tags: &mut [u8]);
+
+    /// Append the bytes of `word` selected by `tag` to `result`.
+    fn compact(result: &mut Vec<u8>, word: u64, tag: u8);
+}
+
+/// Scalar code, one word at a time.
+#[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+pub(super) struct Portable;
+
+impl Kernel for Portable {
+    #[inline(always)]
+    fn tags(src: &[u8], tags: &mut [u8]) {
+        for (tag, word) in tags.iter_mut().zip(src.chunks_exact(8)) {
+            *tag = compute_tag_word(u64::from_le_bytes(word.try_into().unwrap())).0;
+        }
+    }
+
+    #[inline(always)]
+    fn compact(result: &mut Vec<u8>, word: u64, tag: u8) {
+        pack_nonzero(result, word, tag);
+    }
+}
+
+/// Pack `src` with the fastest kernel the CPU supports.
+#[inline]
+pub(super) fn pack<S: PackSource>(src: &S, result: &mut Vec<u8>) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        x86::pack(src, result)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        pack_source::<S, Portable>(src, result)
+    }
+}
+
+/// `pshufb` masks gathering the bytes selected by each tag to the front.
+///
+/// Entry `tag` lists the indices of the set bits of `tag` in ascending
+/// order; the remaining lanes are 0x80, which `pshufb` turns into zeros.
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+const SHUFFLE: [[u8; 8]; 256] = {
+    let mut table = [[0x80u8; 8]; 256];
+    let mut tag = 0;
+    while tag < 256 {
+        let mut n = 0;
+        let mut bit = 0;
+        while bit < 8 {
+            if tag & (1 << bit) != 0 {
+                table[tag][n] = bit as u8;
+                n += 1;
+            }
+            bit += 1;
+        }
+        tag += 1;
+    }
+    table
+};
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    use super::{pack_nonzero, pack_source, Kernel, PackSource, SHUFFLE};
+
+    /// Best instruction set available, detected on first use.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[repr(u8)]
+    pub(in crate::pack) enum Level {
+        Sse2 = 1,
+        Ssse3 = 2,
+        Avx2 = 3,
+    }
+
+    static LEVEL: AtomicU8 = AtomicU8::new(0);
+
+    pub(in crate::pack) fn level() -> Level {
+        match LEVEL.load(Ordering::Relaxed) {
+            1 => Level::Sse2,
+            2 => Level::Ssse3,
+            3 => Level::Avx2,
+            _ => {
+                let level = if is_x86_feature_detected!("avx2") {
+                    Level::Avx2
+                } else if is_x86_feature_detected!("ssse3") {
+                    Level::Ssse3
+                } else {
+                    Level::Sse2
+                };
+                LEVEL.store(level as u8, Ordering::Relaxed);
+                level
+            }
+        }
+    }
+
+    #[inline]
+    pub(in crate::pack) fn pack<S: PackSource>(src: &S, result: &mut Vec<u8>) {
+        // SAFETY: `level()` only returns instruction sets the CPU supports.
+        unsafe { pack_with(level(), src, result) }
+    }
+
+    /// Pack with the kernel for `level`.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support `level`, i.e. it must be at most `level()`.
+    pub(in crate::pack) unsafe fn pack_with<S: PackSource>(
+        level: Level,
+        src: &S,
+        result: &mut Vec<u8>,
+    ) {
+        // SAFETY: guaranteed by the caller; SSE2 is part of the x86_64
+        // baseline.
+        unsafe {
+            match level {
+                Level::Avx2 => pack_avx2(src, result),
+                Level::Ssse3 => pack_ssse3(src, result),
+                Level::Sse2 => pack_source::<S, Sse2>(src, result),
+            }
+        }
+    }
+
+    // The whole packing loop is compiled with the target features enabled,
+    // so the kernel calls below are inlined into it.
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn pack_avx2<S: PackSource>(src: &S, result: &mut Vec<u8>) {
+        pack_source::<S, Avx2>(src, result)
+    }
+
+    #[target_feature(enable = "ssse3")]
+    unsafe fn pack_ssse3<S: PackSource>(src: &S, result: &mut Vec<u8>) {
+        pack_source::<S, Ssse3>(src, result)
+    }
+
+    /// Tags of 2 words per 16-byte compare; scalar compaction.
+    struct Sse2;
+
+    /// SSE2 tags; `pshufb` compaction.
+    struct Ssse3;
+
+    /// Tags of 4 words per 32-byte compare; `pshufb` compaction.
+    struct Avx2;
+
+    impl Kernel for Sse2 {
+        #[inline(always)]
+        fn tags(src: &[u8], tags: &mut [u8]) {
+            // SAFETY: SSE2 is part of the x86_64 baseline.
+            unsafe { tags_sse2(src, tags) }
+        }
+
+        #[inline(always)]
+        fn compact(result: &mut Vec<u8>, word: u64, tag: u8) {
+            pack_nonzero(result, word, tag);
+        }
+    }
+
+    impl Kernel for Ssse3 {
+        #[inline(always)]
+        fn tags(src: &[u8], tags: &mut [u8]) {
+            // SAFETY: as for `Sse2`.
+            unsafe { tags_sse2(src, tags) }
+        }
+
+        #[inline(always)]
+        fn compact(result: &mut Vec<u8>, word: u64, tag: u8) {
+            // SAFETY: only used from `pack_ssse3` and `pack_avx2`, which
+            // run once SSSE3 support has been detected.
+            unsafe { compact_ssse3(result, word, tag) }
+        }
+    }
+
+    impl Kernel for Avx2 {
+        #[inline(always)]
+        fn tags(src: &[u8], tags: &mut [u8]) {
+            // SAFETY: only used from `pack_avx2`, which runs once AVX2
+            // support has been detected.
+            unsafe { tags_avx2(src, tags) }
+        }
+
+        #[inline(always)]
+        fn compact(result: &mut Vec<u8>, word: u64, tag: u8) {
+            // SAFETY: AVX2 implies SSSE3.
+            unsafe { compact_ssse3(result, word, tag) }
+        }
+    }
+
+    /// Tag bytes of whole words, two words per compare.
+    #[target_feature(enable = "sse2")]
+    unsafe fn tags_sse2(src: &[u8], tags: &mut [u8]) {
+        debug_assert_eq!(src.len(), tags.len() * 8);
+        let zero = _mm_setzero_si128();
+        let mut pairs = src.chunks_exact(16);
+        let mut out = tags.chunks_exact_mut(2);
+        for (chunk, tag) in (&mut pairs).zip(&mut out) {
+            // SAFETY: `chunk` is 16 readable bytes; `loadu` has no
+            // alignment requirement.
+            let v = unsafe { _mm_loadu_si128(chunk.as_ptr().cast()) };
+            // One bit per byte, set where the byte is non-zero.
+            let mask = !_mm_movemask_epi8(_mm_cmpeq_epi8(v, zero)) as u32;
+            tag[0] = mask as u8;
+            tag[1] = (mask >> 8) as u8;
+        }
+        if let [last] = out.into_remainder() {
+            let word = u64::from_le_bytes(pairs.remainder().try_into().unwrap());
+            *last = super::compute_tag_word(word).0;
+        }
+    }
+
+    /// Tag bytes of whole words, four words per compare.
+    #[target_feature(enable = "avx2")]
+    unsafe fn tags_avx2(src: &[u8], tags: &mut [u8]) {
+        debug_assert_eq!(src.len(), tags.len() * 8);
+        let zero = _mm256_setzero_si256();
+        let mut quads = src.chunks_exact(32);
+        let mut out = tags.chunks_exact_mut(4);
+        for (chunk, tag) in (&mut quads).zip(&mut out) {
+            // SAFETY: `chunk` is 32 readable bytes; `loadu` has no
+            // alignment requirement.
+            let v = unsafe { _mm256_loadu_si256(chunk.as_ptr().cast()) };
+            let mask = !_mm256_movemask_epi8(_mm256_cmpeq_epi8(v, zero)) as u32;
+            tag.copy_from_slice(&mask.to_le_bytes());
+        }
+        // SAFETY: AVX2 implies SSE2.
+        unsafe { tags_sse2(quads.remainder(), out.into_remainder()) }
+    }
+
+    /// Append the bytes of `word` selected by `tag`, gathered with `pshufb`.
+    #[target_feature(enable = "ssse3")]
+    unsafe fn compact_ssse3(result: &mut Vec<u8>, word: u64, tag: u8) {
+        result.reserve(8);
+        let len = result.len();
+        let v = _mm_cvtsi64_si128(word as i64);
+        let mask = _mm_cvtsi64_si128(i64::from_le_bytes(SHUFFLE[tag as usize]));
+        let packed = _mm_shuffle_epi8(v, mask);
+        // SAFETY: `reserve` left room for 8 bytes past `len`. All 8 lanes
+        // are written; only the `tag.count_ones()` gathered bytes are kept.
+        unsafe {
+            _mm_storel_epi64(result.as_mut_ptr().add(len).cast(), packed);
+            result.set_len(len + tag.count_ones() as usize);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "x86_64")]
+    fn portable(src: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        pack_source::<&[u8], Portable>(&src, &mut out);
+        out
+    }
+
+    /// Inputs covering every tag value, FF runs and partial words.
+    #[cfg(target_arch = "x86_64")]
+    fn inputs() -> Vec<Vec<u8>> {
+        let mut every_tag = Vec::new();
+        for tag in 0..=255u8 {
+            for bit in 0..8 {
+                every_tag.push(if tag & (1 << bit) != 0 { bit + 1 } else { 0 });
+            }
+        }
+        let mut inputs = vec![every_tag.clone(), every_tag.iter().rev().copied().collect()];
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        for len in [0usize, 1, 7, 8, 15, 16, 17, 31, 33, 255, 2048, 4099] {
+            inputs.push(
+                (0..len)
+                    .map(|_| {
+                        seed ^= seed << 13;
+                        seed ^= seed >> 7;
+                        seed ^= seed << 17;
+                        if seed.is_multiple_of(3) {
+                            0
+                        } else {
+                            (seed >> 8) as u8
+                        }
+                    })
+                    .collect(),
+            );
+            inputs.push(vec![0xA5; len]);
+        }
+        inputs
+    }
+
+    #[test]
+    fn test_shuffle_table() {
+        assert_eq!(SHUFFLE[0], [0x80; 8]);
+        assert_eq!(SHUFFLE[0b1010_0101], [0, 2, 5, 7, 0x80, 0x80, 0x80, 0x80]);
+        assert_eq!(SHUFFLE[0xFF], [0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_portable_matches_scalar_tags() {
+        let data: Vec<u8> = (0..64u8).map(|b| b % 3).collect();
+        let mut tags = [0u8; 8];
+        Portable::tags(&data, &mut tags);
+        for (i, tag) in tags.iter().enumerate() {
+            let word = u64::from_le_bytes(data[i * 8..i * 8 + 8].try_into().unwrap());
+            assert_eq!(*tag, compute_tag_word(word).0);
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_x86_kernels_match_portable() {
+        use x86::Level;
+
+        let best = x86::level();
+        let levels = [Level::Sse2, Level::Ssse3, Level::Avx2];
+        for input in inputs() {
+            let expected = portable(&input);
+            for &level in levels.iter().filter(|&&l| l as u8 <= best as u8) {
+                let mut out = vec![0xEE];
+                // SAFETY: `level` is at most the detected level.
+                unsafe { x86::pack_with(level, &input.as_slice(), &mut out) };
+                assert_eq!(
+                    &out[1..],
+                    &expected[..],
+                    "{:?}, {} bytes",
+                    level,
+                    input.len()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Incremental pack/unpack over `std::io` streams.
+//!
+//! [`PackWriter`] packs bytes as they are written and [`UnpackReader`]
+//! unpacks bytes as they are read, so a large packed file never has to be
+//! held in memory. Both produce exactly the bytes of [`pack`](super::pack)
+//! and [`unpack`](super::unpack) on the whole stream, however the data is
+//! split into writes or reads.
+
+use std::io::{self, Read, Write};
+
+use super::{compute_tag_word, pack_nonzero};
+use crate::error::PackError;
+
+/// Longest 0xFF run, in words.
+const MAX_FF_RUN: usize = 256;
+
+/// Packs everything written to it into an inner writer.
+///
+/// Output is produced a word at a time, except for 0xFF runs: their count
+/// byte comes first, so a run is buffered (up to 2 KiB) until it ends. The
+/// last partial word and any open run are written by
+/// [`finish`](Self::finish), which pads the data to a whole word as `pack`
+/// does; `flush` only writes what is already final. Dropping the writer
+/// without calling `finish` finishes it, ignoring errors.
+pub struct PackWriter<W: Write> {
+    /// `None` once finished.
+    inner: Option<W>,
+    /// Bytes of the current, incomplete word.
+    word: [u8; 8],
+    word_len: usize,
+    /// Raw bytes of the open 0xFF run.
+    run: Vec<u8>,
+    /// Packed output not yet written to `inner`.
+    out: Vec<u8>,
+}
+
+impl<W: Write> PackWriter<W> {
+    /// Create a writer packing into `inner`.
+    pub fn new(inner: W) -> Self {
+        PackWriter {
+            inner: Some(inner),
+            word: [0; 8],
+            word_len: 0,
+            run: Vec::new(),
+            out: Vec::new(),
+        }
+    }
+
+    /// The inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("PackWriter used after finish")
+    }
+
+    /// Pack the final partial word and any open run, write all output and
+    /// return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_output()?;
+        Ok(self.inner.take().expect("PackWriter used after finish"))
+    }
+
+    fn finish_output(&mut self) -> io::Result<()> {
+        if self.word_len > 0 {
+            self.word[self.word_len..].fill(0);
+            self.word_len = 0;
+            self.push_word(u64::from_le_bytes(self.word));
+        }
+        self.flush_run();
+        self.write_out()?;
+        self.inner
+            .as_mut()
+            .expect("PackWriter used after finish")
+            .flush()
+    }
+
+    /// Pack one complete word, as the loop in `pack` does.
+    fn push_word(&mut self, word: u64) {
+        let (tag, notzero) = compute_tag_word(word);
+        let in_run = !self.run.is_empty();
+        // Promote 6/7 non-zero to 8 ONLY when already in an FF run
+        if notzero == 8 || ((notzero == 6 || notzero == 7) && in_run) {
+            self.run.extend_from_slice(&word.to_le_bytes());
+            if self.run.len() == MAX_FF_RUN * 8 {
+                self.flush_run();
+            }
+        } else {
+            self.flush_run();
+            self.out.push(tag);
+            if tag != 0 {
+                pack_nonzero(&mut self.out, word, tag);
+            }
+        }
+    }
+
+    fn flush_run(&mut self) {
+        if self.run.is_empty() {
+            return;
+        }
+        self.out.push(0xFF);
+        self.out.push((self.run.len() / 8 - 1) as u8);
+        self.out.extend_from_slice(&self.run);
+        self.run.clear();
+    }
+
+    /// Write `out` to the inner writer. On error, the part already written
+    /// is dropped from `out` and the rest is kept for the next attempt.
+    fn write_out(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().expect("PackWriter used after finish");
+        let mut written = 0;
+        let result = loop {
+            if written == self.out.len() {
+                break Ok(());
+            }
+            match inner.write(&self.out[written..]) {
+                Ok(0) => break Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.out.drain(..written);
+        result
+    }
+}
+
+impl<W: Write> Write for PackWriter<W> {
+    /// Pack `buf`, writing the finished output to the inner writer.
+    ///
+    /// As with `BufWriter`, output left over from an earlier failure is
+    /// written first, and an error from that means none of `buf` was taken.
+    /// Once `buf` is buffered it counts as written; an error writing its
+    /// output is reported by the next `write`, `flush` or `finish`.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_out()?;
+        let mut rest = buf;
+        if self.word_len > 0 {
+            let n = rest.len().min(8 - self.word_len);
+            self.word[self.word_len..self.word_len + n].copy_from_slice(&rest[..n]);
+            self.word_len += n;
+            rest = &rest[n..];
+            if self.word_len < 8 {
+                return Ok(buf.len());
+            }
+            self.word_len = 0;
+            self.push_word(u64::from_le_bytes(self.word));
+        }
+        let mut words = rest.chunks_exact(8);
+        for chunk in &mut words {
+            self.push_word(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        let tail = words.remainder();
+        self.word[..tail.len()].copy_from_slice(tail);
+        self.word_len = tail.len();
+        // Retried, and any error reported, by the next call.
+        let _ = self.write_out();
+        Ok(buf.len())
+    }
+
+    /// Write the packed output produced so far and flush the inner writer.
+    ///
+    /// A partial word or open 0xFF run stays buffered until more data or
+    /// `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_out()?;
+        self.inner
+            .as_mut()
+            .expect("PackWriter used after finish")
+            .flush()
+    }
+}
+
+impl<W: Write> Drop for PackWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() && !std::thread::panicking() {
+            let _ = self.finish_output();
+        }
+    }
+}
+
+/// Unpacks the packed bytes read from an inner reader.
+///
+/// Reading yields the unpacked data, including the zero padding of the
+/// last word. Malformed or truncated input is reported as an
+/// `io::ErrorKind::InvalidData` error wrapping a `PackError`.
+pub struct UnpackReader<R: Read> {
+    inner: R,
+    /// Packed bytes read from `inner`; `buf[pos..end]` is not consumed yet.
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+    /// The current unpacked word; `word[word_pos..]` is not returned yet.
+    word: [u8; 8],
+    word_pos: usize,
+    /// Raw bytes left in the current 0xFF run.
+    raw_left: usize,
+}
+
+/// Size of each read from the inner reader.
+const READ_CHUNK: usize = 8 * 1024;
+
+impl<R: Read> UnpackReader<R> {
+    /// Create a reader unpacking the data of `inner`.
+    pub fn new(inner: R) -> Self {
+        UnpackReader {
+            inner,
+            buf: vec![0; READ_CHUNK].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+            word: [0; 8],
+            word_pos: 8,
+            raw_left: 0,
+        }
+    }
+
+    /// The inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Return the inner reader, discarding any buffered input.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn available(&self) -> usize {
+        self.end - self.pos
+    }
+
+    /// Buffer at least `n` (at most 9) unconsumed bytes; returns false at
+    /// end of input.
+    fn fill(&mut self, n: usize) -> io::Result<bool> {
+        while self.available() < n {
+            if self.end == self.buf.len() {
+                self.buf.copy_within(self.pos..self.end, 0);
+                self.end -= self.pos;
+                self.pos = 0;
+            }
+            let read = match self.inner.read(&mut self.buf[self.end..]) {
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if read == 0 {
+                return Ok(false);
+            }
+            self.end += read;
+        }
+        Ok(true)
+    }
+
+    /// Decode the next segment header into `word` or `raw_left`.
+    ///
+    /// Returns false at a clean end of input.
+    fn next_segment(&mut self) -> io::Result<bool> {
+        if !self.fill(1)? {
+            return Ok(false);
+        }
+        let header = self.buf[self.pos];
+        if header == 0xFF {
+            if !self.fill(2)? {
+                return Err(invalid("0xFF tag at end of data without count byte"));
+            }
+            self.raw_left = (self.buf[self.pos + 1] as usize + 1) * 8;
+            self.pos += 2;
+        } else {
+            let notzero = header.count_ones() as usize;
+            if !self.fill(1 + notzero)? {
+                return Err(invalid("truncated packed data in normal segment"));
+            }
+            let mut data = self.buf[self.pos + 1..self.pos + 1 + notzero].iter();
+            for (i, byte) in self.word.iter_mut().enumerate() {
+                *byte = if header & (1 << i) != 0 {
+                    *data.next().unwrap()
+                } else {
+                    0
+                };
+            }
+            self.word_pos = 0;
+            self.pos += 1 + notzero;
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for UnpackReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < out.len() {
+            if self.word_pos < 8 {
+                let n = (8 - self.word_pos).min(out.len() - written);
+                out[written..written + n]
+                    .copy_from_slice(&self.word[self.word_pos..self.word_pos + n]);
+                self.word_pos += n;
+                written += n;
+            } else if self.raw_left > 0 {
+                if self.available() == 0 {
+                    if written > 0 {
+                        break;
+                    }
+                    if !self.fill(1)? {
+                        return Err(invalid("truncated 0xFF run"));
+                    }
+                }
+                let n = self.raw_left.min(self.available()).min(out.len() - written);
+                out[written..written + n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+                self.pos += n;
+                self.raw_left -= n;
+                written += n;
+            } else if written > 0 && self.available() == 0 {
+                // Return what we have rather than block for more input.
+                break;
+            } else if !self.next_segment()? {
+                break;
+            }
+        }
+        Ok(written)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        PackError::InvalidData(message.into()),
+    )
+}
@@ -0,0 +1,779 @@
+//! Text schema parser: builds a `Sproto` directly from `.sproto` source.
+//!
+//! Accepts the same grammar as the reference `sprotoparser.lua`:
+//! ```text
+//! # comment
+//! .Person {
+//!     .PhoneNumber {              # nested type, full name "Person.PhoneNumber"
+//!         number 0 : string
+//!         type 1 : integer
+//!     }
+//!     name 0 : string
+//!     fpn 1 : integer(2)          # fixed-point decimal
+//!     photo 2 : binary
+//!     phones 3 : *PhoneNumber(number)  # map keyed by `number`
+//! }
+//!
+//! login 1 {
+//!     request Person
+//!     response { ok 0 : boolean } # inline type, named "login.response"
+//! }
+//! ```
+//!
+//! Like the reference parser, types are stored sorted by full name, fields
+//! sorted by tag, and protocols sorted by tag, so the result matches what
+//! `binary_schema::load_binary` produces for the compiled schema.
+
+use std::collections::HashMap;
+
+use crate::error::ParseError;
+use crate::types::*;
+
+/// Parse `.sproto` schema source into a `Sproto` object.
+pub fn parse(source: &str) -> Result<Sproto, ParseError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        types: Vec::new(),
+        protocols: Vec::new(),
+    };
+    parser.parse_schema()?;
+    build_sproto(parser.types, parser.protocols)
+}
+
+// --- Lexer ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    /// A word, possibly dotted (`Person.PhoneNumber`).
+    Name(String),
+    Number(u64),
+    Dot,
+    LBrace,
+    RBrace,
+    Colon,
+    Star,
+    LParen,
+    RParen,
+    Eof,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    pos: Pos,
+}
+
+fn error_at(pos: Pos, message: impl Into<String>) -> ParseError {
+    ParseError {
+        line: pos.line,
+        column: pos.column,
+        message: message.into(),
+    }
+}
+
+fn is_word_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut line_start = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let pos = Pos {
+            line,
+            column: i - line_start + 1,
+        };
+
+        if c == '\n' {
+            i += 1;
+            line += 1;
+            line_start = i;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        let kind = match c {
+            '.' => TokenKind::Dot,
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            ':' => TokenKind::Colon,
+            '*' => TokenKind::Star,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            _ if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let n = text
+                    .parse::<u64>()
+                    .map_err(|_| error_at(pos, format!("number '{}' is too large", text)))?;
+                tokens.push(Token {
+                    kind: TokenKind::Number(n),
+                    pos,
+                });
+                continue;
+            }
+            _ if is_word_start(c) => {
+                let start = i;
+                loop {
+                    while i < chars.len() && is_word_char(chars[i]) {
+                        i += 1;
+                    }
+                    // Dotted type reference: word ("." word)*
+                    if i + 1 < chars.len() && chars[i] == '.' && is_word_start(chars[i + 1]) {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Name(chars[start..i].iter().collect()),
+                    pos,
+                });
+                continue;
+            }
+            _ => return Err(error_at(pos, format!("unexpected character '{}'", c))),
+        };
+        tokens.push(Token { kind, pos });
+        i += 1;
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        pos: Pos {
+            line,
+            column: i - line_start + 1,
+        },
+    });
+    Ok(tokens)
+}
+
+// --- Parser (syntax only; names are resolved in `build_sproto`) ---
+
+struct TypeDef {
+    name: String,
+    pos: Pos,
+    fields: Vec<FieldDef>,
+}
+
+struct FieldDef {
+    name: String,
+    pos: Pos,
+    tag: u16,
+    array: bool,
+    type_name: String,
+    type_pos: Pos,
+    extra: Option<FieldExtra>,
+}
+
+enum FieldExtra {
+    /// `*Type(key)`; an empty key declares a two-field map.
+    Key(String, Pos),
+    /// `integer(N)`.
+    Decimal(u32, Pos),
+}
+
+enum ProtoType {
+    /// A named type reference (or the full name of an inline type).
+    Named(String, Pos),
+    /// `response nil`.
+    Nil,
+}
+
+struct ProtoDef {
+    name: String,
+    pos: Pos,
+    tag: u16,
+    request: Option<ProtoType>,
+    response: Option<ProtoType>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    types: Vec<TypeDef>,
+    protocols: Vec<ProtoDef>,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn advance(&mut self) -> Token {
+        let tok = self.tokens[self.pos].clone();
+        if tok.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        let tok = self.peek();
+        let found = match &tok.kind {
+            TokenKind::Name(n) => format!("'{}'", n),
+            TokenKind::Number(n) => format!("'{}'", n),
+            TokenKind::Dot => "'.'".into(),
+            TokenKind::LBrace => "'{'".into(),
+            TokenKind::RBrace => "'}'".into(),
+            TokenKind::Colon => "':'".into(),
+            TokenKind::Star => "'*'".into(),
+            TokenKind::LParen => "'('".into(),
+            TokenKind::RParen => "')'".into(),
+            TokenKind::Eof => "end of input".into(),
+        };
+        error_at(tok.pos, format!("expected {}, found {}", expected, found))
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<Pos, ParseError> {
+        if self.peek().kind == kind {
+            Ok(self.advance().pos)
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    /// Expect a plain (undotted) word.
+    fn expect_word(&mut self, what: &str) -> Result<(String, Pos), ParseError> {
+        match &self.peek().kind {
+            TokenKind::Name(n) if !n.contains('.') => {
+                let n = n.clone();
+                Ok((n, self.advance().pos))
+            }
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    fn expect_type_name(&mut self) -> Result<(String, Pos), ParseError> {
+        match &self.peek().kind {
+            TokenKind::Name(n) => {
+                let n = n.clone();
+                Ok((n, self.advance().pos))
+            }
+            _ => Err(self.unexpected("type name")),
+        }
+    }
+
+    fn expect_tag(&mut self) -> Result<u16, ParseError> {
+        match self.peek().kind {
+            TokenKind::Number(n) => {
+                let pos = self.advance().pos;
+                // Tags are stored inline in the binary schema, so they must
+                // fit the inline value range.
+                if n >= 0x7fff {
+                    return Err(error_at(pos, format!("tag {} is out of range", n)));
+                }
+                Ok(n as u16)
+            }
+            _ => Err(self.unexpected("tag number")),
+        }
+    }
+
+    fn parse_schema(&mut self) -> Result<(), ParseError> {
+        loop {
+            match &self.peek().kind {
+                TokenKind::Eof => return Ok(()),
+                TokenKind::Dot => {
+                    self.advance();
+                    self.parse_type("")?;
+                }
+                TokenKind::Name(_) => self.parse_protocol()?,
+                _ => return Err(self.unexpected("type or protocol definition")),
+            }
+        }
+    }
+
+    /// Parse `Name { ... }` after the leading `.`, registering the type and
+    /// any nested types under `prefix`.
+    fn parse_type(&mut self, prefix: &str) -> Result<(), ParseError> {
+        let (name, pos) = self.expect_word("type name")?;
+        let full_name = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        let fields = self.parse_struct_body(&full_name)?;
+        self.types.push(TypeDef {
+            name: full_name,
+            pos,
+            fields,
+        });
+        Ok(())
+    }
+
+    /// Parse `{ field* }`, where nested `.Type { }` blocks are allowed.
+    fn parse_struct_body(&mut self, full_name: &str) -> Result<Vec<FieldDef>, ParseError> {
+        self.expect(TokenKind::LBrace, "'{'")?;
+        let mut fields = Vec::new();
+        loop {
+            match &self.peek().kind {
+                TokenKind::RBrace => {
+                    self.advance();
+                    return Ok(fields);
+                }
+                TokenKind::Dot => {
+                    self.advance();
+                    self.parse_type(full_name)?;
+                }
+                TokenKind::Name(_) => fields.push(self.parse_field()?),
+                _ => return Err(self.unexpected("field definition or '}'")),
+            }
+        }
+    }
+
+    /// Parse `name tag : [*]type[(key|N)]`.
+    fn parse_field(&mut self) -> Result<FieldDef, ParseError> {
+        let (name, pos) = self.expect_word("field name")?;
+        let tag = self.expect_tag()?;
+        self.expect(TokenKind::Colon, "':'")?;
+        let array = if self.peek().kind == TokenKind::Star {
+            self.advance();
+            true
+        } else {
+            false
+        };
+        let (type_name, type_pos) = self.expect_type_name()?;
+
+        let mut extra = None;
+        if self.peek().kind == TokenKind::LParen {
+            let lpos = self.advance().pos;
+            extra = Some(match &self.peek().kind {
+                TokenKind::Number(n) => {
+                    let n = *n;
+                    let npos = self.advance().pos;
+                    if n > 9 {
+                        return Err(error_at(
+                            npos,
+                            format!("decimal precision {} is out of range", n),
+                        ));
+                    }
+                    FieldExtra::Decimal(n as u32, npos)
+                }
+                TokenKind::Name(_) => {
+                    let (key, kpos) = self.expect_word("key field name")?;
+                    FieldExtra::Key(key, kpos)
+                }
+                TokenKind::RParen => FieldExtra::Key(String::new(), lpos),
+                _ => return Err(self.unexpected("key field name, precision or ')'")),
+            });
+            self.expect(TokenKind::RParen, "')'")?;
+        }
+
+        Ok(FieldDef {
+            name,
+            pos,
+            tag,
+            array,
+            type_name,
+            type_pos,
+            extra,
+        })
+    }
+
+    /// Parse `name tag { [request T] [response T|nil] }`.
+    fn parse_protocol(&mut self) -> Result<(), ParseError> {
+        let (name, pos) = self.expect_word("protocol name")?;
+        let tag = self.expect_tag()?;
+        self.expect(TokenKind::LBrace, "'{'")?;
+
+        let mut request = None;
+        let mut response = None;
+        loop {
+            let (key, kpos) = match &self.peek().kind {
+                TokenKind::RBrace => {
+                    self.advance();
+                    break;
+                }
+                TokenKind::Name(_) => self.expect_word("'request' or 'response'")?,
+                _ => return Err(self.unexpected("'request', 'response' or '}'")),
+            };
+            let already_set = match key.as_str() {
+                "request" => request.is_some(),
+                "response" => response.is_some(),
+                _ => {
+                    return Err(error_at(
+                        kpos,
+                        format!("invalid protocol field '{}' in protocol '{}'", key, name),
+                    ))
+                }
+            };
+            if already_set {
+                return Err(error_at(
+                    kpos,
+                    format!("redefined {} in protocol '{}'", key, name),
+                ));
+            }
+
+            let value = if self.peek().kind == TokenKind::LBrace {
+                let full_name = format!("{}.{}", name, key);
+                let fields = self.parse_struct_body(&full_name)?;
+                self.types.push(TypeDef {
+                    name: full_name.clone(),
+                    pos: kpos,
+                    fields,
+                });
+                ProtoType::Named(full_name, kpos)
+            } else {
+                let (type_name, tpos) = self.expect_type_name()?;
+                if type_name == "nil" {
+                    if key == "request" {
+                        return Err(error_at(tpos, "request type can't be nil"));
+                    }
+                    ProtoType::Nil
+                } else {
+                    ProtoType::Named(type_name, tpos)
+                }
+            };
+            if key == "request" {
+                request = Some(value);
+            } else {
+                response = Some(value);
+            }
+        }
+
+        self.protocols.push(ProtoDef {
+            name,
+            pos,
+            tag,
+            request,
+            response,
+        });
+        Ok(())
+    }
+}
+
+// --- Resolution ---
+
+fn is_builtin(name: &str) -> bool {
+    matches!(name, "integer" | "boolean" | "string" | "binary" | "double")
+}
+
+/// Resolve a type reference from within type `scope`, searching the
+/// innermost enclosing scope first (mirrors `checktype` in sprotoparser).
+pub(crate) fn resolve_type(types: &HashMap<&str, usize>, scope: &str, name: &str) -> Option<usize> {
+    let mut scope = scope;
+    loop {
+        if scope.is_empty() {
+            return types.get(name).copied();
+        }
+        if let Some(&idx) = types.get(format!("{}.{}", scope, name).as_str()) {
+            return Some(idx);
+        }
+        scope = match scope.rfind('.') {
+            Some(i) => &scope[..i],
+            None => "",
+        };
+    }
+}
+
+fn build_sproto(
+    mut type_defs: Vec<TypeDef>,
+    mut proto_defs: Vec<ProtoDef>,
+) -> Result<Sproto, ParseError> {
+    let mut seen_types: HashMap<&str, Pos> = HashMap::new();
+    for td in &type_defs {
+        if let Some(first) = seen_types.insert(&td.name, td.pos) {
+            return Err(error_at(
+                td.pos,
+                format!(
+                    "redefined type '{}' (first defined at {}:{})",
+                    td.name, first.line, first.column
+                ),
+            ));
+        }
+    }
+
+    // Types are ordered by full name, as the reference parser emits them.
+    type_defs.sort_by(|a, b| a.name.cmp(&b.name));
+    let index: HashMap<&str, usize> = type_defs
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.name.as_str(), i))
+        .collect();
+
+    // First pass: resolve field types so key fields can be checked against
+    // their (possibly later) subtypes.
+    let mut resolved: Vec<Vec<Field>> = Vec::with_capacity(type_defs.len());
+    for td in &type_defs {
+        let mut fields: Vec<Field> = Vec::with_capacity(td.fields.len());
+        for fd in &td.fields {
+            if fields.iter().any(|f| *f.name == *fd.name) {
+                return Err(error_at(
+                    fd.pos,
+                    format!("redefined field '{}' in type '{}'", fd.name, td.name),
+                ));
+            }
+            if let Some(prev) = fields.iter().find(|f| f.tag == fd.tag) {
+                return Err(error_at(
+                    fd.pos,
+                    format!(
+                        "redefined tag {} in type '{}' (used by '{}')",
+                        fd.tag, td.name, prev.name
+                    ),
+                ));
+            }
+
+            let field_type = match fd.type_name.as_str() {
+                "integer" => FieldType::Integer,
+                "boolean" => FieldType::Boolean,
+                "string" => FieldType::String,
+                "binary" => FieldType::Binary,
+                "double" => FieldType::Double,
+                name => match resolve_type(&index, &td.name, name) {
+                    Some(idx) => FieldType::Struct(idx),
+                    None => {
+                        return Err(error_at(
+                            fd.type_pos,
+                            format!(
+                                "undefined type '{}' in field '{}.{}'",
+                                name, td.name, fd.name
+                            ),
+                        ))
+                    }
+                },
+            };
+
+            let mut field = Field::new(&fd.name, fd.tag, field_type);
+            field.is_array = fd.array;
+            match &fd.extra {
+                Some(FieldExtra::Decimal(n, pos)) => {
+                    if field.field_type != FieldType::Integer {
+                        return Err(error_at(
+                            *pos,
+                            format!(
+                                "decimal precision is only valid for integer fields ('{}.{}')",
+                                td.name, fd.name
+                            ),
+                        ));
+                    }
+                    field.decimal_precision = 10u32.pow(*n);
+                }
+                Some(FieldExtra::Key(_, pos))
+                    if !fd.array || !matches!(field.field_type, FieldType::Struct(_)) =>
+                {
+                    return Err(error_at(
+                        *pos,
+                        format!(
+                            "map key requires an array of structs ('{}.{}')",
+                            td.name, fd.name
+                        ),
+                    ));
+                }
+                Some(FieldExtra::Key(..)) | None => {}
+            }
+            fields.push(field);
+        }
+        resolved.push(fields);
+    }
+
+    // Second pass: map keys refer to fields of the (now resolved) subtype.
+    for (ti, td) in type_defs.iter().enumerate() {
+        for fd in &td.fields {
+            let (key, pos) = match &fd.extra {
+                Some(FieldExtra::Key(key, pos)) => (key, *pos),
+                _ => continue,
+            };
+            let fi = resolved[ti]
+                .iter()
+                .position(|f| *f.name == *fd.name)
+                .expect("field was resolved in the first pass");
+            let sub_idx = match resolved[ti][fi].field_type {
+                FieldType::Struct(idx) => idx,
+                _ => unreachable!("checked in the first pass"),
+            };
+            let sub_fields = &resolved[sub_idx];
+            let sub_name = &type_defs[sub_idx].name;
+
+            if key.is_empty() {
+                // `*Type()`: a two-field struct read as key/value pairs.
+                if sub_fields.len() != 2 {
+                    return Err(error_at(
+                        pos,
+                        format!(
+                            "map '{}.{}' requires type '{}' to have exactly two fields",
+                            td.name, fd.name, sub_name
+                        ),
+                    ));
+                }
+                let mut by_tag: Vec<&Field> = sub_fields.iter().collect();
+                by_tag.sort_by_key(|f| f.tag);
+                if matches!(by_tag[0].field_type, FieldType::Struct(_)) || by_tag[0].is_array {
+                    return Err(error_at(
+                        pos,
+                        format!(
+                            "invalid map key '{}' in type '{}'",
+                            by_tag[0].name, sub_name
+                        ),
+                    ));
+                }
+                let key_tag = by_tag[0].tag as i32;
+                let field = &mut resolved[ti][fi];
+                field.key_tag = key_tag;
+                field.is_map = true;
+            } else {
+                let key_field = sub_fields
+                    .iter()
+                    .find(|f| *f.name == **key)
+                    .ok_or_else(|| {
+                        error_at(
+                            pos,
+                            format!("key '{}' not found in type '{}'", key, sub_name),
+                        )
+                    })?;
+                if matches!(key_field.field_type, FieldType::Struct(_)) || key_field.is_array {
+                    return Err(error_at(
+                        pos,
+                        format!(
+                            "invalid map key '{}' in type '{}': key must be a scalar builtin type",
+                            key, sub_name
+                        ),
+                    ));
+                }
+                let key_tag = key_field.tag as i32;
+                resolved[ti][fi].key_tag = key_tag;
+            }
+        }
+    }
+
+    let mut sproto = Sproto::new();
+    for (td, mut fields) in type_defs.iter().zip(resolved) {
+        fields.sort_by_key(|f| f.tag);
+        sproto.add_type(&td.name, fields);
+    }
+
+    proto_defs.sort_by_key(|p| p.tag);
+    let mut seen_names: HashMap<&str, Pos> = HashMap::new();
+    for (i, pd) in proto_defs.iter().enumerate() {
+        if i > 0 && proto_defs[i - 1].tag == pd.tag {
+            return Err(error_at(
+                pd.pos,
+                format!(
+                    "redefined protocol tag {} ('{}' and '{}')",
+                    pd.tag,
+                    proto_defs[i - 1].name,
+                    pd.name
+                ),
+            ));
+        }
+        if let Some(first) = seen_names.insert(&pd.name, pd.pos) {
+            return Err(error_at(
+                pd.pos,
+                format!(
+                    "redefined protocol '{}' (first defined at {}:{})",
+                    pd.name, first.line, first.column
+                ),
+            ));
+        }
+    }
+    for pd in &proto_defs {
+        let lookup = |t: &ProtoType| -> Result<Option<usize>, ParseError> {
+            match t {
+                ProtoType::Nil => Ok(None),
+                ProtoType::Named(name, pos) => {
+                    if is_builtin(name) {
+                        return Err(error_at(
+                            *pos,
+                            format!(
+                                "protocol '{}' must use a struct type, not '{}'",
+                                pd.name, name
+                            ),
+                        ));
+                    }
+                    index.get(name.as_str()).copied().map(Some).ok_or_else(|| {
+                        error_at(
+                            *pos,
+                            format!("undefined type '{}' in protocol '{}'", name, pd.name),
+                        )
+                    })
+                }
+            }
+        };
+        let request = match &pd.request {
+            Some(t) => lookup(t)?,
+            None => None,
+        };
+        let response = match &pd.response {
+            Some(t) => lookup(t)?,
+            None => None,
+        };
+        let confirm = matches!(pd.response, Some(ProtoType::Nil));
+        sproto.add_protocol(&pd.name, pd.tag, request, response, confirm);
+    }
+
+    Ok(sproto)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested_and_map() {
+        let s = parse(
+            r#"
+            .Person {
+                .PhoneNumber {
+                    number 0 : string
+                    type 1 : integer
+                }
+                name 0 : string
+                phones 1 : *PhoneNumber(number)
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(s.types_list[0].name, "Person");
+        assert_eq!(s.types_list[1].name, "Person.PhoneNumber");
+        let phones = s
+            .get_type("Person")
+            .unwrap()
+            .find_field_by_name("phones")
+            .unwrap();
+        assert_eq!(phones.field_type, FieldType::Struct(1));
+        assert!(phones.is_array);
+        assert_eq!(phones.key_tag, 0);
+        assert!(!phones.is_map);
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let err = parse(".Foo {\n    a 0 : integer\n    b 1 : Bar\n}\n").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 11));
+        assert!(err.message.contains("undefined type 'Bar'"));
+    }
+
+    #[test]
+    fn test_parse_unterminated() {
+        let err = parse(".Foo {\n  a 0 : integer\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(err.message.contains("end of input"));
+    }
+}
@@ -0,0 +1,244 @@
+//! Schema printer: renders a `Sproto` back to `.sproto` source.
+//!
+//! Useful for inspecting binary schemas from `binary_schema::load_binary`:
+//!
+//! ```
+//! use sproto::parser;
+//!
+//! let schema = parser::parse(
+//!     ".Person { .Phone { number 0 : string } phones 0 : *Phone(number) }",
+//! )
+//! .unwrap();
+//! assert_eq!(
+//!     schema.to_string(),
+//!     ".Person {\n    .Phone {\n        number 0 : string\n    }\n    phones 0 : *Phone(number)\n}\n"
+//! );
+//! ```
+//!
+//! Dotted type names are nested back under their parent, and protocol
+//! request/response types named `<protocol>.request` / `<protocol>.response`
+//! are printed inline. Type references use the shortest name that resolves
+//! to the same type from where they appear. Parsing the output gives back
+//! the same schema; a dotted type whose parent is not a type is printed
+//! under an empty block for the parent, which then parses as a type.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{self, Write};
+
+use crate::parser::resolve_type;
+use crate::types::{Field, FieldType, Protocol, Sproto};
+
+const INDENT: &str = "    ";
+
+/// Render `sproto` as `.sproto` source; same as `sproto.to_string()`.
+pub fn to_text(sproto: &Sproto) -> String {
+    sproto.to_string()
+}
+
+/// Types first, then protocols, separated by blank lines.
+impl fmt::Display for Sproto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let printer = Printer::new(self);
+        let mut first = true;
+        for name in printer.children("") {
+            if !std::mem::take(&mut first) {
+                f.write_char('\n')?;
+            }
+            printer.write_type(f, name, 0)?;
+        }
+        for proto in &self.protocols {
+            if !std::mem::take(&mut first) {
+                f.write_char('\n')?;
+            }
+            printer.write_protocol(f, proto)?;
+        }
+        Ok(())
+    }
+}
+
+struct Printer<'a> {
+    sproto: &'a Sproto,
+    index: HashMap<&'a str, usize>,
+    /// Full names of the types nested directly in each type; `""` is the
+    /// top level.
+    nested: BTreeMap<&'a str, BTreeSet<&'a str>>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(sproto: &'a Sproto) -> Self {
+        let index: HashMap<&str, usize> = sproto
+            .types_list
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.name.as_str(), i))
+            .collect();
+        let inline: HashSet<&str> = sproto
+            .protocols
+            .iter()
+            .flat_map(|p| inline_types(sproto, p))
+            .flatten()
+            .collect();
+
+        let mut nested: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for st in &sproto.types_list {
+            if inline.contains(st.name.as_str()) {
+                continue;
+            }
+            // Register the type under its parent, and any missing parents
+            // under theirs.
+            let mut name = st.name.as_str();
+            loop {
+                let parent = name.rfind('.').map_or("", |i| &name[..i]);
+                let known =
+                    parent.is_empty() || index.contains_key(parent) || nested.contains_key(parent);
+                nested.entry(parent).or_default().insert(name);
+                if known {
+                    break;
+                }
+                name = parent;
+            }
+        }
+
+        Printer {
+            sproto,
+            index,
+            nested,
+        }
+    }
+
+    fn children(&self, parent: &str) -> impl Iterator<Item = &'a str> + '_ {
+        self.nested.get(parent).into_iter().flatten().copied()
+    }
+
+    /// `.Name { ... }` for the type `full_name`, which may be a parent with
+    /// no type of its own.
+    fn write_type(&self, f: &mut fmt::Formatter<'_>, full_name: &str, depth: usize) -> fmt::Result {
+        let short = full_name.rsplit('.').next().unwrap_or(full_name);
+        write!(f, "{}.{} ", INDENT.repeat(depth), short)?;
+        self.write_body(f, full_name, depth)?;
+        f.write_char('\n')
+    }
+
+    /// `{ nested types, fields }`, without a trailing newline.
+    fn write_body(&self, f: &mut fmt::Formatter<'_>, full_name: &str, depth: usize) -> fmt::Result {
+        let fields = match self.index.get(full_name) {
+            Some(&idx) => &self.sproto.types_list[idx].fields[..],
+            None => &[],
+        };
+        let mut nested = self.children(full_name).peekable();
+        if fields.is_empty() && nested.peek().is_none() {
+            return f.write_str("{}");
+        }
+
+        f.write_str("{\n")?;
+        for name in nested {
+            self.write_type(f, name, depth + 1)?;
+        }
+        let indent = INDENT.repeat(depth + 1);
+        for field in fields {
+            writeln!(
+                f,
+                "{}{} {} : {}",
+                indent,
+                field.name,
+                field.tag,
+                self.field_type(full_name, field)
+            )?;
+        }
+        write!(f, "{}}}", INDENT.repeat(depth))
+    }
+
+    /// `[*]type[(key|N)]`, as written inside the type `scope`.
+    fn field_type(&self, scope: &str, field: &Field) -> String {
+        let mut spec = String::new();
+        if field.is_array {
+            spec.push('*');
+        }
+        match &field.field_type {
+            FieldType::Integer if field.decimal_precision > 0 => {
+                let _ = write!(spec, "integer({})", field.decimal_precision.ilog10());
+            }
+            FieldType::Integer => spec.push_str("integer"),
+            FieldType::Boolean => spec.push_str("boolean"),
+            FieldType::String => spec.push_str("string"),
+            FieldType::Binary => spec.push_str("binary"),
+            FieldType::Double => spec.push_str("double"),
+            FieldType::Struct(idx) => {
+                spec.push_str(&self.type_ref(scope, *idx));
+                let sub_type = &self.sproto.types_list[*idx];
+                if field.is_map {
+                    spec.push_str("()");
+                } else if let Ok(key_tag) = u16::try_from(field.key_tag) {
+                    if let Some(key) = sub_type.find_field_by_tag(key_tag) {
+                        let _ = write!(spec, "({})", key.name);
+                    }
+                }
+            }
+        }
+        spec
+    }
+
+    /// The shortest suffix of the type's full name that resolves to it
+    /// from `scope`.
+    fn type_ref(&self, scope: &str, idx: usize) -> String {
+        let full_name = &self.sproto.types_list[idx].name;
+        let mut start = full_name.len();
+        while let Some(dot) = full_name[..start].rfind('.') {
+            let candidate = &full_name[dot + 1..];
+            if resolve_type(&self.index, scope, candidate) == Some(idx) {
+                return candidate.to_string();
+            }
+            start = dot;
+        }
+        full_name.clone()
+    }
+
+    fn write_protocol(&self, f: &mut fmt::Formatter<'_>, proto: &Protocol) -> fmt::Result {
+        write!(f, "{} {} ", proto.name, proto.tag)?;
+        if proto.request.is_none() && proto.response.is_none() && !proto.confirm {
+            return f.write_str("{}\n");
+        }
+
+        f.write_str("{\n")?;
+        let [request, response] = inline_types(self.sproto, proto);
+        self.write_message(f, "request", proto.request, request)?;
+        if proto.confirm {
+            writeln!(f, "{}response nil", INDENT)?;
+        } else {
+            self.write_message(f, "response", proto.response, response)?;
+        }
+        f.write_str("}\n")
+    }
+
+    /// `request T` or `request { ... }` when `inline` is the type's name.
+    fn write_message(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        key: &str,
+        idx: Option<usize>,
+        inline: Option<&str>,
+    ) -> fmt::Result {
+        let Some(idx) = idx else { return Ok(()) };
+        write!(f, "{}{} ", INDENT, key)?;
+        match inline {
+            Some(name) => self.write_body(f, name, 1)?,
+            None => f.write_str(&self.sproto.types_list[idx].name)?,
+        }
+        f.write_char('\n')
+    }
+}
+
+/// The request and response types of `proto` that are printed inline:
+/// those named `<protocol>.request` / `<protocol>.response`, as the parser
+/// names inline types.
+fn inline_types<'a>(sproto: &'a Sproto, proto: &Protocol) -> [Option<&'a str>; 2] {
+    let named = |idx: Option<usize>, key: &str| {
+        let name = sproto.types_list.get(idx?)?.name.as_str();
+        let inline = name.strip_prefix(proto.name.as_str())?.strip_prefix('.')? == key;
+        inline.then_some(name)
+    };
+    [
+        named(proto.request, "request"),
+        named(proto.response, "response"),
+    ]
+}
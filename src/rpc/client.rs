@@ -0,0 +1,484 @@
+//! Async RPC client over a framed byte stream (feature = "tokio").
+//!
+//! `Client` owns a `Host` and the `RequestSender` attached to it, and drives
+//! them over any `AsyncRead + AsyncWrite` stream: a TCP socket, a TLS
+//! stream, or `tokio::io::duplex` in tests. Each call gets the next free
+//! session ID and returns a `ResponseFuture` that resolves with the response
+//! body once the reader task dispatches the matching response.
+//!
+//! ```ignore
+//! let (client, mut incoming) = Client::new(socket, Host::new(local), sender);
+//! let body = client.call("login", &request_body).await?;
+//!
+//! // Requests pushed by the server
+//! while let Some(req) = incoming.recv().await {
+//!     if let Some(responder) = &req.responder {
+//!         client.respond(responder, &response_body)?;
+//!     }
+//! }
+//! ```
+//!
+//! Dropping a `ResponseFuture` cancels the call: its session is forgotten
+//! and a late response is discarded.
+//!
+//! Only framing and I/O errors close the connection. Packets the `Host`
+//! rejects, such as requests for protocols this schema does not know, are
+//! skipped, so a peer running a newer schema does not fail unrelated calls.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Sleep;
+
+use crate::codec::{SprotoDecode, SprotoEncode};
+use crate::error::RpcError;
+use crate::rpc::framing::{FrameDecoder, FrameEncoder, LengthPrefix};
+use crate::rpc::router::decode_empty;
+use crate::rpc::{DispatchResult, Host, RequestSender, Responder};
+use crate::types::Sproto;
+
+/// Connection settings for a `Client`.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    /// Length prefix of each packet on the stream.
+    pub prefix: LengthPrefix,
+    /// Largest incoming frame accepted; defaults to the prefix limit.
+    pub max_frame_len: Option<usize>,
+    /// Timeout applied to every call; `None` waits indefinitely.
+    pub timeout: Option<Duration>,
+}
+
+/// A request pushed by the remote side.
+#[derive(Debug)]
+pub struct IncomingRequest {
+    /// Protocol name.
+    pub name: String,
+    /// Raw wire-encoded request body.
+    pub body: Vec<u8>,
+    /// Present if the remote side expects a response; pass to `Client::respond`.
+    pub responder: Option<Box<Responder>>,
+    /// Optional user data from the package header.
+    pub ud: Option<i64>,
+}
+
+/// Receiver for requests pushed by the remote side.
+///
+/// Requests are queued until received; dropping `Incoming` discards them.
+#[derive(Debug)]
+pub struct Incoming {
+    rx: mpsc::UnboundedReceiver<IncomingRequest>,
+}
+
+impl Incoming {
+    /// Wait for the next request, or `None` once the connection is closed.
+    pub async fn recv(&mut self) -> Option<IncomingRequest> {
+        self.rx.recv().await
+    }
+}
+
+/// Calls waiting for a response, keyed by session.
+struct Pending {
+    calls: HashMap<u64, oneshot::Sender<Result<Vec<u8>, RpcError>>>,
+    closed: bool,
+}
+
+/// State shared by the client handles and the reader/writer tasks.
+struct Shared {
+    host: Mutex<Host>,
+    sender: Mutex<RequestSender>,
+    pending: Mutex<Pending>,
+    next_session: AtomicU64,
+}
+
+impl Shared {
+    /// Fail all pending calls and reject new ones.
+    fn close(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.closed = true;
+        // Dropping the senders resolves each call with `RpcError::Closed`.
+        pending.calls.clear();
+    }
+
+    /// Complete the call waiting on `session`, if it is still waiting.
+    fn resolve(&self, session: u64, result: Result<Vec<u8>, RpcError>) {
+        let tx = self.pending.lock().unwrap().calls.remove(&session);
+        if let Some(tx) = tx {
+            let _ = tx.send(result);
+        }
+    }
+
+    /// Forget a call that timed out or was cancelled.
+    fn forget(&self, session: u64) {
+        self.pending.lock().unwrap().calls.remove(&session);
+        self.host.lock().unwrap().cancel_session(session);
+    }
+}
+
+/// Aborts the reader task once the last `Client` handle is dropped.
+///
+/// Aborting skips the `shared.close()` at the end of the read loop, so the
+/// pending calls are failed here instead.
+struct ReaderTask {
+    handle: JoinHandle<()>,
+    shared: Arc<Shared>,
+}
+
+impl Drop for ReaderTask {
+    fn drop(&mut self) {
+        self.shared.close();
+        self.handle.abort();
+    }
+}
+
+/// An async RPC client. Cheap to clone; clones share the connection.
+#[derive(Clone)]
+pub struct Client {
+    shared: Arc<Shared>,
+    remote: Arc<Sproto>,
+    outgoing: mpsc::UnboundedSender<Vec<u8>>,
+    framer: FrameEncoder,
+    timeout: Option<Duration>,
+    _reader: Arc<ReaderTask>,
+}
+
+impl Client {
+    /// Start a client on `stream` with default options.
+    ///
+    /// `host` dispatches incoming packets and `sender`, usually from
+    /// `host.attach(remote)`, builds outgoing requests. Spawns the reader and
+    /// writer tasks, so it must be called within a Tokio runtime.
+    pub fn new<S>(stream: S, host: Host, sender: RequestSender) -> (Client, Incoming)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        Self::with_options(stream, host, sender, ClientOptions::default())
+    }
+
+    /// Start a client on `stream` with the given options.
+    pub fn with_options<S>(
+        stream: S,
+        host: Host,
+        sender: RequestSender,
+        options: ClientOptions,
+    ) -> (Client, Incoming)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let remote = Arc::clone(&sender.remote_sproto);
+        let shared = Arc::new(Shared {
+            host: Mutex::new(host),
+            sender: Mutex::new(sender),
+            pending: Mutex::new(Pending {
+                calls: HashMap::new(),
+                closed: false,
+            }),
+            next_session: AtomicU64::new(1),
+        });
+
+        let mut decoder = FrameDecoder::with_prefix(options.prefix);
+        if let Some(max) = options.max_frame_len {
+            decoder = decoder.with_max_frame_len(max);
+        }
+        let (reader, writer) = tokio::io::split(stream);
+        let (out_tx, out_rx) = mpsc::unbounded_channel();
+        let (in_tx, in_rx) = mpsc::unbounded_channel();
+        tokio::spawn(write_loop(writer, out_rx, Arc::clone(&shared)));
+        let reader = tokio::spawn(read_loop(reader, decoder, Arc::clone(&shared), in_tx));
+
+        let client = Client {
+            shared: Arc::clone(&shared),
+            remote,
+            outgoing: out_tx,
+            framer: FrameEncoder::with_prefix(options.prefix),
+            timeout: options.timeout,
+            _reader: Arc::new(ReaderTask {
+                handle: reader,
+                shared,
+            }),
+        };
+        (client, Incoming { rx: in_rx })
+    }
+
+    /// Call `protocol_name` with an already-encoded request body.
+    ///
+    /// The request is sent before this returns; the future resolves with the
+    /// raw response body. Dropping the future cancels the call.
+    pub fn call(&self, protocol_name: &str, body: &[u8]) -> ResponseFuture {
+        let session = self.shared.next_session.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut pending = self.shared.pending.lock().unwrap();
+            if pending.closed {
+                return ResponseFuture::failed(session, RpcError::Closed);
+            }
+            pending.calls.insert(session, tx);
+        }
+        // Armed from here on: any early return forgets the session. The
+        // sender registers the session with the host.
+        let guard = PendingCall {
+            shared: Arc::clone(&self.shared),
+            session,
+            armed: true,
+        };
+
+        let packet =
+            self.shared
+                .sender
+                .lock()
+                .unwrap()
+                .request(protocol_name, body, Some(session), None);
+        if let Err(err) = packet.and_then(|p| self.send(&p)) {
+            return ResponseFuture::failed(session, err);
+        }
+
+        let future = ResponseFuture {
+            session,
+            state: State::Waiting(rx),
+            guard: Some(guard),
+            deadline: None,
+        };
+        match self.timeout {
+            Some(timeout) => future.with_timeout(timeout),
+            None => future,
+        }
+    }
+
+    /// Call `protocol_name`, encoding `request` and decoding the response
+    /// against the protocol's types in the remote schema.
+    ///
+    /// As with `Router`, a protocol without a request or response type
+    /// exchanges the empty message; use `()` for it. A `response nil`
+    /// protocol is answered with an empty acknowledgement, so its call
+    /// resolves to `()` once the peer confirms.
+    pub async fn call_as<Req, Resp>(
+        &self,
+        protocol_name: &str,
+        request: &Req,
+    ) -> Result<Resp, RpcError>
+    where
+        Req: SprotoEncode,
+        Resp: SprotoDecode,
+    {
+        let remote = &self.remote;
+        let proto = remote
+            .get_protocol(protocol_name)
+            .ok_or_else(|| RpcError::UnknownProtocol(protocol_name.to_string()))?;
+        let (request_type, response_type) = (proto.request, proto.response);
+
+        let mut body = Vec::new();
+        if let Some(idx) = request_type {
+            request.encode_to(remote, &remote.types_list[idx], &mut body)?;
+        }
+        let response = self.call(protocol_name, &body).await?;
+        match response_type {
+            Some(idx) => Ok(Resp::decode_from(
+                remote,
+                &remote.types_list[idx],
+                &response,
+            )?),
+            None => decode_empty(remote),
+        }
+    }
+
+    /// Send a request without a session; no response is expected.
+    pub fn notify(&self, protocol_name: &str, body: &[u8]) -> Result<(), RpcError> {
+        let packet = self
+            .shared
+            .sender
+            .lock()
+            .unwrap()
+            .request(protocol_name, body, None, None)?;
+        self.send(&packet)
+    }
+
+    /// Answer a request received from `Incoming`.
+    pub fn respond(&self, responder: &Responder, body: &[u8]) -> Result<(), RpcError> {
+        let packet = responder.respond(body, None)?;
+        self.send(&packet)
+    }
+
+    /// Number of calls still waiting for a response.
+    pub fn pending_calls(&self) -> usize {
+        self.shared.pending.lock().unwrap().calls.len()
+    }
+
+    /// Whether the connection has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.shared.pending.lock().unwrap().closed
+    }
+
+    /// Frame a packet and queue it for the writer task.
+    fn send(&self, packet: &[u8]) -> Result<(), RpcError> {
+        let frame = self.framer.encode(packet)?;
+        self.outgoing.send(frame).map_err(|_| RpcError::Closed)
+    }
+}
+
+/// Removes a call from the pending table unless it completed normally.
+struct PendingCall {
+    shared: Arc<Shared>,
+    session: u64,
+    armed: bool,
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        if self.armed {
+            self.shared.forget(self.session);
+        }
+    }
+}
+
+enum State {
+    Waiting(oneshot::Receiver<Result<Vec<u8>, RpcError>>),
+    Failed(Option<RpcError>),
+}
+
+/// The response to one `Client::call`.
+///
+/// Resolves with the raw response body, `RpcError::Timeout` if a timeout
+/// elapses first, `RpcError::Closed` if the connection goes away, or
+/// `RpcError::InvalidResponse` if a strict `Host` rejects the response body.
+/// Dropping it cancels the call.
+#[must_use = "dropping a ResponseFuture cancels the call"]
+pub struct ResponseFuture {
+    session: u64,
+    state: State,
+    guard: Option<PendingCall>,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl ResponseFuture {
+    fn failed(session: u64, err: RpcError) -> Self {
+        ResponseFuture {
+            session,
+            state: State::Failed(Some(err)),
+            guard: None,
+            deadline: None,
+        }
+    }
+
+    /// The session ID allocated for this call.
+    pub fn session(&self) -> u64 {
+        self.session
+    }
+
+    /// Fail with `RpcError::Timeout` if no response arrives within `timeout`,
+    /// replacing any timeout already set.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Box::pin(tokio::time::sleep(timeout)));
+        self
+    }
+}
+
+impl Future for ResponseFuture {
+    type Output = Result<Vec<u8>, RpcError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let rx = match &mut this.state {
+            State::Waiting(rx) => rx,
+            State::Failed(err) => {
+                return Poll::Ready(Err(err
+                    .take()
+                    .expect("ResponseFuture polled after completion")));
+            }
+        };
+
+        if let Poll::Ready(result) = Pin::new(rx).poll(cx) {
+            // The reader task already removed the session.
+            if let Some(mut guard) = this.guard.take() {
+                guard.armed = false;
+            }
+            this.state = State::Failed(None);
+            return Poll::Ready(result.unwrap_or(Err(RpcError::Closed)));
+        }
+
+        if let Some(deadline) = &mut this.deadline {
+            if deadline.as_mut().poll(cx).is_ready() {
+                this.guard = None;
+                this.state = State::Failed(None);
+                return Poll::Ready(Err(RpcError::Timeout(this.session)));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+async fn read_loop<R>(
+    mut reader: R,
+    mut decoder: FrameDecoder,
+    shared: Arc<Shared>,
+    incoming: mpsc::UnboundedSender<IncomingRequest>,
+) where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = vec![0u8; 8192];
+    'read: loop {
+        let n = match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        decoder.extend(&buf[..n]);
+        loop {
+            let packet = match decoder.next_frame() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(_) => break 'read,
+            };
+            let result = shared.host.lock().unwrap().dispatch(&packet);
+            match result {
+                Ok(DispatchResult::Response { session, body, .. }) => {
+                    shared.resolve(session, Ok(body));
+                }
+                Ok(DispatchResult::Request {
+                    name,
+                    body,
+                    responder,
+                    ud,
+                }) => {
+                    let _ = incoming.send(IncomingRequest {
+                        name,
+                        body,
+                        responder,
+                        ud,
+                    });
+                }
+                // Fails only the call it answers.
+                Err(err @ RpcError::InvalidResponse { session, .. }) => {
+                    shared.resolve(session, Err(err));
+                }
+                // A late response to a call that timed out or was cancelled,
+                // or a packet this side cannot handle, such as a request for
+                // a protocol added to the peer's schema later. Only the
+                // stream itself going bad closes the connection.
+                Err(_) => {}
+            }
+        }
+    }
+    shared.close();
+}
+
+async fn write_loop<W>(
+    mut writer: W,
+    mut frames: mpsc::UnboundedReceiver<Vec<u8>>,
+    shared: Arc<Shared>,
+) where
+    W: AsyncWrite + Unpin,
+{
+    while let Some(frame) = frames.recv().await {
+        if writer.write_all(&frame).await.is_err() || writer.flush().await.is_err() {
+            shared.close();
+            return;
+        }
+    }
+    let _ = writer.shutdown().await;
+}
@@ -0,0 +1,244 @@
+//! Length-prefixed stream framing for RPC packets.
+//!
+//! `Host::dispatch` and `RequestSender::request` work on whole packed
+//! packets. On a byte stream such as TCP each packet is preceded by its
+//! length, as in skynet's gate service: a 2-byte big-endian prefix by
+//! default, or a 4-byte one for larger packets.
+//!
+//! ```ignore
+//! let mut decoder = FrameDecoder::new();
+//! decoder.extend(&bytes_from_socket);
+//! while let Some(packet) = decoder.next_frame()? {
+//!     host.dispatch(&packet)?;
+//! }
+//! ```
+
+use crate::error::RpcError;
+
+/// Width of the big-endian length prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthPrefix {
+    /// 2-byte prefix, frames up to 65535 bytes (skynet default).
+    #[default]
+    U16,
+    /// 4-byte prefix.
+    U32,
+}
+
+impl LengthPrefix {
+    /// Number of bytes in the prefix.
+    pub fn size(self) -> usize {
+        match self {
+            LengthPrefix::U16 => 2,
+            LengthPrefix::U32 => 4,
+        }
+    }
+
+    /// Largest frame length the prefix can express.
+    pub fn max_len(self) -> usize {
+        match self {
+            LengthPrefix::U16 => u16::MAX as usize,
+            LengthPrefix::U32 => u32::MAX as usize,
+        }
+    }
+
+    fn write(self, len: usize, out: &mut Vec<u8>) {
+        match self {
+            LengthPrefix::U16 => out.extend_from_slice(&(len as u16).to_be_bytes()),
+            LengthPrefix::U32 => out.extend_from_slice(&(len as u32).to_be_bytes()),
+        }
+    }
+
+    fn read(self, data: &[u8]) -> usize {
+        match self {
+            LengthPrefix::U16 => u16::from_be_bytes([data[0], data[1]]) as usize,
+            LengthPrefix::U32 => u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize,
+        }
+    }
+}
+
+/// Writes packets with a length prefix.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameEncoder {
+    prefix: LengthPrefix,
+}
+
+impl FrameEncoder {
+    /// Create an encoder with a 2-byte prefix.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an encoder with the given prefix width.
+    pub fn with_prefix(prefix: LengthPrefix) -> Self {
+        FrameEncoder { prefix }
+    }
+
+    /// The prefix width in use.
+    pub fn prefix(&self) -> LengthPrefix {
+        self.prefix
+    }
+
+    /// Frame a packet into a new buffer.
+    pub fn encode(&self, packet: &[u8]) -> Result<Vec<u8>, RpcError> {
+        let mut out = Vec::with_capacity(self.prefix.size() + packet.len());
+        self.encode_into(packet, &mut out)?;
+        Ok(out)
+    }
+
+    /// Append a framed packet to `out`.
+    ///
+    /// Fails without writing anything if the packet is too long for the prefix.
+    pub fn encode_into(&self, packet: &[u8], out: &mut Vec<u8>) -> Result<(), RpcError> {
+        let max = self.prefix.max_len();
+        if packet.len() > max {
+            return Err(RpcError::FrameTooLarge {
+                size: packet.len(),
+                max,
+            });
+        }
+        out.reserve(self.prefix.size() + packet.len());
+        self.prefix.write(packet.len(), out);
+        out.extend_from_slice(packet);
+        Ok(())
+    }
+}
+
+/// Splits a byte stream into length-prefixed packets.
+///
+/// Bytes are fed in with [`extend`](Self::extend) as they arrive, in chunks
+/// of any size; [`next_frame`](Self::next_frame) yields each packet once all
+/// of it has been buffered.
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    prefix: LengthPrefix,
+    max_frame_len: usize,
+    buf: Vec<u8>,
+    /// Start of unconsumed data in `buf`.
+    pos: usize,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::with_prefix(LengthPrefix::default())
+    }
+}
+
+impl FrameDecoder {
+    /// Create a decoder for 2-byte prefixes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a decoder for the given prefix width.
+    pub fn with_prefix(prefix: LengthPrefix) -> Self {
+        FrameDecoder {
+            prefix,
+            max_frame_len: prefix.max_len(),
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Reject frames longer than `max` bytes instead of buffering them.
+    ///
+    /// Useful with 4-byte prefixes, where a peer could otherwise announce a
+    /// frame of up to 4 GiB.
+    pub fn with_max_frame_len(mut self, max: usize) -> Self {
+        self.max_frame_len = max.min(self.prefix.max_len());
+        self
+    }
+
+    /// The prefix width in use.
+    pub fn prefix(&self) -> LengthPrefix {
+        self.prefix
+    }
+
+    /// Append bytes read from the stream.
+    pub fn extend(&mut self, data: &[u8]) {
+        // Reclaim consumed space before growing the buffer.
+        if self.pos > 0 && self.pos >= self.buf.len() / 2 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Take the next complete packet, or `None` if more bytes are needed.
+    ///
+    /// An oversized length prefix is an error; the stream cannot be
+    /// resynchronised after it, so the connection should be closed.
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, RpcError> {
+        let pending = &self.buf[self.pos..];
+        let prefix_size = self.prefix.size();
+        if pending.len() < prefix_size {
+            return Ok(None);
+        }
+        let len = self.prefix.read(pending);
+        if len > self.max_frame_len {
+            return Err(RpcError::FrameTooLarge {
+                size: len,
+                max: self.max_frame_len,
+            });
+        }
+        if pending.len() < prefix_size + len {
+            return Ok(None);
+        }
+        let frame = pending[prefix_size..prefix_size + len].to_vec();
+        self.pos += prefix_size + len;
+        if self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+        }
+        Ok(Some(frame))
+    }
+
+    /// Number of buffered bytes not yet returned as a packet.
+    pub fn buffered(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// Whether no bytes are buffered.
+    pub fn is_empty(&self) -> bool {
+        self.buffered() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_big_endian() {
+        let packet = vec![0xAB; 0x0102];
+        let framed = FrameEncoder::new().encode(&packet).unwrap();
+        assert_eq!(&framed[..2], &[0x01, 0x02]);
+        assert_eq!(&framed[2..], &packet[..]);
+
+        let framed = FrameEncoder::with_prefix(LengthPrefix::U32)
+            .encode(&packet)
+            .unwrap();
+        assert_eq!(&framed[..4], &[0x00, 0x00, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_decoder_compacts_buffer() {
+        let enc = FrameEncoder::new();
+        let mut stream = Vec::new();
+        for i in 0..100u8 {
+            enc.encode_into(&[i; 10], &mut stream).unwrap();
+        }
+        let mut dec = FrameDecoder::new();
+        let mut frames = Vec::new();
+        for chunk in stream.chunks(7) {
+            dec.extend(chunk);
+            while let Some(frame) = dec.next_frame().unwrap() {
+                frames.push(frame);
+            }
+            assert!(dec.buf.len() < 40, "buffer grew to {}", dec.buf.len());
+        }
+        assert_eq!(frames.len(), 100);
+        assert_eq!(frames[99], vec![99; 10]);
+        assert!(dec.is_empty());
+    }
+}
@@ -1,10 +1,21 @@
-use std::collections::HashMap;
-use std::collections::HashSet;
+#[cfg(feature = "tokio")]
+pub mod client;
+pub mod framing;
+pub mod router;
+mod session;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::codec::wire::*;
 use crate::error::{DecodeError, RpcError};
 use crate::pack;
-use crate::types::Sproto;
+use crate::types::{Protocol, Sproto};
+use crate::value::decode_value;
+use session::SessionTable;
+
+pub use router::Router;
+pub use session::{Clock, SessionInfo, SystemClock};
 
 /// Decoded package header fields (private).
 struct PackageHeader {
@@ -15,14 +26,17 @@ struct PackageHeader {
 }
 
 /// RPC dispatch result.
+///
+/// `Host::dispatch` returns bodies as owned `Vec<u8>`s; `Host::dispatch_into`
+/// returns `DispatchResult<&[u8]>`, borrowing them from the caller's buffer.
 #[derive(Debug)]
-pub enum DispatchResult {
+pub enum DispatchResult<B = Vec<u8>> {
     /// An incoming request message.
     Request {
         /// Protocol name.
         name: String,
         /// Raw wire-encoded request body (empty if no request data).
-        body: Vec<u8>,
+        body: B,
         /// Responder to create the response packet, if a session is present.
         responder: Option<Box<Responder>>,
         /// Optional user data from the package header.
@@ -32,17 +46,56 @@ pub enum DispatchResult {
     Response {
         /// Session ID matching the original request.
         session: u64,
+        /// The protocol of the original request, from the schema of the
+        /// `RequestSender` that sent it; `None` for sessions registered with
+        /// `Host::register_session`. Its `response` type indexes that schema.
+        protocol: Option<Protocol>,
         /// Raw wire-encoded response body (empty if no response data).
-        body: Vec<u8>,
+        body: B,
         /// Optional user data from the package header.
         ud: Option<i64>,
     },
 }
 
+impl DispatchResult<&[u8]> {
+    /// Copy the borrowed body into an owned `DispatchResult`.
+    pub fn into_owned(self) -> DispatchResult {
+        match self {
+            DispatchResult::Request {
+                name,
+                body,
+                responder,
+                ud,
+            } => DispatchResult::Request {
+                name,
+                body: body.to_vec(),
+                responder,
+                ud,
+            },
+            DispatchResult::Response {
+                session,
+                protocol,
+                body,
+                ud,
+            } => DispatchResult::Response {
+                session,
+                protocol,
+                body: body.to_vec(),
+                ud,
+            },
+        }
+    }
+}
+
 /// A responder that encodes a response packet with the correct session.
 #[derive(Debug)]
 pub struct Responder {
     session: u64,
+    protocol: String,
+    /// Index of the protocol's response type, if it has one.
+    response: Option<usize>,
+    /// Schema to check response bodies against, in strict mode.
+    strict: Option<Arc<Sproto>>,
 }
 
 impl Responder {
@@ -51,33 +104,95 @@ impl Responder {
         self.session
     }
 
+    /// Name of the protocol being answered.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// Whether the protocol has no response type, so the only valid answer is
+    /// an empty acknowledgement (`response nil`).
+    pub fn is_ack_only(&self) -> bool {
+        self.response.is_none()
+    }
+
+    /// Build an empty response packet, acknowledging the request.
+    pub fn acknowledge(&self, ud: Option<i64>) -> Result<Vec<u8>, RpcError> {
+        self.respond(&[], ud)
+    }
+
     /// Build a response packet from an already-encoded body.
     ///
     /// The `body` should be the wire-encoded response message (e.g., produced by
     /// serde or derive encoding). Pass an empty slice if there is no response data.
+    ///
+    /// Fails with `RpcError::InvalidBody` if the protocol has no response
+    /// type but `body` is not empty (all zero bytes count as empty), or in
+    /// strict mode if `body` does not decode as the response type.
     pub fn respond(&self, body: &[u8], ud: Option<i64>) -> Result<Vec<u8>, RpcError> {
+        let mut out = Vec::new();
+        self.respond_into(body, ud, &mut out)?;
+        Ok(out)
+    }
+
+    /// Like [`respond`](Self::respond), but appends the packet to `out`.
+    ///
+    /// Nothing is written on error.
+    pub fn respond_into(
+        &self,
+        body: &[u8],
+        ud: Option<i64>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), RpcError> {
+        match (&self.strict, self.response) {
+            // Checked even in non-strict mode: only an empty acknowledgement
+            // can answer a protocol without a response type.
+            (_, None) if !is_empty_body(body) => {
+                return Err(RpcError::InvalidBody {
+                    protocol: self.protocol.clone(),
+                    kind: "response",
+                    reason: "protocol has no response type".into(),
+                });
+            }
+            (Some(sproto), Some(_)) => {
+                check_body(sproto, &self.protocol, "response", self.response, body)?;
+            }
+            _ => {}
+        }
         // Response header: no type field (indicates response), session present
         let header = encode_package_header(None, Some(self.session), ud);
-        let mut combined = header;
-        combined.extend_from_slice(body);
-        Ok(pack::pack(&combined))
+        pack::pack_chain_append(&header, body, out);
+        Ok(())
     }
 }
 
 /// A request sender created by `Host::attach()`.
 #[derive(Debug)]
 pub struct RequestSender {
-    remote_sproto: Sproto,
-    sessions: HashMap<u64, ()>,
+    remote_sproto: Arc<Sproto>,
+    sessions: SessionTable,
+    strict: bool,
 }
 
 impl RequestSender {
+    /// The remote schema that requests are built against.
+    pub fn remote_sproto(&self) -> &Sproto {
+        &self.remote_sproto
+    }
+
     /// Create and send a request packet.
     ///
     /// - `protocol_name`: name of the protocol to call on the remote side
     /// - `body`: wire-encoded request data (pass empty slice for no-data requests)
     /// - `session`: optional session ID for request-response tracking
     /// - `ud`: optional user data
+    ///
+    /// A session is registered with the host this sender is attached to, so
+    /// its response is accepted by `Host::dispatch`.
+    ///
+    /// Fails with `RpcError::OneWay` if a session is given for a protocol
+    /// that is never answered, `RpcError::DuplicateSession` if the session
+    /// is already pending, and in strict mode with
+    /// `RpcError::InvalidBody` if `body` does not match the request type.
     pub fn request(
         &mut self,
         protocol_name: &str,
@@ -85,51 +200,159 @@ impl RequestSender {
         session: Option<u64>,
         ud: Option<i64>,
     ) -> Result<Vec<u8>, RpcError> {
+        let mut out = Vec::new();
+        self.request_into(protocol_name, body, session, ud, &mut out)?;
+        Ok(out)
+    }
+
+    /// Like [`request`](Self::request), but appends the packet to `out`.
+    ///
+    /// Nothing is written on error.
+    pub fn request_into(
+        &mut self,
+        protocol_name: &str,
+        body: &[u8],
+        session: Option<u64>,
+        ud: Option<i64>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), RpcError> {
         let proto = self
             .remote_sproto
             .get_protocol(protocol_name)
             .ok_or_else(|| RpcError::UnknownProtocol(protocol_name.to_string()))?;
 
+        if session.is_some() && !proto.expects_response() {
+            return Err(RpcError::OneWay(proto.name.clone()));
+        }
+        if self.strict {
+            check_body(
+                &self.remote_sproto,
+                &proto.name,
+                "request",
+                proto.request,
+                body,
+            )?;
+        }
+
         let proto_tag = proto.tag;
 
         // Build request header with protocol type tag
         let header = encode_package_header(Some(proto_tag), session, ud);
 
         if let Some(s) = session {
-            self.sessions.insert(s, ());
+            if !self
+                .sessions
+                .open(s, Arc::clone(&self.remote_sproto), proto_tag)
+            {
+                return Err(RpcError::DuplicateSession(s));
+            }
         }
 
-        let mut combined = header;
-        combined.extend_from_slice(body);
-        Ok(pack::pack(&combined))
+        pack::pack_chain_append(&header, body, out);
+        Ok(())
     }
 }
 
 /// RPC host endpoint that dispatches incoming messages.
 pub struct Host {
-    sproto: Sproto,
-    sessions: HashSet<u64>,
+    sproto: Arc<Sproto>,
+    sessions: SessionTable,
+    strict: bool,
+    clock: Arc<dyn Clock>,
+    /// Largest unpacked packet `dispatch` accepts.
+    unpack_limit: usize,
 }
 
 impl Host {
     /// Create a new RPC host.
     pub fn new(sproto: Sproto) -> Self {
         Host {
-            sproto,
-            sessions: HashSet::new(),
+            sproto: Arc::new(sproto),
+            sessions: SessionTable::default(),
+            strict: false,
+            clock: Arc::new(SystemClock),
+            unpack_limit: usize::MAX,
         }
     }
 
+    /// Check message bodies against the protocol schemas.
+    ///
+    /// In strict mode, incoming request bodies and the bodies given to
+    /// `Responder::respond` and `RequestSender::request` (for senders
+    /// attached afterwards) must decode as the protocol's request or
+    /// response type, and incoming requests of one-way protocols must not
+    /// carry a session. Each body is fully decoded, so this is meant for
+    /// debug builds and tests, e.g. `.with_strict(cfg!(debug_assertions))`.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Whether strict mode is enabled.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Reject packets that unpack to more than `max_len` bytes.
+    ///
+    /// `dispatch` and `dispatch_into` then fail with
+    /// `RpcError::Pack(PackError::TooLarge)` before allocating more than
+    /// `max_len` bytes for a packet, header included. Unlimited by default;
+    /// set it for packets from untrusted peers, which can otherwise expand
+    /// each packed byte to 8.
+    pub fn with_unpack_limit(mut self, max_len: usize) -> Self {
+        self.unpack_limit = max_len;
+        self
+    }
+
+    /// The limit set with `with_unpack_limit`, if any.
+    pub fn unpack_limit(&self) -> Option<usize> {
+        (self.unpack_limit != usize::MAX).then_some(self.unpack_limit)
+    }
+
+    /// Use `clock` to compute session deadlines instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
     /// Dispatch an incoming packed binary message.
     ///
     /// Returns a `DispatchResult` with the raw body bytes. The caller is
     /// responsible for decoding the body using serde or derive traits.
+    ///
+    /// In strict mode, a response whose body does not match its protocol
+    /// fails with `RpcError::InvalidResponse`, naming the session; the
+    /// session is no longer pending afterwards.
     pub fn dispatch(&mut self, packed_data: &[u8]) -> Result<DispatchResult, RpcError> {
-        let unpacked = pack::unpack(packed_data)?;
+        let unpacked = pack::unpack_with_limit(packed_data, self.unpack_limit)?;
+        Ok(self.dispatch_unpacked(&unpacked)?.into_owned())
+    }
 
+    /// Dispatch an incoming packed message without allocating its body.
+    ///
+    /// The packet is unpacked into `buf`, which is cleared first and can be
+    /// reused across calls, and the returned body borrows from it. A request
+    /// still allocates its protocol name and `Responder`, and a response
+    /// with a recorded protocol allocates a clone of that `Protocol`,
+    /// including its name.
+    pub fn dispatch_into<'a>(
+        &mut self,
+        packed_data: &[u8],
+        buf: &'a mut Vec<u8>,
+    ) -> Result<DispatchResult<&'a [u8]>, RpcError> {
+        buf.clear();
+        pack::unpack_into_with_limit(packed_data, buf, self.unpack_limit)?;
+        self.dispatch_unpacked(buf)
+    }
+
+    fn dispatch_unpacked<'a>(
+        &mut self,
+        unpacked: &'a [u8],
+    ) -> Result<DispatchResult<&'a [u8]>, RpcError> {
         // Decode the package header inline
-        let header = decode_package_header(&unpacked)?;
-        let body = unpacked[header.bytes_consumed..].to_vec();
+        let header = decode_package_header(unpacked)?;
+        let body = &unpacked[header.bytes_consumed..];
 
         if let Some(proto_type) = header.type_tag {
             // REQUEST
@@ -138,14 +361,24 @@ impl Host {
                 .get_protocol_by_tag(proto_type as u16)
                 .ok_or_else(|| RpcError::UnknownProtocol(format!("tag {}", proto_type)))?;
 
-            let proto_name = proto.name.clone();
+            if self.strict {
+                if header.session.is_some() && !proto.expects_response() {
+                    return Err(RpcError::OneWay(proto.name.clone()));
+                }
+                check_body(&self.sproto, &proto.name, "request", proto.request, body)?;
+            }
 
-            let responder = header
-                .session
-                .map(|s| Box::new(Responder { session: s as u64 }));
+            let responder = header.session.map(|s| {
+                Box::new(Responder {
+                    session: s as u64,
+                    protocol: proto.name.clone(),
+                    response: proto.response,
+                    strict: self.strict.then(|| Arc::clone(&self.sproto)),
+                })
+            });
 
             Ok(DispatchResult::Request {
-                name: proto_name,
+                name: proto.name.clone(),
                 body,
                 responder,
                 ud: header.ud,
@@ -156,12 +389,33 @@ impl Host {
                 RpcError::Decode(DecodeError::InvalidData("response without session".into()))
             })? as u64;
 
-            if !self.sessions.remove(&session_id) {
-                return Err(RpcError::UnknownSession(session_id));
+            // The session is answered even if the body is invalid, so it is
+            // removed either way; the error names it instead.
+            let pending = self
+                .sessions
+                .remove(session_id)
+                .ok_or(RpcError::UnknownSession(session_id))?;
+            let protocol = pending.protocol();
+            if let (true, Some(proto), Some((remote, _))) =
+                (self.strict, protocol, &pending.protocol)
+            {
+                check_body(remote, &proto.name, "response", proto.response, body).map_err(
+                    |err| match err {
+                        RpcError::InvalidBody {
+                            protocol, reason, ..
+                        } => RpcError::InvalidResponse {
+                            session: session_id,
+                            protocol,
+                            reason,
+                        },
+                        other => other,
+                    },
+                )?;
             }
 
             Ok(DispatchResult::Response {
                 session: session_id,
+                protocol: protocol.cloned(),
                 body,
                 ud: header.ud,
             })
@@ -169,17 +423,95 @@ impl Host {
     }
 
     /// Create a `RequestSender` attached to a remote sproto schema.
+    ///
+    /// The sender shares this host's session table: requests it sends with a
+    /// session are expected by `dispatch` automatically.
     pub fn attach(&self, remote_sproto: Sproto) -> RequestSender {
         RequestSender {
-            remote_sproto,
-            sessions: HashMap::new(),
+            remote_sproto: Arc::new(remote_sproto),
+            sessions: self.sessions.clone(),
+            strict: self.strict,
         }
     }
 
     /// Register a session for response tracking.
+    ///
+    /// Only needed for requests not sent through a sender from `attach`;
+    /// their responses carry no `protocol`.
     pub fn register_session(&mut self, session: u64) {
-        self.sessions.insert(session);
+        self.sessions.register(session, None);
+    }
+
+    /// Register a session that expires `timeout` from now.
+    ///
+    /// Can also be called for a session already opened by an attached
+    /// sender, to give it a deadline; an existing deadline is replaced.
+    /// Expired sessions are only removed by `poll_expired`.
+    pub fn register_session_with_timeout(&mut self, session: u64, timeout: Duration) {
+        let deadline = self.clock.now() + timeout;
+        self.sessions.register(session, Some(deadline));
     }
+
+    /// Stop expecting a response for `session`.
+    ///
+    /// Returns whether the session was registered. A response that arrives
+    /// later is rejected with `RpcError::UnknownSession`.
+    pub fn cancel_session(&mut self, session: u64) -> bool {
+        self.sessions.remove(session).is_some()
+    }
+
+    /// Remove and return the sessions whose deadline is at or before `now`,
+    /// earliest deadline first.
+    ///
+    /// Call it periodically, e.g. from a timer tick, passing the host's clock
+    /// time. Responses to the returned sessions are rejected with
+    /// `RpcError::UnknownSession`.
+    pub fn poll_expired(&mut self, now: Instant) -> Vec<SessionInfo> {
+        self.sessions.expire(now)
+    }
+
+    /// The sessions awaiting a response, ordered by session ID.
+    ///
+    /// Includes sessions opened by attached senders. The result is a
+    /// snapshot; it does not change as responses arrive.
+    pub fn pending_sessions(&self) -> Vec<SessionInfo> {
+        self.sessions.snapshot()
+    }
+}
+
+/// Whether `body` carries no fields.
+///
+/// Unpacked bodies end in zero padding, so an all-zero body counts as empty.
+fn is_empty_body(body: &[u8]) -> bool {
+    body.iter().all(|&b| b == 0)
+}
+
+/// Check that `body` decodes as the type at `type_index`.
+///
+/// An empty body (see `is_empty_body`) is accepted whether or not the
+/// protocol has a type.
+fn check_body(
+    sproto: &Sproto,
+    protocol: &str,
+    kind: &'static str,
+    type_index: Option<usize>,
+    body: &[u8],
+) -> Result<(), RpcError> {
+    if is_empty_body(body) {
+        return Ok(());
+    }
+    let reason = match type_index {
+        Some(idx) => match decode_value(sproto, &sproto.types_list[idx], body) {
+            Ok(_) => return Ok(()),
+            Err(e) => e.to_string(),
+        },
+        None => format!("protocol has no {} type", kind),
+    };
+    Err(RpcError::InvalidBody {
+        protocol: protocol.to_string(),
+        kind,
+        reason,
+    })
 }
 
 // ---------------------------------------------------------------------------
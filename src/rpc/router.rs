@@ -0,0 +1,170 @@
+//! Typed request handlers, registered per protocol.
+//!
+//! A `Router` maps protocols of the local schema to handlers. Each handler
+//! receives the request body decoded against `Protocol::request` and returns
+//! a value that is encoded against `Protocol::response`, so a server loop
+//! does not need a `match` over protocol names:
+//!
+//! ```ignore
+//! let mut router = Router::new(schema.clone());
+//! router.on("login", |req: LoginRequest| Ok(LoginResponse { ok: true }))?;
+//! router.on("ping", |_: ()| Ok(()))?;
+//!
+//! if let DispatchResult::Request { name, body, responder, .. } = host.dispatch(&packet)? {
+//!     if let Some(reply) = router.handle(&name, &body, responder.as_deref())? {
+//!         send(reply);
+//!     }
+//! }
+//! ```
+//!
+//! Protocols without a request (or response) type exchange the empty
+//! message; use `()` for them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::codec::{DecodeLimits, SprotoDecode, SprotoEncode};
+use crate::error::RpcError;
+use crate::rpc::Responder;
+use crate::types::{Protocol, Sproto, SprotoType};
+
+/// A registered handler: request body in, response body out.
+type Handler = Box<dyn Fn(&[u8], DecodeLimits) -> Result<Vec<u8>, RpcError> + Send + Sync>;
+
+/// Handler for protocols without a registered handler.
+type Fallback = Box<dyn Fn(&Protocol, &[u8]) -> Result<Option<Vec<u8>>, RpcError> + Send + Sync>;
+
+/// Dispatches decoded requests to per-protocol handlers.
+pub struct Router {
+    sproto: Arc<Sproto>,
+    handlers: HashMap<u16, Handler>,
+    fallback: Option<Fallback>,
+    limits: DecodeLimits,
+}
+
+impl Router {
+    /// Create a router for the protocols of `sproto`, the schema the host
+    /// dispatches against.
+    pub fn new(sproto: impl Into<Arc<Sproto>>) -> Self {
+        Router {
+            sproto: sproto.into(),
+            handlers: HashMap::new(),
+            fallback: None,
+            limits: DecodeLimits::default(),
+        }
+    }
+
+    /// Decode request bodies with `limits` instead of the defaults.
+    pub fn with_decode_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Register `handler` for the protocol named `protocol_name`.
+    ///
+    /// Replaces any handler already registered for the protocol.
+    pub fn on<Req, Resp, F>(
+        &mut self,
+        protocol_name: &str,
+        handler: F,
+    ) -> Result<&mut Self, RpcError>
+    where
+        Req: SprotoDecode + 'static,
+        Resp: SprotoEncode + 'static,
+        F: Fn(Req) -> Result<Resp, RpcError> + Send + Sync + 'static,
+    {
+        let tag = self
+            .sproto
+            .get_protocol(protocol_name)
+            .ok_or_else(|| RpcError::UnknownProtocol(protocol_name.to_string()))?
+            .tag;
+        self.on_tag(tag, handler)
+    }
+
+    /// Register `handler` for the protocol with tag `tag`.
+    pub fn on_tag<Req, Resp, F>(&mut self, tag: u16, handler: F) -> Result<&mut Self, RpcError>
+    where
+        Req: SprotoDecode + 'static,
+        Resp: SprotoEncode + 'static,
+        F: Fn(Req) -> Result<Resp, RpcError> + Send + Sync + 'static,
+    {
+        let proto = self
+            .sproto
+            .get_protocol_by_tag(tag)
+            .ok_or_else(|| RpcError::UnknownProtocol(format!("tag {}", tag)))?;
+        let request = proto.request;
+        let response = proto.response;
+        let sproto = Arc::clone(&self.sproto);
+
+        let erased: Handler = Box::new(move |body: &[u8], limits| {
+            let req = match request {
+                Some(idx) => {
+                    Req::decode_from_with_limits(&sproto, &sproto.types_list[idx], body, limits)?
+                }
+                None => decode_empty::<Req>(&sproto)?,
+            };
+            let resp = handler(req)?;
+            let mut out = Vec::new();
+            if let Some(idx) = response {
+                resp.encode_to(&sproto, &sproto.types_list[idx], &mut out)?;
+            }
+            Ok(out)
+        });
+        self.handlers.insert(tag, erased);
+        Ok(self)
+    }
+
+    /// Handle requests for protocols without a registered handler.
+    ///
+    /// `fallback` gets the protocol and the raw request body, and returns the
+    /// raw response body, `None` for no response, or an error. Without a
+    /// fallback, such requests fail with `RpcError::Unhandled`.
+    pub fn fallback<F>(&mut self, fallback: F) -> &mut Self
+    where
+        F: Fn(&Protocol, &[u8]) -> Result<Option<Vec<u8>>, RpcError> + Send + Sync + 'static,
+    {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
+
+    /// Whether a handler is registered for the protocol with tag `tag`.
+    pub fn has_handler(&self, tag: u16) -> bool {
+        self.handlers.contains_key(&tag)
+    }
+
+    /// Handle a request from `DispatchResult::Request`.
+    ///
+    /// Runs the handler for `protocol_name` and, if the request carried a
+    /// session, returns the packed response packet built by `responder`.
+    pub fn handle(
+        &self,
+        protocol_name: &str,
+        body: &[u8],
+        responder: Option<&Responder>,
+    ) -> Result<Option<Vec<u8>>, RpcError> {
+        let proto = self
+            .sproto
+            .get_protocol(protocol_name)
+            .ok_or_else(|| RpcError::UnknownProtocol(protocol_name.to_string()))?;
+
+        let response = match self.handlers.get(&proto.tag) {
+            Some(handler) => Some(handler(body, self.limits)?),
+            None => match &self.fallback {
+                Some(fallback) => fallback(proto, body)?,
+                None => return Err(RpcError::Unhandled(proto.name.clone())),
+            },
+        };
+
+        match (responder, response) {
+            (Some(responder), Some(body)) => Ok(Some(responder.respond(&body, None)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Decode the empty message, for protocols without a request type.
+pub(crate) fn decode_empty<T: SprotoDecode>(sproto: &Sproto) -> Result<T, RpcError> {
+    let empty = SprotoType::new(String::new(), Vec::new());
+    // A struct with no fields: a zero field count.
+    Ok(T::decode_from(sproto, &empty, &[0, 0])?)
+}
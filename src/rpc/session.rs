@@ -0,0 +1,150 @@
+//! Sessions awaiting a response, shared by a `Host` and its senders.
+//!
+//! `Host::attach` hands the host's table to the new `RequestSender`, so a
+//! request sent with a session is expected by the host without a separate
+//! `register_session` call, and the response can be matched back to the
+//! protocol it answers.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use crate::types::{Protocol, Sproto};
+
+/// Source of the current time for session deadlines.
+///
+/// `Host` uses [`SystemClock`] unless another clock is given with
+/// `Host::with_clock`; tests can pass a closure returning a controlled
+/// `Instant`.
+pub trait Clock: Send + Sync {
+    /// The current time.
+    fn now(&self) -> Instant;
+}
+
+/// The system monotonic clock, `Instant::now()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<F: Fn() -> Instant + Send + Sync> Clock for F {
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+/// A snapshot of a session awaiting its response, from
+/// `Host::pending_sessions` or `Host::poll_expired`.
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    /// The session ID.
+    pub session: u64,
+    /// The protocol of the request, as in `DispatchResult::Response`;
+    /// `None` for sessions registered with `Host::register_session`.
+    pub protocol: Option<Protocol>,
+    /// When the session expires, if it was given a timeout.
+    pub deadline: Option<Instant>,
+}
+
+/// A session awaiting its response.
+#[derive(Debug)]
+pub(crate) struct PendingSession {
+    /// Remote schema and tag of the request's protocol; `None` for sessions
+    /// registered by hand with `Host::register_session`.
+    pub protocol: Option<(Arc<Sproto>, u16)>,
+    /// Expiry time for `Host::poll_expired`.
+    pub deadline: Option<Instant>,
+}
+
+impl PendingSession {
+    /// The protocol of the request, looked up in the remote schema.
+    pub fn protocol(&self) -> Option<&Protocol> {
+        self.protocol
+            .as_ref()
+            .and_then(|(remote, tag)| remote.get_protocol_by_tag(*tag))
+    }
+
+    fn info(&self, session: u64) -> SessionInfo {
+        SessionInfo {
+            session,
+            protocol: self.protocol().cloned(),
+            deadline: self.deadline,
+        }
+    }
+}
+
+/// A session table; clones share the same sessions.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SessionTable(Arc<Mutex<HashMap<u64, PendingSession>>>);
+
+impl SessionTable {
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, PendingSession>> {
+        self.0.lock().unwrap()
+    }
+
+    /// Open a session for a request; fails if it is already pending.
+    pub fn open(&self, session: u64, remote: Arc<Sproto>, tag: u16) -> bool {
+        let mut sessions = self.lock();
+        if sessions.contains_key(&session) {
+            return false;
+        }
+        sessions.insert(
+            session,
+            PendingSession {
+                protocol: Some((remote, tag)),
+                deadline: None,
+            },
+        );
+        true
+    }
+
+    /// Expect a response for `session`, keeping any protocol already
+    /// recorded. A `deadline` replaces the session's current one.
+    pub fn register(&self, session: u64, deadline: Option<Instant>) {
+        let mut sessions = self.lock();
+        let pending = sessions.entry(session).or_insert(PendingSession {
+            protocol: None,
+            deadline: None,
+        });
+        if deadline.is_some() {
+            pending.deadline = deadline;
+        }
+    }
+
+    /// Stop expecting `session`, returning its entry.
+    pub fn remove(&self, session: u64) -> Option<PendingSession> {
+        self.lock().remove(&session)
+    }
+
+    /// All pending sessions, ordered by session ID.
+    pub fn snapshot(&self) -> Vec<SessionInfo> {
+        let mut infos: Vec<SessionInfo> = self
+            .lock()
+            .iter()
+            .map(|(&session, pending)| pending.info(session))
+            .collect();
+        infos.sort_by_key(|info| info.session);
+        infos
+    }
+
+    /// Remove the sessions whose deadline is at or before `now`, ordered by
+    /// deadline.
+    pub fn expire(&self, now: Instant) -> Vec<SessionInfo> {
+        let mut sessions = self.lock();
+        let expired: Vec<u64> = sessions
+            .iter()
+            .filter(|(_, pending)| pending.deadline.is_some_and(|d| d <= now))
+            .map(|(&session, _)| session)
+            .collect();
+        let mut infos: Vec<SessionInfo> = expired
+            .into_iter()
+            .filter_map(|session| sessions.remove(&session).map(|p| p.info(session)))
+            .collect();
+        infos.sort_by_key(|info| (info.deadline, info.session));
+        infos
+    }
+}
@@ -0,0 +1,450 @@
+//! `Deserializer` implementations reading through `StructDecoder`.
+
+use ::serde::de::value::{BorrowedStrDeserializer, SeqDeserializer};
+use ::serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, Visitor};
+use ::serde::forward_to_deserialize_any;
+
+use crate::codec::{DecodeLimits, DecodedField, StructArrayIter, StructDecoder};
+use crate::error::DecodeError;
+use crate::types::{FieldType, Sproto};
+
+/// Deserialize a `T` from bytes encoded as the sproto type `type_name`.
+///
+/// Strings and binaries are borrowed from `data` where `T` allows it.
+pub fn from_slice<'de, T>(
+    sproto: &'de Sproto,
+    type_name: &str,
+    data: &'de [u8],
+) -> Result<T, DecodeError>
+where
+    T: Deserialize<'de>,
+{
+    from_slice_with_limits(sproto, type_name, data, DecodeLimits::default())
+}
+
+/// Deserialize a `T` like `from_slice`, enforcing `limits`.
+pub fn from_slice_with_limits<'de, T>(
+    sproto: &'de Sproto,
+    type_name: &str,
+    data: &'de [u8],
+    limits: DecodeLimits,
+) -> Result<T, DecodeError>
+where
+    T: Deserialize<'de>,
+{
+    let st = sproto
+        .get_type(type_name)
+        .ok_or_else(|| DecodeError::UnknownType(type_name.to_string()))?;
+    T::deserialize(StructDeserializer {
+        dec: StructDecoder::with_limits(sproto, st, data, limits)?,
+    })
+}
+
+// =============================================================================
+// Struct
+// =============================================================================
+
+/// Presents an encoded struct as a map from field name to value.
+struct StructDeserializer<'de> {
+    dec: StructDecoder<'de>,
+}
+
+impl<'de> de::Deserializer<'de> for StructDeserializer<'de> {
+    type Error = DecodeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_map(FieldAccess {
+            dec: self.dec,
+            value: None,
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf seq tuple tuple_struct map struct enum identifier
+    }
+}
+
+/// Yields the encoded fields of a struct; absent fields are never visited.
+struct FieldAccess<'de> {
+    dec: StructDecoder<'de>,
+    value: Option<DecodedField<'de>>,
+}
+
+impl<'de> de::MapAccess<'de> for FieldAccess<'de> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, DecodeError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.dec.next_field()? {
+            Some(f) => {
+                let name: &'de str = &f.field().name;
+                self.value = Some(f);
+                seed.deserialize(BorrowedStrDeserializer::new(name))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, DecodeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let f = self
+            .value
+            .take()
+            .ok_or_else(|| DecodeError::InvalidData("value requested before key".into()))?;
+        seed.deserialize(FieldDeserializer { f })
+    }
+}
+
+// =============================================================================
+// Fields
+// =============================================================================
+
+/// Deserializes the value of one decoded field.
+struct FieldDeserializer<'de> {
+    f: DecodedField<'de>,
+}
+
+impl<'de> FieldDeserializer<'de> {
+    fn scalar_array(&self) -> Result<Vec<Scalar<'de>>, DecodeError> {
+        let field = self.f.field();
+        Ok(match field.field_type {
+            FieldType::Integer if field.decimal_precision > 0 => self
+                .f
+                .as_integer_array()?
+                .into_iter()
+                .map(|v| Scalar::Decimal(v, field.decimal_precision))
+                .collect(),
+            FieldType::Integer => self
+                .f
+                .as_integer_array()?
+                .into_iter()
+                .map(Scalar::Integer)
+                .collect(),
+            FieldType::Boolean => self
+                .f
+                .as_bool_array()
+                .into_iter()
+                .map(Scalar::Boolean)
+                .collect(),
+            FieldType::Double => self
+                .f
+                .as_double_array()?
+                .into_iter()
+                .map(Scalar::Double)
+                .collect(),
+            FieldType::String => self
+                .f
+                .as_string_array()?
+                .into_iter()
+                .map(Scalar::Str)
+                .collect(),
+            FieldType::Binary => self
+                .f
+                .as_bytes_array()?
+                .into_iter()
+                .map(Scalar::Bytes)
+                .collect(),
+            FieldType::Struct(_) => unreachable!("struct arrays are not scalar"),
+        })
+    }
+}
+
+/// Forward to the scalar value for scalar fields, to `deserialize_any` otherwise.
+macro_rules! forward_to_scalar {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+                let field = self.f.field();
+                if !field.is_array && !matches!(field.field_type, FieldType::Struct(_)) {
+                    Scalar::from_field(&self.f)?.$method(visitor)
+                } else {
+                    self.deserialize_any(visitor)
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for FieldDeserializer<'de> {
+    type Error = DecodeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let field = self.f.field();
+        match (&field.field_type, field.is_array) {
+            (FieldType::Struct(_), false) => StructDeserializer {
+                dec: self.f.as_struct()?,
+            }
+            .deserialize_any(visitor),
+            (FieldType::Struct(_), true) if field.key_tag >= 0 => visitor.visit_map(EntryAccess {
+                iter: self.f.as_struct_iter()?,
+                key_tag: field.key_tag as u16,
+                is_map: field.is_map,
+                value: None,
+            }),
+            (FieldType::Struct(_), true) => visitor.visit_seq(StructSeqAccess {
+                iter: self.f.as_struct_iter()?,
+            }),
+            (_, true) => visitor.visit_seq(SeqDeserializer::new(self.scalar_array()?.into_iter())),
+            (_, false) => Scalar::from_field(&self.f)?.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_scalar! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let field = self.f.field();
+        match (&field.field_type, field.is_array) {
+            // Keyed arrays are maps by default but may also be read as a `Vec`.
+            (FieldType::Struct(_), true) => visitor.visit_seq(StructSeqAccess {
+                iter: self.f.as_struct_iter()?,
+            }),
+            (FieldType::Struct(_), false) | (_, true) => self.deserialize_any(visitor),
+            (_, false) => Scalar::from_field(&self.f)?.deserialize_seq(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i128 u128 char str string bytes byte_buf unit unit_struct tuple
+        tuple_struct map struct enum identifier
+    }
+}
+
+/// Yields the elements of a `*T` field.
+struct StructSeqAccess<'de> {
+    iter: StructArrayIter<'de>,
+}
+
+impl<'de> de::SeqAccess<'de> for StructSeqAccess<'de> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, DecodeError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(dec) => seed.deserialize(StructDeserializer { dec: dec? }).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The value half of a map entry, held between `next_key` and `next_value`.
+enum EntryValue<'de> {
+    /// A `*T(key)` element: the whole struct.
+    Struct(StructDecoder<'de>),
+    /// A `*T()` element: its non-key field.
+    Field(DecodedField<'de>),
+}
+
+/// Yields the elements of a `*T(key)` or `*T()` field as map entries.
+struct EntryAccess<'de> {
+    iter: StructArrayIter<'de>,
+    key_tag: u16,
+    is_map: bool,
+    value: Option<EntryValue<'de>>,
+}
+
+impl<'de> de::MapAccess<'de> for EntryAccess<'de> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, DecodeError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let dec = match self.iter.next() {
+            Some(dec) => dec?,
+            None => return Ok(None),
+        };
+        let mut fields = dec.clone();
+        let mut key = None;
+        let mut value = None;
+        while let Some(f) = fields.next_field()? {
+            if f.tag() == self.key_tag {
+                key = Some(f);
+            } else if value.is_none() {
+                value = Some(f);
+            }
+        }
+        let key = key.ok_or_else(|| {
+            DecodeError::InvalidData(format!("map element is missing key tag {}", self.key_tag))
+        })?;
+        self.value = Some(if self.is_map {
+            EntryValue::Field(value.ok_or_else(|| {
+                DecodeError::InvalidData(format!(
+                    "map element '{}' is missing its value",
+                    key.field().name
+                ))
+            })?)
+        } else {
+            EntryValue::Struct(dec)
+        });
+        seed.deserialize(FieldDeserializer { f: key }).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, DecodeError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(EntryValue::Struct(dec)) => seed.deserialize(StructDeserializer { dec }),
+            Some(EntryValue::Field(f)) => seed.deserialize(FieldDeserializer { f }),
+            None => Err(DecodeError::InvalidData(
+                "value requested before key".into(),
+            )),
+        }
+    }
+}
+
+// =============================================================================
+// Scalars
+// =============================================================================
+
+/// A decoded scalar value, either a whole field or an array element.
+enum Scalar<'de> {
+    Integer(i64),
+    /// Raw wire value and precision of an `integer(N)` field.
+    Decimal(i64, u32),
+    Boolean(bool),
+    Double(f64),
+    Str(&'de str),
+    Bytes(&'de [u8]),
+}
+
+impl<'de> Scalar<'de> {
+    fn from_field(f: &DecodedField<'de>) -> Result<Self, DecodeError> {
+        let field = f.field();
+        Ok(match field.field_type {
+            FieldType::Integer if field.decimal_precision > 0 => {
+                Scalar::Decimal(f.as_integer()?, field.decimal_precision)
+            }
+            FieldType::Integer => Scalar::Integer(f.as_integer()?),
+            FieldType::Boolean => Scalar::Boolean(f.as_bool()?),
+            FieldType::Double => Scalar::Double(f.as_double()?),
+            FieldType::String => Scalar::Str(f.as_string()?),
+            FieldType::Binary => Scalar::Bytes(f.as_bytes()),
+            FieldType::Struct(_) => unreachable!("struct fields are not scalar"),
+        })
+    }
+}
+
+/// Decimals deserialize as their raw wire value when an integer is requested.
+macro_rules! decimal_as_raw {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+                match self {
+                    Scalar::Decimal(raw, _) => visitor.visit_i64(raw),
+                    other => other.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Scalar<'de> {
+    type Error = DecodeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        match self {
+            Scalar::Integer(v) => visitor.visit_i64(v),
+            Scalar::Decimal(raw, precision) => visitor.visit_f64(raw as f64 / precision as f64),
+            Scalar::Boolean(v) => visitor.visit_bool(v),
+            Scalar::Double(v) => visitor.visit_f64(v),
+            Scalar::Str(v) => visitor.visit_borrowed_str(v),
+            Scalar::Bytes(v) => visitor.visit_borrowed_bytes(v),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        match self {
+            // `Vec<u8>` deserializes from a sequence of integers.
+            Scalar::Str(v) => visitor.visit_seq(SeqDeserializer::new(v.bytes())),
+            Scalar::Bytes(v) => visitor.visit_seq(SeqDeserializer::new(v.iter().copied())),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    decimal_as_raw! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+    }
+
+    forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string bytes byte_buf unit unit_struct
+        tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DecodeError> for Scalar<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
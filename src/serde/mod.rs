@@ -0,0 +1,65 @@
+//! Serde adapter over `StructEncoder` / `StructDecoder`.
+//!
+//! Rust structs are mapped onto sproto types by field name, so domain types
+//! only need `#[derive(Serialize, Deserialize)]` instead of hand-written tag
+//! plumbing:
+//!
+//! | Rust                     | sproto                                   |
+//! |--------------------------|------------------------------------------|
+//! | `i64`, `u32`, ...        | `integer`                                |
+//! | `f64`                    | `double`, or `integer(N)` (decimal)      |
+//! | `bool`                   | `boolean`                                |
+//! | `String`, `&str`         | `string`                                 |
+//! | `Vec<u8>`, `&[u8]`       | `binary`                                 |
+//! | nested struct            | `T`                                      |
+//! | `Vec<T>`                 | `*T`                                     |
+//! | `HashMap<K, T>`          | `*T(key)`, keyed by the element's key    |
+//! | `HashMap<K, V>`          | `*T()`, two-field key/value pairs        |
+//! | `Option<T>`              | absent field when `None`                 |
+//!
+//! Decimal fields accept an `f64` and scale it by the field precision; an
+//! integer value is written through unscaled. Absent fields deserialize as
+//! `None`; use `#[serde(default)]` for non-optional fields such as `Vec<T>`.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Person {
+//!     name: String,
+//!     id: i64,
+//!     email: Option<String>,
+//! }
+//!
+//! let schema = sproto::parser::parse(
+//!     ".Person { name 0 : string\n id 1 : integer\n email 2 : string }",
+//! )
+//! .unwrap();
+//!
+//! let alice = Person { name: "Alice".into(), id: 10000, email: None };
+//! let bytes = sproto::serde::to_vec(&schema, "Person", &alice).unwrap();
+//! let decoded: Person = sproto::serde::from_slice(&schema, "Person", &bytes).unwrap();
+//! assert_eq!(decoded, alice);
+//! ```
+
+mod de;
+mod ser;
+
+pub use de::{from_slice, from_slice_with_limits};
+pub use ser::to_vec;
+
+use std::fmt::Display;
+
+use crate::error::{DecodeError, EncodeError};
+
+impl ::serde::ser::Error for EncodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        EncodeError::Other(msg.to_string())
+    }
+}
+
+impl ::serde::de::Error for DecodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        DecodeError::InvalidData(msg.to_string())
+    }
+}
@@ -0,0 +1,1235 @@
+//! `Serializer` implementations writing through `StructEncoder`.
+
+use ::serde::ser::{self, Impossible, Serialize};
+
+use crate::codec::{StructArrayEncoder, StructEncoder};
+use crate::error::EncodeError;
+use crate::types::{Field, FieldType, Sproto};
+
+/// Serialize `value` as the sproto type `type_name`.
+pub fn to_vec<T>(sproto: &Sproto, type_name: &str, value: &T) -> Result<Vec<u8>, EncodeError>
+where
+    T: Serialize + ?Sized,
+{
+    let st = sproto
+        .get_type(type_name)
+        .ok_or_else(|| EncodeError::UnknownType(type_name.to_string()))?;
+    let mut buf = Vec::new();
+    let mut enc = StructEncoder::new(sproto, st, &mut buf);
+    value.serialize(StructSerializer {
+        enc: &mut enc,
+        key: None,
+    })?;
+    enc.finish();
+    Ok(buf)
+}
+
+fn mismatch(sproto: &Sproto, field: &Field, actual: &str) -> EncodeError {
+    EncodeError::TypeMismatch {
+        field: field.name.to_string(),
+        expected: field.schema_type_name(sproto),
+        actual: actual.to_string(),
+    }
+}
+
+/// Serialize one struct field, skipping it when the value is `None`.
+fn serialize_field<T>(
+    enc: &mut StructEncoder<'_>,
+    field: &Field,
+    value: &T,
+) -> Result<(), EncodeError>
+where
+    T: Serialize + ?Sized,
+{
+    if !matches!(field.field_type, FieldType::Struct(_)) {
+        return value.serialize(FieldSerializer { enc, field });
+    }
+    // The nested encoders reserve space as soon as they are opened, so an
+    // absent struct has to be detected before calling into them.
+    if !value.serialize(Presence)? {
+        return Ok(());
+    }
+    if field.is_array {
+        enc.encode_struct_array(field.tag, |arr| {
+            value.serialize(StructArraySerializer {
+                arr,
+                field,
+                key: None,
+            })
+        })
+    } else {
+        enc.encode_nested(field.tag, |sub| {
+            value.serialize(StructSerializer {
+                enc: sub,
+                key: None,
+            })
+        })
+    }
+}
+
+// =============================================================================
+// Struct
+// =============================================================================
+
+/// Serializes a Rust struct into the fields of the encoder's sproto type.
+struct StructSerializer<'a, 'b> {
+    enc: &'a mut StructEncoder<'b>,
+    /// For an element of a `*T(key)` map, the key its key field must equal.
+    key: Option<MapKey<'a>>,
+}
+
+/// The map key of a `*T(key)` element being serialized.
+struct MapKey<'a> {
+    field: &'a Field,
+    value: Element,
+    seen: bool,
+}
+
+impl MapKey<'_> {
+    fn mismatch(&self, what: &str) -> EncodeError {
+        EncodeError::Other(format!(
+            "map field '{}': element {} the map key",
+            self.field.name, what
+        ))
+    }
+
+    /// Check the element's key field `value` against the map key.
+    fn check<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.seen = true;
+        if !value.serialize(Presence)? {
+            return Err(self.mismatch("lacks"));
+        }
+        if value.serialize(ElementSerializer)? != self.value {
+            return Err(self.mismatch("key field does not match"));
+        }
+        Ok(())
+    }
+}
+
+impl StructSerializer<'_, '_> {
+    fn unsupported(&self, what: &str) -> EncodeError {
+        EncodeError::Other(format!(
+            "type '{}' must be serialized from a struct, got {}",
+            self.enc.sproto_type.name, what
+        ))
+    }
+}
+
+macro_rules! reject_primitives {
+    ($($method:ident($ty:ty) => $what:expr;)*) => {
+        $(
+            fn $method(self, _v: $ty) -> Result<Self::Ok, EncodeError> {
+                Err(self.unsupported($what))
+            }
+        )*
+    };
+}
+
+impl<'a, 'b> ser::Serializer for StructSerializer<'a, 'b> {
+    type Ok = ();
+    type Error = EncodeError;
+    type SerializeSeq = Impossible<(), EncodeError>;
+    type SerializeTuple = Impossible<(), EncodeError>;
+    type SerializeTupleStruct = Impossible<(), EncodeError>;
+    type SerializeTupleVariant = Impossible<(), EncodeError>;
+    type SerializeMap = Impossible<(), EncodeError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), EncodeError>;
+
+    reject_primitives! {
+        serialize_bool(bool) => "boolean";
+        serialize_i8(i8) => "integer";
+        serialize_i16(i16) => "integer";
+        serialize_i32(i32) => "integer";
+        serialize_i64(i64) => "integer";
+        serialize_u8(u8) => "integer";
+        serialize_u16(u16) => "integer";
+        serialize_u32(u32) => "integer";
+        serialize_u64(u64) => "integer";
+        serialize_f32(f32) => "double";
+        serialize_f64(f64) => "double";
+        serialize_char(char) => "string";
+        serialize_str(&str) => "string";
+        serialize_bytes(&[u8]) => "binary";
+    }
+
+    fn serialize_none(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), EncodeError> {
+        Err(self.unsupported("enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), EncodeError> {
+        Err(self.unsupported("enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, EncodeError> {
+        Err(self.unsupported("sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, EncodeError> {
+        Err(self.unsupported("tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, EncodeError> {
+        Err(self.unsupported("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, EncodeError> {
+        Err(self.unsupported("enum"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, EncodeError> {
+        Err(self.unsupported("map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, EncodeError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, EncodeError> {
+        Err(self.unsupported("enum"))
+    }
+}
+
+impl ser::SerializeStruct for StructSerializer<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), EncodeError> {
+        let st = self.enc.sproto_type;
+        let (_, field) = st.field_index_by_name(key).ok_or_else(|| {
+            EncodeError::Other(format!("unknown field '{}' in type '{}'", key, st.name))
+        })?;
+        if let Some(key) = &mut self.key {
+            if field.tag as i32 == key.field.key_tag {
+                key.check(value)?;
+            }
+        }
+        serialize_field(self.enc, field, value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        match self.key {
+            Some(key) if !key.seen => Err(key.mismatch("lacks")),
+            _ => Ok(()),
+        }
+    }
+}
+
+// =============================================================================
+// Struct arrays and maps
+// =============================================================================
+
+/// Serializes a sequence or map into a `*T`, `*T(key)` or `*T()` field.
+struct StructArraySerializer<'a, 'b> {
+    arr: &'a mut StructArrayEncoder<'b>,
+    field: &'a Field,
+    /// The `*T(key)` map key passed to `serialize_key`, for `serialize_value`.
+    key: Option<Element>,
+}
+
+impl StructArraySerializer<'_, '_> {
+    fn unsupported(&self, what: &str) -> EncodeError {
+        EncodeError::TypeMismatch {
+            field: self.field.name.to_string(),
+            expected: "struct array".into(),
+            actual: what.to_string(),
+        }
+    }
+}
+
+impl<'a, 'b> ser::Serializer for StructArraySerializer<'a, 'b> {
+    type Ok = ();
+    type Error = EncodeError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Impossible<(), EncodeError>;
+    type SerializeTupleVariant = Impossible<(), EncodeError>;
+    type SerializeMap = Self;
+    type SerializeStruct = Impossible<(), EncodeError>;
+    type SerializeStructVariant = Impossible<(), EncodeError>;
+
+    reject_primitives! {
+        serialize_bool(bool) => "boolean";
+        serialize_i8(i8) => "integer";
+        serialize_i16(i16) => "integer";
+        serialize_i32(i32) => "integer";
+        serialize_i64(i64) => "integer";
+        serialize_u8(u8) => "integer";
+        serialize_u16(u16) => "integer";
+        serialize_u32(u32) => "integer";
+        serialize_u64(u64) => "integer";
+        serialize_f32(f32) => "double";
+        serialize_f64(f64) => "double";
+        serialize_char(char) => "string";
+        serialize_str(&str) => "string";
+        serialize_bytes(&[u8]) => "binary";
+    }
+
+    fn serialize_none(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EncodeError> {
+        Err(self.unsupported("unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), EncodeError> {
+        Err(self.unsupported("enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), EncodeError> {
+        Err(self.unsupported("enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, EncodeError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, EncodeError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, EncodeError> {
+        Err(self.unsupported("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, EncodeError> {
+        Err(self.unsupported("enum"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, EncodeError> {
+        if self.field.key_tag < 0 {
+            return Err(self.unsupported("map (field has no key)"));
+        }
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, EncodeError> {
+        Err(self.unsupported("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, EncodeError> {
+        Err(self.unsupported("enum"))
+    }
+}
+
+impl ser::SerializeSeq for StructArraySerializer<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.arr
+            .encode_element(|enc| value.serialize(StructSerializer { enc, key: None }))
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for StructArraySerializer<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for StructArraySerializer<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), EncodeError> {
+        if self.field.is_map {
+            return Err(EncodeError::Other(format!(
+                "map field '{}' must be serialized with serialize_entry",
+                self.field.name
+            )));
+        }
+        // `*T(key)` elements carry their own key; it is checked against the
+        // map key when the element is serialized.
+        self.key = Some(key.serialize(ElementSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        let key = self.key.take().ok_or_else(|| {
+            EncodeError::Other(format!(
+                "map field '{}': value serialized before its key",
+                self.field.name
+            ))
+        })?;
+        let key = MapKey {
+            field: self.field,
+            value: key,
+            seen: false,
+        };
+        self.arr.encode_element(|enc| {
+            value.serialize(StructSerializer {
+                enc,
+                key: Some(key),
+            })
+        })
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), EncodeError>
+    where
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
+    {
+        if !self.field.is_map {
+            ser::SerializeMap::serialize_key(self, key)?;
+            return ser::SerializeMap::serialize_value(self, value);
+        }
+        let key_tag = self.field.key_tag as u16;
+        self.arr.encode_element(|enc| {
+            let st = enc.sproto_type;
+            let (key_field, value_field) = match st.fields.as_slice() {
+                [a, b] if a.tag == key_tag => (a, b),
+                [a, b] => (b, a),
+                _ => {
+                    return Err(EncodeError::Other(format!(
+                        "map type '{}' must have exactly two fields",
+                        st.name
+                    )))
+                }
+            };
+            serialize_field(enc, key_field, key)?;
+            serialize_field(enc, value_field, value)
+        })
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Scalar fields
+// =============================================================================
+
+/// Serializes a scalar or scalar array value into a single field.
+struct FieldSerializer<'a, 'b> {
+    enc: &'a mut StructEncoder<'b>,
+    field: &'a Field,
+}
+
+impl FieldSerializer<'_, '_> {
+    fn mismatch(&self, actual: &str) -> EncodeError {
+        mismatch(self.enc.sproto, self.field, actual)
+    }
+
+    fn is_scalar(&self, ty: FieldType) -> bool {
+        !self.field.is_array && self.field.field_type == ty
+    }
+}
+
+impl<'a, 'b> ser::Serializer for FieldSerializer<'a, 'b> {
+    type Ok = ();
+    type Error = EncodeError;
+    type SerializeSeq = ScalarArraySerializer<'a, 'b>;
+    type SerializeTuple = ScalarArraySerializer<'a, 'b>;
+    type SerializeTupleStruct = Impossible<(), EncodeError>;
+    type SerializeTupleVariant = Impossible<(), EncodeError>;
+    type SerializeMap = Impossible<(), EncodeError>;
+    type SerializeStruct = Impossible<(), EncodeError>;
+    type SerializeStructVariant = Impossible<(), EncodeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), EncodeError> {
+        if !self.is_scalar(FieldType::Boolean) {
+            return Err(self.mismatch("boolean"));
+        }
+        self.enc.set_bool(self.field.tag, v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), EncodeError> {
+        if !self.is_scalar(FieldType::Integer) {
+            return Err(self.mismatch("integer"));
+        }
+        self.enc.set_integer(self.field.tag, v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), EncodeError> {
+        let v = i64::try_from(v).map_err(|_| {
+            EncodeError::Other(format!(
+                "integer {} out of range for field '{}'",
+                v, self.field.name
+            ))
+        })?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), EncodeError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), EncodeError> {
+        if self.is_scalar(FieldType::Double) {
+            self.enc.set_double(self.field.tag, v)
+        } else if self.is_scalar(FieldType::Integer) && self.field.decimal_precision > 0 {
+            self.enc.set_decimal(self.field.tag, v)
+        } else {
+            Err(self.mismatch("double"))
+        }
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), EncodeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), EncodeError> {
+        if self.is_scalar(FieldType::String) || self.is_scalar(FieldType::Binary) {
+            self.enc.set_string(self.field.tag, v)
+        } else {
+            Err(self.mismatch("string"))
+        }
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), EncodeError> {
+        if self.is_scalar(FieldType::String) || self.is_scalar(FieldType::Binary) {
+            self.enc.set_bytes(self.field.tag, v)
+        } else {
+            Err(self.mismatch("binary"))
+        }
+    }
+
+    fn serialize_none(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EncodeError> {
+        Err(self.mismatch("unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), EncodeError> {
+        Err(self.mismatch("enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), EncodeError> {
+        Err(self.mismatch("enum"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, EncodeError> {
+        let cap = len.unwrap_or(0);
+        let values = match (&self.field.field_type, self.field.is_array) {
+            (FieldType::Integer, true) => ScalarArray::Integer(Vec::with_capacity(cap)),
+            (FieldType::Boolean, true) => ScalarArray::Boolean(Vec::with_capacity(cap)),
+            (FieldType::Double, true) => ScalarArray::Double(Vec::with_capacity(cap)),
+            (FieldType::String | FieldType::Binary, true) => {
+                ScalarArray::Bytes(Vec::with_capacity(cap))
+            }
+            // `Vec<u8>` serializes as a sequence of integers.
+            (FieldType::String | FieldType::Binary, false) => {
+                ScalarArray::Raw(Vec::with_capacity(cap))
+            }
+            _ => return Err(self.mismatch("sequence")),
+        };
+        Ok(ScalarArraySerializer {
+            enc: self.enc,
+            field: self.field,
+            values,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, EncodeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, EncodeError> {
+        Err(self.mismatch("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, EncodeError> {
+        Err(self.mismatch("enum"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, EncodeError> {
+        Err(self.mismatch("map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, EncodeError> {
+        Err(self.mismatch("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, EncodeError> {
+        Err(self.mismatch("enum"))
+    }
+}
+
+/// Elements collected for a scalar array, written on `end()`.
+enum ScalarArray {
+    Integer(Vec<i64>),
+    Boolean(Vec<bool>),
+    Double(Vec<f64>),
+    Bytes(Vec<Vec<u8>>),
+    /// Bytes of a single `string` / `binary` field.
+    Raw(Vec<u8>),
+}
+
+/// Collects sequence elements and writes them with the matching `set_*_array`.
+struct ScalarArraySerializer<'a, 'b> {
+    enc: &'a mut StructEncoder<'b>,
+    field: &'a Field,
+    values: ScalarArray,
+}
+
+impl ser::SerializeSeq for ScalarArraySerializer<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        let elem = value.serialize(ElementSerializer)?;
+        match (&mut self.values, elem) {
+            (ScalarArray::Integer(v), Element::Integer(n)) => v.push(n),
+            (ScalarArray::Integer(v), Element::Double(d)) if self.field.decimal_precision > 0 => {
+                v.push(self.field.scale_decimal(d))
+            }
+            (ScalarArray::Boolean(v), Element::Boolean(b)) => v.push(b),
+            (ScalarArray::Double(v), Element::Double(d)) => v.push(d),
+            (ScalarArray::Bytes(v), Element::Bytes(b)) => v.push(b),
+            (ScalarArray::Raw(v), Element::Integer(n)) if (0..=255).contains(&n) => v.push(n as u8),
+            (_, elem) => return Err(mismatch(self.enc.sproto, self.field, elem.describe())),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        let tag = self.field.tag;
+        match &self.values {
+            ScalarArray::Integer(v) => self.enc.set_integer_array(tag, v),
+            ScalarArray::Boolean(v) => self.enc.set_bool_array(tag, v),
+            ScalarArray::Double(v) => self.enc.set_double_array(tag, v),
+            ScalarArray::Bytes(v) => self.enc.set_bytes_array(tag, v),
+            ScalarArray::Raw(v) => self.enc.set_bytes(tag, v),
+        }
+    }
+}
+
+impl ser::SerializeTuple for ScalarArraySerializer<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// A single scalar array element.
+#[derive(PartialEq)]
+enum Element {
+    Integer(i64),
+    Boolean(bool),
+    Double(f64),
+    Bytes(Vec<u8>),
+}
+
+impl Element {
+    fn describe(&self) -> &'static str {
+        match self {
+            Element::Integer(_) => "integer",
+            Element::Boolean(_) => "boolean",
+            Element::Double(_) => "double",
+            Element::Bytes(_) => "string",
+        }
+    }
+}
+
+/// Serializes a scalar array element into an `Element`.
+struct ElementSerializer;
+
+impl ElementSerializer {
+    fn unsupported(what: &str) -> EncodeError {
+        EncodeError::Other(format!("unsupported array element: {}", what))
+    }
+}
+
+impl ser::Serializer for ElementSerializer {
+    type Ok = Element;
+    type Error = EncodeError;
+    type SerializeSeq = Impossible<Element, EncodeError>;
+    type SerializeTuple = Impossible<Element, EncodeError>;
+    type SerializeTupleStruct = Impossible<Element, EncodeError>;
+    type SerializeTupleVariant = Impossible<Element, EncodeError>;
+    type SerializeMap = Impossible<Element, EncodeError>;
+    type SerializeStruct = Impossible<Element, EncodeError>;
+    type SerializeStructVariant = Impossible<Element, EncodeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Element, EncodeError> {
+        Ok(Element::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Element, EncodeError> {
+        Ok(Element::Integer(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Element, EncodeError> {
+        Ok(Element::Integer(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Element, EncodeError> {
+        Ok(Element::Integer(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Element, EncodeError> {
+        Ok(Element::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Element, EncodeError> {
+        Ok(Element::Integer(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Element, EncodeError> {
+        Ok(Element::Integer(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Element, EncodeError> {
+        Ok(Element::Integer(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Element, EncodeError> {
+        i64::try_from(v)
+            .map(Element::Integer)
+            .map_err(|_| EncodeError::Other(format!("integer {} out of range", v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Element, EncodeError> {
+        Ok(Element::Double(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Element, EncodeError> {
+        Ok(Element::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Element, EncodeError> {
+        Ok(Element::Bytes(v.to_string().into_bytes()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Element, EncodeError> {
+        Ok(Element::Bytes(v.as_bytes().to_vec()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Element, EncodeError> {
+        Ok(Element::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Element, EncodeError> {
+        Err(Self::unsupported("None"))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Element, EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Element, EncodeError> {
+        Err(Self::unsupported("unit"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Element, EncodeError> {
+        Err(Self::unsupported("unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<Element, EncodeError> {
+        Err(Self::unsupported("enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Element, EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Element, EncodeError> {
+        Err(Self::unsupported("enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, EncodeError> {
+        Err(Self::unsupported("nested sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, EncodeError> {
+        Err(Self::unsupported("tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, EncodeError> {
+        Err(Self::unsupported("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, EncodeError> {
+        Err(Self::unsupported("enum"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, EncodeError> {
+        Err(Self::unsupported("map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, EncodeError> {
+        Err(Self::unsupported("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, EncodeError> {
+        Err(Self::unsupported("enum"))
+    }
+}
+
+// =============================================================================
+// Presence probe
+// =============================================================================
+
+/// Reports whether a value would produce a field, i.e. is not `None`.
+///
+/// Compound values are not walked; they are always present.
+struct Presence;
+
+impl ser::Serializer for Presence {
+    type Ok = bool;
+    type Error = EncodeError;
+    type SerializeSeq = Present;
+    type SerializeTuple = Present;
+    type SerializeTupleStruct = Present;
+    type SerializeTupleVariant = Present;
+    type SerializeMap = Present;
+    type SerializeStruct = Present;
+    type SerializeStructVariant = Present;
+
+    fn serialize_bool(self, _v: bool) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_char(self, _v: char) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_none(self) -> Result<bool, EncodeError> {
+        Ok(false)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<bool, EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<bool, EncodeError> {
+        Ok(false)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<bool, EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Present, EncodeError> {
+        Ok(Present)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Present, EncodeError> {
+        Ok(Present)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Present, EncodeError> {
+        Ok(Present)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Present, EncodeError> {
+        Ok(Present)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Present, EncodeError> {
+        Ok(Present)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Present, EncodeError> {
+        Ok(Present)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Present, EncodeError> {
+        Ok(Present)
+    }
+}
+
+/// Compound state for `Presence`; discards its contents.
+struct Present;
+
+macro_rules! impl_present {
+    ($($trait:ident::$method:ident),*) => {
+        $(
+            impl ser::$trait for Present {
+                type Ok = bool;
+                type Error = EncodeError;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, _value: &T) -> Result<(), EncodeError> {
+                    Ok(())
+                }
+
+                fn end(self) -> Result<bool, EncodeError> {
+                    Ok(true)
+                }
+            }
+        )*
+    };
+}
+
+impl_present!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+impl ser::SerializeMap for Present {
+    type Ok = bool;
+    type Error = EncodeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, _key: &T) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, _value: &T) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+}
+
+impl ser::SerializeStruct for Present {
+    type Ok = bool;
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        _value: &T,
+    ) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+}
+
+impl ser::SerializeStructVariant for Present {
+    type Ok = bool;
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        _value: &T,
+    ) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<bool, EncodeError> {
+        Ok(true)
+    }
+}
@@ -1,5 +1,7 @@
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::error::SchemaError;
 
 /// The type of a field in a sproto schema.
 #[derive(Debug, Clone, PartialEq)]
@@ -16,8 +18,8 @@ pub enum FieldType {
 /// A field definition within a sproto type.
 #[derive(Debug, Clone)]
 pub struct Field {
-    /// Field name (Rc<str> for cheap cloning as HashMap key).
-    pub name: Rc<str>,
+    /// Field name (Arc<str> for cheap cloning as HashMap key).
+    pub name: Arc<str>,
     /// Field tag (unique within the type, ascending order).
     pub tag: u16,
     /// The base type of this field.
@@ -73,6 +75,38 @@ impl Field {
             decimal_precision: precision,
         }
     }
+
+    /// Scale a fixed-point value to the wire integer of an `integer(N)` field.
+    ///
+    /// Rounds half away from zero, like the C implementation's `round()`.
+    pub fn scale_decimal(&self, value: f64) -> i64 {
+        (value * self.decimal_precision as f64).round() as i64
+    }
+
+    /// Convert the wire integer of an `integer(N)` field back to its value.
+    pub fn unscale_decimal(&self, raw: i64) -> f64 {
+        raw as f64 / self.decimal_precision as f64
+    }
+
+    /// Type of this field as written in a `.sproto` file, e.g. `*Person`.
+    pub(crate) fn schema_type_name(&self, sproto: &Sproto) -> String {
+        let base = match &self.field_type {
+            FieldType::Integer if self.decimal_precision > 0 => {
+                format!("integer({})", self.decimal_precision.ilog10())
+            }
+            FieldType::Integer => "integer".to_string(),
+            FieldType::Boolean => "boolean".to_string(),
+            FieldType::String => "string".to_string(),
+            FieldType::Binary => "binary".to_string(),
+            FieldType::Double => "double".to_string(),
+            FieldType::Struct(idx) => sproto.types_list[*idx].name.clone(),
+        };
+        if self.is_array {
+            format!("*{}", base)
+        } else {
+            base
+        }
+    }
 }
 
 /// A user-defined type (struct/message) in the sproto schema.
@@ -83,7 +117,7 @@ pub struct SprotoType {
     /// Fields sorted by tag in ascending order.
     pub fields: Vec<Field>,
     /// Map from field name to index in `fields` for O(1) lookup.
-    pub field_by_name: HashMap<Rc<str>, usize>,
+    pub field_by_name: HashMap<Arc<str>, usize>,
     /// If tags are contiguous starting from base_tag, this is the base tag.
     /// -1 means tags are not contiguous (use binary search for lookup).
     pub base_tag: i32,
@@ -103,10 +137,20 @@ pub struct Protocol {
     pub request: Option<usize>,
     /// Index into `Sproto.types_list` for the response type, if any.
     pub response: Option<usize>,
-    /// If true, response is explicitly nil (no response expected).
+    /// If true, the response is declared `nil`: the peer acknowledges the
+    /// request with an empty response.
     pub confirm: bool,
 }
 
+impl Protocol {
+    /// Whether requests are answered, with a response body or with an empty
+    /// acknowledgement for `response nil`. Requests of other protocols are
+    /// one-way and must not carry a session.
+    pub fn expects_response(&self) -> bool {
+        self.response.is_some() || self.confirm
+    }
+}
+
 /// The top-level sproto schema container, holding all types and protocols.
 #[derive(Debug, Clone)]
 pub struct Sproto {
@@ -126,10 +170,10 @@ impl SprotoType {
     /// Create a new SprotoType. Fields must be sorted by tag.
     /// Automatically computes `base_tag` and `maxn` from the fields.
     pub fn new(name: String, fields: Vec<Field>) -> Self {
-        let field_by_name: HashMap<Rc<str>, usize> = fields
+        let field_by_name: HashMap<Arc<str>, usize> = fields
             .iter()
             .enumerate()
-            .map(|(i, f)| (Rc::clone(&f.name), i))
+            .map(|(i, f)| (Arc::clone(&f.name), i))
             .collect();
         let (base_tag, maxn) = compute_base_tag_and_maxn(&fields);
         SprotoType {
@@ -246,6 +290,40 @@ impl Sproto {
         idx
     }
 
+    /// Replace the fields of an existing type. Fields must be sorted by tag.
+    ///
+    /// Lets a type be added first as a placeholder, so that its own fields
+    /// (or those of types it references) can point back at it.
+    pub fn set_type_fields(&mut self, idx: usize, fields: Vec<Field>) {
+        let st = &mut self.types_list[idx];
+        *st = SprotoType::new(std::mem::take(&mut st.name), fields);
+    }
+
+    /// Check `fields` against those of an existing type, for a type that is
+    /// registered under a name already present.
+    ///
+    /// Each of `fields` needs a field with the same tag, type, array flag and
+    /// decimal precision. Names, map keys and extra existing fields are not
+    /// compared: none of them changes how `fields` encode.
+    pub fn check_type_fields(&self, idx: usize, fields: &[Field]) -> Result<(), SchemaError> {
+        let st = &self.types_list[idx];
+        for field in fields {
+            let matches = st.find_field_by_tag(field.tag).is_some_and(|existing| {
+                existing.field_type == field.field_type
+                    && existing.is_array == field.is_array
+                    && existing.decimal_precision == field.decimal_precision
+            });
+            if !matches {
+                return Err(SchemaError::FieldMismatch {
+                    type_name: st.name.clone(),
+                    field: field.name.to_string(),
+                    tag: field.tag,
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Add a protocol to the schema.
     /// Returns the index of the newly added protocol.
     pub fn add_protocol(
@@ -268,6 +346,192 @@ impl Sproto {
         });
         idx
     }
+
+    /// Add a type like `add_type`, first checking it against the types
+    /// added so far.
+    ///
+    /// Fails on a duplicate name, unsorted or duplicate tags, duplicate
+    /// field names, struct references past the new type, and invalid map
+    /// keys; the schema is left unchanged on error.
+    pub fn try_add_type(&mut self, name: &str, fields: Vec<Field>) -> Result<usize, SchemaError> {
+        if self.types_by_name.contains_key(name) {
+            return Err(SchemaError::DuplicateType(name.to_string()));
+        }
+        let idx = self.add_type(name, fields);
+        let mut errors = Vec::new();
+        self.check_type(&self.types_list[idx], &mut errors);
+        if let Some(err) = errors.into_iter().next() {
+            self.types_list.pop();
+            self.types_by_name.remove(name);
+            return Err(err);
+        }
+        Ok(idx)
+    }
+
+    /// Add a protocol like `add_protocol`, first checking that its name and
+    /// tag are unused and that its request/response types exist.
+    pub fn try_add_protocol(
+        &mut self,
+        name: &str,
+        tag: u16,
+        request: Option<usize>,
+        response: Option<usize>,
+        confirm: bool,
+    ) -> Result<usize, SchemaError> {
+        if self.protocols_by_name.contains_key(name) {
+            return Err(SchemaError::DuplicateProtocol(name.to_string()));
+        }
+        if let Some(other) = self.get_protocol_by_tag(tag) {
+            return Err(SchemaError::DuplicateProtocolTag {
+                tag,
+                first: other.name.clone(),
+                second: name.to_string(),
+            });
+        }
+        if let Some(index) = [request, response]
+            .into_iter()
+            .flatten()
+            .find(|&i| i >= self.types_list.len())
+        {
+            return Err(SchemaError::DanglingProtocolType {
+                protocol: name.to_string(),
+                index,
+                count: self.types_list.len(),
+            });
+        }
+        Ok(self.add_protocol(name, tag, request, response, confirm))
+    }
+
+    /// Check the whole schema, returning every problem found.
+    ///
+    /// Schemas from `parser::parse` are valid by construction, and
+    /// `SchemaBuilder::build` and `binary_schema::load_binary` run this check
+    /// before returning, reporting only the first problem; it is mainly for schemas built with `add_type` /
+    /// `add_protocol`, which accept anything. An invalid schema can make
+    /// field lookups miss (unsorted tags) or panic (dangling type indices).
+    pub fn validate(&self) -> Result<(), Vec<SchemaError>> {
+        let mut errors = Vec::new();
+
+        let mut names = HashSet::new();
+        for st in &self.types_list {
+            if !names.insert(st.name.as_str()) {
+                errors.push(SchemaError::DuplicateType(st.name.clone()));
+            }
+            self.check_type(st, &mut errors);
+        }
+
+        let mut names = HashSet::new();
+        let mut tags: HashMap<u16, &str> = HashMap::new();
+        for proto in &self.protocols {
+            if !names.insert(proto.name.as_str()) {
+                errors.push(SchemaError::DuplicateProtocol(proto.name.clone()));
+            }
+            if let Some(first) = tags.insert(proto.tag, &proto.name) {
+                errors.push(SchemaError::DuplicateProtocolTag {
+                    tag: proto.tag,
+                    first: first.to_string(),
+                    second: proto.name.clone(),
+                });
+            }
+            for index in [proto.request, proto.response].into_iter().flatten() {
+                if index >= self.types_list.len() {
+                    errors.push(SchemaError::DanglingProtocolType {
+                        protocol: proto.name.clone(),
+                        index,
+                        count: self.types_list.len(),
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check the fields of one type against the rest of the schema.
+    fn check_type(&self, st: &SprotoType, errors: &mut Vec<SchemaError>) {
+        let type_name = || st.name.clone();
+
+        for pair in st.fields.windows(2) {
+            let tag = pair[1].tag;
+            if tag == pair[0].tag {
+                errors.push(SchemaError::DuplicateTag {
+                    type_name: type_name(),
+                    tag,
+                });
+            } else if tag < pair[0].tag {
+                errors.push(SchemaError::UnsortedTags {
+                    type_name: type_name(),
+                    tag,
+                });
+            }
+        }
+
+        let mut names = HashSet::new();
+        for field in &st.fields {
+            if !names.insert(&*field.name) {
+                errors.push(SchemaError::DuplicateFieldName {
+                    type_name: type_name(),
+                    field: field.name.to_string(),
+                });
+            }
+
+            let sub_type = match field.field_type {
+                FieldType::Struct(index) => match self.types_list.get(index) {
+                    Some(sub_type) => Some(sub_type),
+                    None => {
+                        errors.push(SchemaError::DanglingTypeRef {
+                            type_name: type_name(),
+                            field: field.name.to_string(),
+                            index,
+                            count: self.types_list.len(),
+                        });
+                        continue;
+                    }
+                },
+                _ => None,
+            };
+
+            if field.key_tag < 0 && !field.is_map {
+                continue;
+            }
+            let Some(sub_type) = sub_type.filter(|_| field.is_array) else {
+                errors.push(SchemaError::KeyOnNonStructArray {
+                    type_name: type_name(),
+                    field: field.name.to_string(),
+                });
+                continue;
+            };
+            if field.is_map && sub_type.fields.len() != 2 {
+                errors.push(SchemaError::PairMapArity {
+                    type_name: type_name(),
+                    field: field.name.to_string(),
+                    sub_type: sub_type.name.clone(),
+                });
+            }
+            let key = u16::try_from(field.key_tag)
+                .ok()
+                .and_then(|tag| sub_type.fields.iter().find(|f| f.tag == tag));
+            match key {
+                None => errors.push(SchemaError::MissingKey {
+                    type_name: type_name(),
+                    field: field.name.to_string(),
+                    key_tag: field.key_tag,
+                }),
+                Some(key) if key.is_array || matches!(key.field_type, FieldType::Struct(_)) => {
+                    errors.push(SchemaError::NonScalarKey {
+                        type_name: type_name(),
+                        field: field.name.to_string(),
+                        key: key.name.to_string(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+    }
 }
 
 impl Default for Sproto {
@@ -304,3 +568,267 @@ pub(crate) fn compute_base_tag_and_maxn(fields: &[Field]) -> (i32, usize) {
 
     (base_tag, maxn)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_schema_types_are_send_sync() {
+        assert_send_sync::<Field>();
+        assert_send_sync::<SprotoType>();
+        assert_send_sync::<Protocol>();
+        assert_send_sync::<Sproto>();
+        assert_send_sync::<Arc<Sproto>>();
+    }
+
+    #[test]
+    fn test_shared_schema_across_threads() {
+        let mut sproto = Sproto::new();
+        sproto.add_type(
+            "Person",
+            vec![
+                Field::new("name", 0, FieldType::String),
+                Field::new("age", 1, FieldType::Integer),
+            ],
+        );
+        let sproto = Arc::new(sproto);
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let sproto = Arc::clone(&sproto);
+                std::thread::spawn(move || {
+                    let st = sproto.get_type("Person").unwrap();
+                    let mut buf = Vec::new();
+                    let mut enc = crate::codec::StructEncoder::new(&sproto, st, &mut buf);
+                    enc.set_integer(1, i).unwrap();
+                    enc.finish();
+                    buf
+                })
+            })
+            .collect();
+        for (i, h) in handles.into_iter().enumerate() {
+            let buf = h.join().unwrap();
+            let st = sproto.get_type("Person").unwrap();
+            let mut dec = crate::codec::StructDecoder::new(&sproto, st, &buf).unwrap();
+            let f = dec.next_field().unwrap().unwrap();
+            assert_eq!(f.as_integer().unwrap(), i as i64);
+        }
+    }
+
+    /// A valid schema with a self-recursive type and both kinds of map.
+    fn valid_schema() -> Sproto {
+        let mut s = Sproto::new();
+        let phone = s.add_type(
+            "Phone",
+            vec![
+                Field::new("number", 0, FieldType::String),
+                Field::new("type", 1, FieldType::Integer),
+            ],
+        );
+        let person = s.add_type("Person", Vec::new());
+        let mut by_number = Field::array("phones", 1, FieldType::Struct(phone));
+        by_number.key_tag = 0;
+        let mut pairs = Field::array("pairs", 2, FieldType::Struct(phone));
+        pairs.key_tag = 0;
+        pairs.is_map = true;
+        s.set_type_fields(
+            person,
+            vec![
+                Field::new("name", 0, FieldType::String),
+                by_number,
+                pairs,
+                Field::array("children", 5, FieldType::Struct(person)),
+            ],
+        );
+        s.add_protocol("get", 1, Some(person), Some(phone), false);
+        s.add_protocol("bye", 2, None, None, true);
+        s
+    }
+
+    #[test]
+    fn test_validate_accepts_valid_schema() {
+        assert_eq!(valid_schema().validate(), Ok(()));
+        assert_eq!(Sproto::new().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_type_errors() {
+        let mut s = Sproto::new();
+        s.add_type("A", vec![Field::new("x", 0, FieldType::Integer)]);
+        s.add_type("A", Vec::new());
+        s.add_type(
+            "B",
+            vec![
+                Field::new("x", 2, FieldType::Integer),
+                Field::new("y", 1, FieldType::Integer),
+                Field::new("z", 1, FieldType::Integer),
+                Field::new("x", 3, FieldType::Struct(9)),
+            ],
+        );
+        assert_eq!(
+            s.validate().unwrap_err(),
+            vec![
+                SchemaError::DuplicateType("A".into()),
+                SchemaError::UnsortedTags {
+                    type_name: "B".into(),
+                    tag: 1
+                },
+                SchemaError::DuplicateTag {
+                    type_name: "B".into(),
+                    tag: 1
+                },
+                SchemaError::DuplicateFieldName {
+                    type_name: "B".into(),
+                    field: "x".into()
+                },
+                SchemaError::DanglingTypeRef {
+                    type_name: "B".into(),
+                    field: "x".into(),
+                    index: 9,
+                    count: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_map_keys() {
+        let mut s = valid_schema();
+        let phone = s.get_type_index("Phone").unwrap();
+        let person = s.get_type_index("Person").unwrap();
+        let mut missing = Field::array("m", 0, FieldType::Struct(phone));
+        missing.key_tag = 7;
+        let mut scalar = Field::new("s", 1, FieldType::Struct(phone));
+        scalar.key_tag = 0;
+        let mut by_array = Field::array("c", 2, FieldType::Struct(person));
+        by_array.key_tag = 5;
+        let mut no_key = Field::array("p", 3, FieldType::Struct(phone));
+        no_key.is_map = true;
+        let mut wide_pair = Field::array("w", 4, FieldType::Struct(person));
+        wide_pair.key_tag = 0;
+        wide_pair.is_map = true;
+        s.add_type("Maps", vec![missing, scalar, by_array, no_key, wide_pair]);
+
+        let field = |name: &str| ("Maps".to_string(), name.to_string());
+        let errors: Vec<_> = s
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| match e {
+                SchemaError::MissingKey {
+                    type_name,
+                    field: f,
+                    key_tag,
+                } => ((type_name, f), format!("missing {}", key_tag)),
+                SchemaError::KeyOnNonStructArray {
+                    type_name,
+                    field: f,
+                } => ((type_name, f), "not a struct array".to_string()),
+                SchemaError::NonScalarKey {
+                    type_name,
+                    field: f,
+                    key,
+                } => ((type_name, f), format!("non-scalar {}", key)),
+                SchemaError::PairMapArity {
+                    type_name,
+                    field: f,
+                    sub_type,
+                } => ((type_name, f), format!("arity of {}", sub_type)),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (field("m"), "missing 7".to_string()),
+                (field("s"), "not a struct array".to_string()),
+                (field("c"), "non-scalar children".to_string()),
+                (field("p"), "missing -1".to_string()),
+                (field("w"), "arity of Person".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_protocols() {
+        let mut s = valid_schema();
+        s.add_protocol("get", 3, None, None, false);
+        s.add_protocol("put", 1, Some(42), None, false);
+        assert_eq!(
+            s.validate().unwrap_err(),
+            vec![
+                SchemaError::DuplicateProtocol("get".into()),
+                SchemaError::DuplicateProtocolTag {
+                    tag: 1,
+                    first: "get".into(),
+                    second: "put".into()
+                },
+                SchemaError::DanglingProtocolType {
+                    protocol: "put".into(),
+                    index: 42,
+                    count: 2
+                },
+            ]
+        );
+        assert_eq!(
+            SchemaError::DuplicateProtocolTag {
+                tag: 1,
+                first: "get".into(),
+                second: "put".into()
+            }
+            .to_string(),
+            "protocol tag 1 is used by both 'get' and 'put'"
+        );
+    }
+
+    #[test]
+    fn test_try_add_leaves_schema_unchanged_on_error() {
+        let mut s = valid_schema();
+        let err = s
+            .try_add_type(
+                "Bad",
+                vec![
+                    Field::new("b", 1, FieldType::Integer),
+                    Field::new("a", 0, FieldType::Integer),
+                ],
+            )
+            .unwrap_err();
+        assert_eq!(err.to_string(), "type 'Bad': field tag 0 is out of order");
+        assert!(s.get_type("Bad").is_none());
+        assert_eq!(s.types_list.len(), 2);
+        assert_eq!(
+            s.try_add_type("Phone", Vec::new()),
+            Err(SchemaError::DuplicateType("Phone".into()))
+        );
+
+        // A type may refer to itself, but not past itself.
+        let node = s.types_list.len();
+        let idx = s
+            .try_add_type(
+                "Node",
+                vec![Field::array("kids", 0, FieldType::Struct(node))],
+            )
+            .unwrap();
+        assert_eq!(idx, node);
+        assert!(matches!(
+            s.try_add_type("Fwd", vec![Field::new("x", 0, FieldType::Struct(node + 2))]),
+            Err(SchemaError::DanglingTypeRef { index, count: 4, .. }) if index == node + 2
+        ));
+
+        assert!(matches!(
+            s.try_add_protocol("again", 2, None, None, false),
+            Err(SchemaError::DuplicateProtocolTag { tag: 2, .. })
+        ));
+        assert!(matches!(
+            s.try_add_protocol("get", 9, None, None, false),
+            Err(SchemaError::DuplicateProtocol(_))
+        ));
+        assert_eq!(
+            s.try_add_protocol("walk", 9, Some(node), None, false),
+            Ok(2)
+        );
+        assert_eq!(s.validate(), Ok(()));
+    }
+}
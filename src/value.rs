@@ -0,0 +1,453 @@
+//! Dynamic message values.
+//!
+//! `Value` holds a decoded sproto struct without a compile-time Rust type,
+//! for tooling, proxies and tests. `encode_value` / `decode_value` walk the
+//! schema the same way the Lua binding does for Lua tables:
+//!
+//! | Schema type          | `Value`                                       |
+//! |----------------------|-----------------------------------------------|
+//! | `integer`            | `Integer`                                     |
+//! | `integer(N)`         | `Decimal` (an `Integer` is written as-is)     |
+//! | `boolean`            | `Boolean`                                     |
+//! | `double`             | `Double` (an `Integer` is converted)          |
+//! | `string`             | `String`                                      |
+//! | `binary`             | `Binary` (a `String` is written as bytes)     |
+//! | struct               | `Struct`                                      |
+//! | `*T`                 | `Array`                                       |
+//! | `*T(key)`, `*T()`    | `Map`, or an `Array` of the element structs   |
+//!
+//! A `*T(key)` map decodes to `(key, element)` pairs and a two-field `*T()`
+//! map to `(key, value)` pairs. When encoding a `*T(key)` map only the
+//! elements are written; as in C, the keys are not checked against them.
+
+use std::collections::BTreeMap;
+
+use crate::codec::{
+    DecodeLimits, DecodedField, SprotoDecode, SprotoEncode, StructDecoder, StructEncoder,
+};
+use crate::error::{DecodeError, EncodeError};
+use crate::types::{Field, FieldType, Sproto, SprotoType};
+
+/// A dynamically typed sproto value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Boolean(bool),
+    Double(f64),
+    String(String),
+    Binary(Vec<u8>),
+    /// A struct, as its present fields ordered by name.
+    Struct(BTreeMap<String, Value>),
+    Array(Vec<Value>),
+    /// A `*T(key)` or `*T()` map, in wire order.
+    Map(Vec<(Value, Value)>),
+    /// An `integer(N)` fixed-point value, already divided by 10^N.
+    Decimal(f64),
+}
+
+impl Value {
+    /// Name of the variant, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Boolean(_) => "boolean",
+            Value::Double(_) => "double",
+            Value::String(_) => "string",
+            Value::Binary(_) => "binary",
+            Value::Struct(_) => "struct",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Decimal(_) => "decimal",
+        }
+    }
+
+    /// Look up a field of a struct value.
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(fields) => fields.get(field),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Integer(v)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Boolean(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Double(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::String(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(v: Vec<u8>) -> Self {
+        Value::Binary(v)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(v: BTreeMap<String, Value>) -> Self {
+        Value::Struct(v)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Self {
+        Value::Array(v)
+    }
+}
+
+/// Encode a `Value::Struct` as `sproto_type`.
+pub fn encode_value(
+    sproto: &Sproto,
+    sproto_type: &SprotoType,
+    value: &Value,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut buf = Vec::new();
+    value.encode_to(sproto, sproto_type, &mut buf)?;
+    Ok(buf)
+}
+
+/// Decode `data` encoded as `sproto_type` into a `Value::Struct`.
+pub fn decode_value(
+    sproto: &Sproto,
+    sproto_type: &SprotoType,
+    data: &[u8],
+) -> Result<Value, DecodeError> {
+    Value::decode_from(sproto, sproto_type, data)
+}
+
+/// Decode `data` like `decode_value`, enforcing `limits`.
+pub fn decode_value_with_limits(
+    sproto: &Sproto,
+    sproto_type: &SprotoType,
+    data: &[u8],
+    limits: DecodeLimits,
+) -> Result<Value, DecodeError> {
+    Value::decode_from_with_limits(sproto, sproto_type, data, limits)
+}
+
+// =============================================================================
+// Encoding
+// =============================================================================
+
+impl SprotoEncode for Value {
+    fn encode_fields(&self, enc: &mut StructEncoder) -> Result<(), EncodeError> {
+        match self {
+            Value::Struct(fields) => encode_struct(enc, fields),
+            other => Err(EncodeError::Other(format!(
+                "type '{}' must be encoded from a struct, got {}",
+                enc.sproto_type.name,
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+fn mismatch(sproto: &Sproto, field: &Field, value: &Value) -> EncodeError {
+    EncodeError::TypeMismatch {
+        field: field.name.to_string(),
+        expected: field.schema_type_name(sproto),
+        actual: value.type_name().to_string(),
+    }
+}
+
+fn encode_struct(
+    enc: &mut StructEncoder,
+    fields: &BTreeMap<String, Value>,
+) -> Result<(), EncodeError> {
+    let st = enc.sproto_type;
+    for (name, value) in fields {
+        let field = st.find_field_by_name(name).ok_or_else(|| {
+            EncodeError::Other(format!("unknown field '{}' in type '{}'", name, st.name))
+        })?;
+        if field.is_array {
+            encode_array_field(enc, field, value)?;
+        } else {
+            encode_scalar_field(enc, field, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn encode_scalar_field(
+    enc: &mut StructEncoder,
+    field: &Field,
+    value: &Value,
+) -> Result<(), EncodeError> {
+    let tag = field.tag;
+    match (&field.field_type, value) {
+        (FieldType::Integer, Value::Integer(v)) => enc.set_integer(tag, *v),
+        (FieldType::Integer, Value::Decimal(v)) if field.decimal_precision > 0 => {
+            enc.set_decimal(tag, *v)
+        }
+        (FieldType::Boolean, Value::Boolean(v)) => enc.set_bool(tag, *v),
+        (FieldType::Double, Value::Double(v)) => enc.set_double(tag, *v),
+        (FieldType::Double, Value::Integer(v)) => enc.set_double(tag, *v as f64),
+        (FieldType::String, Value::String(v)) => enc.set_string(tag, v),
+        (FieldType::Binary, Value::Binary(v)) => enc.set_bytes(tag, v),
+        (FieldType::Binary, Value::String(v)) => enc.set_bytes(tag, v.as_bytes()),
+        (FieldType::Struct(_), Value::Struct(fields)) => {
+            enc.encode_nested(tag, |sub| encode_struct(sub, fields))
+        }
+        _ => Err(mismatch(enc.sproto, field, value)),
+    }
+}
+
+fn encode_array_field(
+    enc: &mut StructEncoder,
+    field: &Field,
+    value: &Value,
+) -> Result<(), EncodeError> {
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Map(entries) if field.key_tag >= 0 => {
+            return encode_map_field(enc, field, entries);
+        }
+        _ => return Err(mismatch(enc.sproto, field, value)),
+    };
+    let tag = field.tag;
+    let sproto = enc.sproto;
+    let bad = |item: &Value| {
+        Err(EncodeError::TypeMismatch {
+            field: field.name.to_string(),
+            expected: field.schema_type_name(sproto),
+            actual: format!("array of {}", item.type_name()),
+        })
+    };
+    match &field.field_type {
+        FieldType::Integer => {
+            let mut values = Vec::with_capacity(items.len());
+            for item in items {
+                values.push(match item {
+                    Value::Integer(v) => *v,
+                    Value::Decimal(v) if field.decimal_precision > 0 => field.scale_decimal(*v),
+                    _ => return bad(item),
+                });
+            }
+            enc.set_integer_array(tag, &values)
+        }
+        FieldType::Boolean => {
+            let mut values = Vec::with_capacity(items.len());
+            for item in items {
+                match item {
+                    Value::Boolean(v) => values.push(*v),
+                    _ => return bad(item),
+                }
+            }
+            enc.set_bool_array(tag, &values)
+        }
+        FieldType::Double => {
+            let mut values = Vec::with_capacity(items.len());
+            for item in items {
+                values.push(match item {
+                    Value::Double(v) => *v,
+                    Value::Integer(v) => *v as f64,
+                    _ => return bad(item),
+                });
+            }
+            enc.set_double_array(tag, &values)
+        }
+        FieldType::String => {
+            let mut values = Vec::with_capacity(items.len());
+            for item in items {
+                match item {
+                    Value::String(v) => values.push(v.as_str()),
+                    _ => return bad(item),
+                }
+            }
+            enc.set_string_array(tag, &values)
+        }
+        FieldType::Binary => {
+            let mut values = Vec::with_capacity(items.len());
+            for item in items {
+                match item {
+                    Value::Binary(v) => values.push(v.as_slice()),
+                    Value::String(v) => values.push(v.as_bytes()),
+                    _ => return bad(item),
+                }
+            }
+            enc.set_bytes_array(tag, &values)
+        }
+        FieldType::Struct(_) => {
+            if let Some(item) = items.iter().find(|v| !matches!(v, Value::Struct(_))) {
+                return bad(item);
+            }
+            enc.encode_struct_array(tag, |arr| {
+                for item in items {
+                    arr.encode_element(|e| item.encode_fields(e))?;
+                }
+                Ok(())
+            })
+        }
+    }
+}
+
+/// Encode a `Value::Map` into a `*T(key)` or `*T()` field.
+fn encode_map_field(
+    enc: &mut StructEncoder,
+    field: &Field,
+    entries: &[(Value, Value)],
+) -> Result<(), EncodeError> {
+    enc.encode_struct_array(field.tag, |arr| {
+        for (key, value) in entries {
+            arr.encode_element(|e| {
+                if !field.is_map {
+                    return value.encode_fields(e);
+                }
+                let st = e.sproto_type;
+                for entry_field in &st.fields {
+                    let v = if entry_field.tag as i32 == field.key_tag {
+                        key
+                    } else {
+                        value
+                    };
+                    if entry_field.is_array {
+                        encode_array_field(e, entry_field, v)?;
+                    } else {
+                        encode_scalar_field(e, entry_field, v)?;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    })
+}
+
+// =============================================================================
+// Decoding
+// =============================================================================
+
+impl SprotoDecode for Value {
+    fn decode_fields(dec: &mut StructDecoder) -> Result<Self, DecodeError> {
+        let mut fields = BTreeMap::new();
+        while let Some(f) = dec.next_field()? {
+            let value = if f.field().is_array {
+                decode_array_field(&f)?
+            } else {
+                decode_scalar_field(&f)?
+            };
+            fields.insert(f.field().name.to_string(), value);
+        }
+        Ok(Value::Struct(fields))
+    }
+}
+
+fn decode_scalar_field(f: &DecodedField) -> Result<Value, DecodeError> {
+    let field = f.field();
+    Ok(match &field.field_type {
+        FieldType::Integer if field.decimal_precision > 0 => Value::Decimal(f.as_decimal()?),
+        FieldType::Integer => Value::Integer(f.as_integer()?),
+        FieldType::Boolean => Value::Boolean(f.as_bool()?),
+        FieldType::Double => Value::Double(f.as_double()?),
+        FieldType::String => Value::String(f.as_string()?.to_string()),
+        FieldType::Binary => Value::Binary(f.as_bytes().to_vec()),
+        FieldType::Struct(_) => Value::decode_fields(&mut f.as_struct()?)?,
+    })
+}
+
+fn decode_array_field(f: &DecodedField) -> Result<Value, DecodeError> {
+    let field = f.field();
+    let items = match &field.field_type {
+        FieldType::Integer if field.decimal_precision > 0 => f
+            .as_decimal_array()?
+            .into_iter()
+            .map(Value::Decimal)
+            .collect(),
+        FieldType::Integer => f
+            .as_integer_array()?
+            .into_iter()
+            .map(Value::Integer)
+            .collect(),
+        FieldType::Boolean => f.as_bool_array().into_iter().map(Value::Boolean).collect(),
+        FieldType::Double => f
+            .as_double_array()?
+            .into_iter()
+            .map(Value::Double)
+            .collect(),
+        FieldType::String => f.as_string_array()?.into_iter().map(Value::from).collect(),
+        FieldType::Binary => f
+            .as_bytes_array()?
+            .into_iter()
+            .map(|b| Value::Binary(b.to_vec()))
+            .collect(),
+        FieldType::Struct(_) if field.key_tag >= 0 => return decode_map_field(f),
+        FieldType::Struct(_) => f
+            .as_struct_iter()?
+            .map(|dec| Value::decode_fields(&mut dec?))
+            .collect::<Result<_, _>>()?,
+    };
+    Ok(Value::Array(items))
+}
+
+/// Decode a `*T(key)` or `*T()` field into `Value::Map`.
+fn decode_map_field(f: &DecodedField) -> Result<Value, DecodeError> {
+    let field = f.field();
+    let mut entries = Vec::new();
+    for dec in f.as_struct_iter()? {
+        let mut dec = dec?;
+        let mut key = None;
+        let mut value = None;
+        let mut element = BTreeMap::new();
+        while let Some(ef) = dec.next_field()? {
+            let v = if ef.field().is_array {
+                decode_array_field(&ef)?
+            } else {
+                decode_scalar_field(&ef)?
+            };
+            let is_key = ef.tag() as i32 == field.key_tag;
+            if field.is_map {
+                if is_key {
+                    key = Some(v);
+                } else if value.is_none() {
+                    value = Some(v);
+                }
+            } else {
+                if is_key {
+                    key = Some(v.clone());
+                }
+                element.insert(ef.field().name.to_string(), v);
+            }
+        }
+        let key = key.ok_or_else(|| {
+            DecodeError::InvalidData(format!(
+                "map element in field '{}' is missing key tag {}",
+                field.name, field.key_tag
+            ))
+        })?;
+        let value = if field.is_map {
+            value.ok_or_else(|| {
+                DecodeError::InvalidData(format!(
+                    "map element in field '{}' is missing its value",
+                    field.name
+                ))
+            })?
+        } else {
+            Value::Struct(element)
+        };
+        entries.push((key, value));
+    }
+    Ok(Value::Map(entries))
+}
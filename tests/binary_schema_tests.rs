@@ -90,3 +90,166 @@ fn test_load_rpc_schema() {
     assert!(bh.request.is_none());
     assert!(bh.response.is_none());
 }
+
+// =============================================================================
+// dump_binary: byte-compatible with the C/Lua sprotoparser output
+// =============================================================================
+
+fn hexdump(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+macro_rules! dump_test {
+    ($name:ident, $file:expr) => {
+        #[test]
+        fn $name() {
+            let data = testdata($file);
+            let sproto = binary_schema::load_binary(&data).unwrap();
+            let dumped = binary_schema::dump_binary(&sproto).unwrap();
+            assert_eq!(
+                hexdump(&dumped),
+                hexdump(&data),
+                "dump mismatch for {}",
+                $file
+            );
+        }
+    };
+}
+
+dump_test!(test_dump_schema, "schema.bin");
+dump_test!(test_dump_rpc_schema, "rpc_schema.bin");
+dump_test!(
+    test_dump_lua_addressbook_schema,
+    "lua_addressbook_schema.bin"
+);
+dump_test!(test_dump_lua_data_schema, "lua_data_schema.bin");
+dump_test!(test_dump_lua_human_schema, "lua_human_schema.bin");
+dump_test!(test_dump_map_schema, "map_schema.bin");
+
+#[test]
+fn test_dump_parsed_schema() {
+    let text = String::from_utf8(testdata("schema.sproto")).unwrap();
+    let sproto = sproto::parser::parse(&text).unwrap();
+    let dumped = binary_schema::dump_binary(&sproto).unwrap();
+    assert_eq!(hexdump(&dumped), hexdump(&testdata("schema.bin")));
+}
+
+#[test]
+fn test_dump_builder_schema_roundtrip() {
+    use sproto::types::{Field, FieldType, Sproto};
+
+    let mut s = Sproto::new();
+    let pair = s.add_type(
+        "Pair",
+        vec![
+            Field::new("key", 0, FieldType::String),
+            Field::new("value", 1, FieldType::Integer),
+        ],
+    );
+    let mut pairs = Field::array("pairs", 4, FieldType::Struct(pair));
+    pairs.key_tag = 0;
+    pairs.is_map = true;
+    let mut by_key = Field::array("by_key", 5, FieldType::Struct(pair));
+    by_key.key_tag = 0;
+    let item = s.add_type(
+        "Item",
+        vec![
+            Field::new("blob", 0, FieldType::Binary),
+            Field::decimal("price", 2, 1000),
+            Field::array("ratios", 3, FieldType::Double),
+            pairs,
+            by_key,
+        ],
+    );
+    s.add_type("Empty", vec![]);
+    s.add_protocol("notify", 7, Some(item), None, false);
+    s.add_protocol("ack", 3, None, None, true);
+    s.add_protocol("get", 5, Some(pair), Some(item), false);
+
+    let loaded = binary_schema::load_binary(&binary_schema::dump_binary(&s).unwrap()).unwrap();
+
+    assert_eq!(loaded.types_list.len(), 3);
+    let it = loaded.get_type("Item").unwrap();
+    assert_eq!(it.fields[0].field_type, FieldType::Binary);
+    assert_eq!(it.fields[1].decimal_precision, 1000);
+    assert_eq!(it.base_tag, -1);
+    assert!(it.fields[2].is_array);
+    assert_eq!(it.fields[2].field_type, FieldType::Double);
+    assert_eq!(it.fields[3].key_tag, 0);
+    assert!(it.fields[3].is_map);
+    assert_eq!(it.fields[4].key_tag, 0);
+    assert!(!it.fields[4].is_map);
+    assert!(loaded.get_type("Empty").unwrap().fields.is_empty());
+
+    // Protocols come back in tag order.
+    let tags: Vec<u16> = loaded.protocols.iter().map(|p| p.tag).collect();
+    assert_eq!(tags, vec![3, 5, 7]);
+    let ack = loaded.get_protocol("ack").unwrap();
+    assert!(ack.confirm && ack.request.is_none() && ack.response.is_none());
+    let get = loaded.get_protocol("get").unwrap();
+    assert_eq!(get.request, Some(pair));
+    assert_eq!(get.response, Some(item));
+    let notify = loaded.get_protocol("notify").unwrap();
+    assert_eq!(notify.request, Some(item));
+    assert!(notify.response.is_none() && !notify.confirm);
+}
+
+#[test]
+fn test_dump_empty_schema() {
+    let empty = sproto::Sproto::new();
+    let dumped = binary_schema::dump_binary(&empty).unwrap();
+    assert_eq!(dumped, vec![0, 0]);
+    // As before, the loader requires a type array.
+    assert!(binary_schema::load_binary(&dumped).is_err());
+}
+
+#[test]
+fn test_dump_value_out_of_range() {
+    use sproto::types::{Field, FieldType, Sproto};
+
+    let mut s = Sproto::new();
+    s.add_type("Wide", vec![Field::new("far", 0x7fff, FieldType::Integer)]);
+    let err = binary_schema::dump_binary(&s).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: type 'Wide': field 'far' tag 32767 is too large for a binary schema"
+    );
+
+    let mut s = Sproto::new();
+    s.add_type("Ok", vec![Field::new("near", 0x7ffe, FieldType::Integer)]);
+    s.add_protocol("far", 0xffff, None, None, false);
+    let err = binary_schema::dump_binary(&s).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: protocol 'far' tag 65535 is too large for a binary schema"
+    );
+}
+
+#[test]
+fn test_load_rejects_invalid_map_key() {
+    use sproto::types::{Field, FieldType, Sproto};
+
+    let mut s = Sproto::new();
+    let pair = s.add_type(
+        "Pair",
+        vec![
+            Field::new("key", 0, FieldType::String),
+            Field::new("value", 1, FieldType::Integer),
+        ],
+    );
+    let mut pairs = Field::array("pairs", 0, FieldType::Struct(pair));
+    pairs.key_tag = 5;
+    pairs.is_map = true;
+    s.add_type("Holder", vec![pairs]);
+
+    // The writer does not check map keys; the loader does.
+    let dumped = binary_schema::dump_binary(&s).unwrap();
+    let err = binary_schema::load_binary(&dumped).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid data: invalid schema: type 'Holder': map field 'pairs' has key tag 5, which its element type lacks"
+    );
+}
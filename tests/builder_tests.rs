@@ -0,0 +1,285 @@
+//! Name-based schema builder tests.
+//!
+//! Schemas built with `SchemaBuilder` are compared against the same schema
+//! parsed from text, and each resolution error is checked.
+
+use sproto::builder::{SchemaBuilder, TypeBuilder};
+use sproto::error::SchemaError;
+use sproto::types::{FieldType, Sproto};
+use sproto::{compat, parser};
+
+const TEXT: &str = r#"
+.Person {
+    .PhoneNumber {
+        number 0 : string
+        type 1 : integer
+    }
+    name 0 : string
+    children 1 : *Person
+    phones 2 : *PhoneNumber(number)
+    balance 3 : integer(2)
+    photo 4 : binary
+    scores 5 : *Score()
+}
+
+.Score {
+    subject 0 : string
+    value 1 : double
+}
+
+login 1 {
+    request { name 0 : string }
+    response Person
+}
+
+logout 2 {
+    response nil
+}
+
+ping 3 {}
+"#;
+
+/// `TEXT`, with the types declared in a different order.
+fn built() -> Sproto {
+    SchemaBuilder::new()
+        .ty("Score", |t| {
+            t.field("subject", 0, "string").field("value", 1, "double")
+        })
+        .ty("Person", |t| {
+            t.field("name", 0, "string")
+                .array("children", 1, "Person")
+                .array("phones", 2, "PhoneNumber")
+                .map_by("number")
+                .decimal("balance", 3, 2)
+                .field("photo", 4, "binary")
+                .array("scores", 5, "Score")
+                .map_by("")
+        })
+        .ty("Person.PhoneNumber", |t| {
+            t.field("number", 0, "string").field("type", 1, "integer")
+        })
+        .protocol("ping", 3, |p| p)
+        .protocol("logout", 2, |p| p.response_nil())
+        .protocol("login", 1, |p| {
+            p.request_type(|t| t.field("name", 0, "string"))
+                .response("Person")
+        })
+        .build()
+        .unwrap()
+}
+
+fn names(sproto: &Sproto) -> (Vec<&str>, Vec<(&str, u16)>) {
+    (
+        sproto.types_list.iter().map(|t| t.name.as_str()).collect(),
+        sproto
+            .protocols
+            .iter()
+            .map(|p| (p.name.as_str(), p.tag))
+            .collect(),
+    )
+}
+
+// =============================================================================
+// Resolution
+// =============================================================================
+
+#[test]
+fn test_builder_matches_parser() {
+    let parsed = parser::parse(TEXT).unwrap();
+    let built = built();
+
+    assert_eq!(names(&built), names(&parsed));
+    let report = compat::check(&parsed, &built);
+    assert!(report.changes.is_empty(), "{}", report);
+    built.validate().unwrap();
+
+    let logout = built.get_protocol("logout").unwrap();
+    assert!(logout.confirm);
+    assert_eq!(logout.response, None);
+    let ping = built.get_protocol("ping").unwrap();
+    assert!(!ping.confirm && ping.request.is_none() && ping.response.is_none());
+}
+
+#[test]
+fn test_builder_recursive_and_map_fields() {
+    let s = built();
+    let person_idx = s.get_type_index("Person").unwrap();
+    let person = &s.types_list[person_idx];
+
+    let children = person.find_field_by_name("children").unwrap();
+    assert_eq!(children.field_type, FieldType::Struct(person_idx));
+    assert!(children.is_array);
+
+    let phones = person.find_field_by_name("phones").unwrap();
+    assert_eq!(
+        phones.field_type,
+        FieldType::Struct(s.get_type_index("Person.PhoneNumber").unwrap())
+    );
+    assert_eq!((phones.key_tag, phones.is_map), (0, false));
+
+    let scores = person.find_field_by_name("scores").unwrap();
+    assert_eq!((scores.key_tag, scores.is_map), (0, true));
+
+    let balance = person.find_field_by_name("balance").unwrap();
+    assert_eq!(balance.decimal_precision, 100);
+}
+
+#[test]
+fn test_builder_inner_scope_shadows_outer() {
+    let s = SchemaBuilder::new()
+        .ty("Item", |t| t.field("id", 0, "integer"))
+        .ty("Order", |t| t.field("item", 0, "Item"))
+        .ty("Order.Item", |t| t.field("sku", 0, "string"))
+        .ty("Order.Line", |t| t.field("item", 0, "Item"))
+        .build()
+        .unwrap();
+
+    let inner = FieldType::Struct(s.get_type_index("Order.Item").unwrap());
+    for ty in ["Order", "Order.Line"] {
+        let field = s.get_type(ty).unwrap().find_field_by_name("item").unwrap();
+        assert_eq!(field.field_type, inner, "in {}", ty);
+    }
+}
+
+// =============================================================================
+// Errors
+// =============================================================================
+
+#[test]
+fn test_builder_dangling_references() {
+    let err = SchemaBuilder::new()
+        .ty("Person", |t| t.array("phones", 0, "Phone"))
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        SchemaError::UndefinedType {
+            type_name: "Person".into(),
+            field: "phones".into(),
+            name: "Phone".into(),
+        }
+    );
+
+    // Nested types are not visible from outside their parent by short name.
+    let err = SchemaBuilder::new()
+        .ty("A.Inner", |t| t)
+        .ty("B", |t| t.field("x", 0, "Inner"))
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, SchemaError::UndefinedType { ref name, .. } if name == "Inner"));
+
+    let err = SchemaBuilder::new()
+        .protocol("login", 1, |p| p.request("LoginRequest"))
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "protocol 'login' refers to undefined type 'LoginRequest'"
+    );
+}
+
+#[test]
+fn test_builder_map_errors() {
+    let phone = |t: TypeBuilder| {
+        t.field("number", 0, "string")
+            .field("type", 1, "integer")
+            .array("tags", 2, "string")
+    };
+    let build = |f: fn(TypeBuilder) -> TypeBuilder| {
+        SchemaBuilder::new()
+            .ty("Phone", phone)
+            .ty("Person", f)
+            .build()
+            .unwrap_err()
+    };
+
+    assert_eq!(
+        build(|t| t.array("phones", 0, "Phone").map_by("email")),
+        SchemaError::UnknownKey {
+            type_name: "Person".into(),
+            field: "phones".into(),
+            key: "email".into(),
+        }
+    );
+    assert_eq!(
+        build(|t| t.array("phones", 0, "Phone").map_by("tags")),
+        SchemaError::NonScalarKey {
+            type_name: "Person".into(),
+            field: "phones".into(),
+            key: "tags".into(),
+        }
+    );
+    assert_eq!(
+        build(|t| t.field("phone", 0, "Phone").map_by("number")),
+        SchemaError::KeyOnNonStructArray {
+            type_name: "Person".into(),
+            field: "phone".into(),
+        }
+    );
+    assert_eq!(
+        build(|t| t.array("phones", 0, "Phone").map_by("")),
+        SchemaError::PairMapArity {
+            type_name: "Person".into(),
+            field: "phones".into(),
+            sub_type: "Phone".into(),
+        }
+    );
+}
+
+#[test]
+fn test_builder_duplicates_and_ranges() {
+    let err = SchemaBuilder::new()
+        .ty("A", |t| t)
+        .ty("A", |t| t)
+        .build()
+        .unwrap_err();
+    assert_eq!(err, SchemaError::DuplicateType("A".into()));
+
+    // An inline request collides with an explicitly named type.
+    let err = SchemaBuilder::new()
+        .ty("login.request", |t| t)
+        .protocol("login", 1, |p| p.request_type(|t| t))
+        .build()
+        .unwrap_err();
+    assert_eq!(err, SchemaError::DuplicateType("login.request".into()));
+
+    // Fields may be given in any order, but tags must be unique.
+    let err = SchemaBuilder::new()
+        .ty("A", |t| {
+            t.field("b", 1, "integer")
+                .field("a", 0, "integer")
+                .field("c", 1, "string")
+        })
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        SchemaError::DuplicateTag {
+            type_name: "A".into(),
+            tag: 1,
+        }
+    );
+
+    let err = SchemaBuilder::new()
+        .protocol("a", 1, |p| p)
+        .protocol("b", 1, |p| p)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "protocol tag 1 is used by both 'a' and 'b'"
+    );
+
+    let err = SchemaBuilder::new()
+        .ty("A", |t| t.decimal("x", 0, 10))
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        SchemaError::DecimalOutOfRange {
+            type_name: "A".into(),
+            field: "x".into(),
+            digits: 10,
+        }
+    );
+}
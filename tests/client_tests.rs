@@ -0,0 +1,449 @@
+//! Async RPC client tests (feature = "tokio").
+//!
+//! The client talks to a hand-driven server over `tokio::io::duplex`; the
+//! server side uses `Host` and the framing codec directly, so each test
+//! controls exactly when and in which order responses are sent.
+
+#![cfg(feature = "tokio")]
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+use sproto::error::RpcError;
+use sproto::parser;
+use sproto::rpc::client::{Client, ClientOptions};
+use sproto::rpc::framing::{FrameDecoder, FrameEncoder};
+use sproto::rpc::{DispatchResult, Host, Responder};
+use sproto::value::{decode_value, encode_value, Value};
+use sproto::Sproto;
+
+const SCHEMA: &str = r#"
+.Echo {
+    data 0 : string
+}
+
+echo 1 {
+    request Echo
+    response Echo
+}
+
+heartbeat 2 {}
+
+ack 3 {
+    request Echo
+    response nil
+}
+
+status 4 {
+    response Echo
+}
+"#;
+
+fn schema() -> Sproto {
+    parser::parse(SCHEMA).unwrap()
+}
+
+fn echo(data: &str) -> Value {
+    let mut fields = BTreeMap::new();
+    fields.insert("data".to_string(), Value::from(data));
+    Value::Struct(fields)
+}
+
+fn encode_echo(sproto: &Sproto, data: &str) -> Vec<u8> {
+    encode_value(sproto, sproto.get_type("Echo").unwrap(), &echo(data)).unwrap()
+}
+
+fn decode_echo(sproto: &Sproto, body: &[u8]) -> String {
+    let value = decode_value(sproto, sproto.get_type("Echo").unwrap(), body).unwrap();
+    match value.get("data") {
+        Some(Value::String(s)) => s.clone(),
+        other => panic!("unexpected data {:?}", other),
+    }
+}
+
+/// The remote end of the connection, driven step by step by each test.
+struct Server {
+    sproto: Sproto,
+    host: Host,
+    stream: DuplexStream,
+    decoder: FrameDecoder,
+}
+
+impl Server {
+    async fn recv(&mut self) -> DispatchResult {
+        let mut buf = [0u8; 1024];
+        loop {
+            if let Some(packet) = self.decoder.next_frame().unwrap() {
+                return self.host.dispatch(&packet).unwrap();
+            }
+            let n = self.stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "client closed the connection");
+            self.decoder.extend(&buf[..n]);
+        }
+    }
+
+    /// Receive an `echo` request and return its data and responder.
+    async fn recv_echo(&mut self) -> (String, Box<Responder>) {
+        match self.recv().await {
+            DispatchResult::Request {
+                name,
+                body,
+                responder,
+                ..
+            } => {
+                assert_eq!(name, "echo");
+                (decode_echo(&self.sproto, &body), responder.unwrap())
+            }
+            _ => panic!("expected Request"),
+        }
+    }
+
+    async fn send(&mut self, packet: &[u8]) {
+        let frame = FrameEncoder::new().encode(packet).unwrap();
+        self.stream.write_all(&frame).await.unwrap();
+    }
+
+    async fn reply(&mut self, responder: &Responder, data: &str) {
+        let body = encode_echo(&self.sproto, data);
+        let packet = responder.respond(&body, None).unwrap();
+        self.send(&packet).await;
+    }
+}
+
+fn connect(options: ClientOptions) -> (Client, sproto::rpc::client::Incoming, Server) {
+    connect_with(Host::new(schema()), options)
+}
+
+fn connect_with(
+    host: Host,
+    options: ClientOptions,
+) -> (Client, sproto::rpc::client::Incoming, Server) {
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let sproto = schema();
+    let sender = host.attach(sproto.clone());
+    let (client, incoming) = Client::with_options(client_io, host, sender, options);
+    let server = Server {
+        host: Host::new(sproto.clone()),
+        sproto,
+        stream: server_io,
+        decoder: FrameDecoder::new(),
+    };
+    (client, incoming, server)
+}
+
+// ============================================================================
+// Calls
+// ============================================================================
+
+#[tokio::test]
+async fn test_client_call_roundtrip() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+    let sproto = schema();
+
+    let call = client.call("echo", &encode_echo(&sproto, "hello"));
+    assert_eq!(call.session(), 1);
+    let (data, responder) = server.recv_echo().await;
+    assert_eq!(data, "hello");
+    assert_eq!(responder.session(), 1);
+    server.reply(&responder, "HELLO").await;
+
+    let body = call.await.unwrap();
+    assert_eq!(decode_echo(&sproto, &body), "HELLO");
+    assert_eq!(client.pending_calls(), 0);
+}
+
+#[tokio::test]
+async fn test_client_concurrent_calls_out_of_order() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+    let sproto = schema();
+
+    let calls: Vec<_> = ["a", "b", "c"]
+        .iter()
+        .map(|data| client.call("echo", &encode_echo(&sproto, data)))
+        .collect();
+    let sessions: Vec<u64> = calls.iter().map(|c| c.session()).collect();
+    assert_eq!(sessions, vec![1, 2, 3]);
+    assert_eq!(client.pending_calls(), 3);
+
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        received.push(server.recv_echo().await);
+    }
+    for (data, responder) in received.iter().rev() {
+        server.reply(responder, &data.to_uppercase()).await;
+    }
+
+    let mut results = Vec::new();
+    for call in calls {
+        results.push(decode_echo(&sproto, &call.await.unwrap()));
+    }
+    assert_eq!(results, vec!["A", "B", "C"]);
+}
+
+#[tokio::test]
+async fn test_client_call_as() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+
+    let server_task = tokio::spawn(async move {
+        let (data, responder) = server.recv_echo().await;
+        server.reply(&responder, &format!("{}!", data)).await;
+        server
+    });
+    let response: Value = client.call_as("echo", &echo("typed")).await.unwrap();
+    assert_eq!(response, echo("typed!"));
+    server_task.await.unwrap();
+
+    // One-way protocols are never answered, so they cannot be called.
+    let err = client
+        .call_as::<(), ()>("heartbeat", &())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RpcError::OneWay(name) if name == "heartbeat"));
+}
+
+#[tokio::test]
+async fn test_client_call_as_empty_messages() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+
+    let server_task = tokio::spawn(async move {
+        // `response nil`: the server only acknowledges.
+        match server.recv().await {
+            DispatchResult::Request {
+                name,
+                body,
+                responder,
+                ..
+            } => {
+                assert_eq!(name, "ack");
+                assert_eq!(decode_echo(&server.sproto, &body), "confirm me");
+                let responder = responder.unwrap();
+                assert!(responder.is_ack_only());
+                server.send(&responder.acknowledge(None).unwrap()).await;
+            }
+            _ => panic!("expected Request"),
+        }
+
+        // No request type: the request body is empty.
+        match server.recv().await {
+            DispatchResult::Request {
+                name,
+                body,
+                responder,
+                ..
+            } => {
+                assert_eq!(name, "status");
+                assert!(body.iter().all(|&b| b == 0));
+                server.reply(&responder.unwrap(), "ready").await;
+            }
+            _ => panic!("expected Request"),
+        }
+        server
+    });
+
+    let () = client.call_as("ack", &echo("confirm me")).await.unwrap();
+    let status: Value = client.call_as("status", &()).await.unwrap();
+    assert_eq!(status, echo("ready"));
+    server_task.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_notify() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+    client.notify("heartbeat", &[]).unwrap();
+    match server.recv().await {
+        DispatchResult::Request {
+            name, responder, ..
+        } => {
+            assert_eq!(name, "heartbeat");
+            assert!(responder.is_none());
+        }
+        _ => panic!("expected Request"),
+    }
+    assert_eq!(client.pending_calls(), 0);
+}
+
+#[tokio::test]
+async fn test_client_unknown_protocol() {
+    let (client, _incoming, _server) = connect(ClientOptions::default());
+    let err = client.call("missing", &[]).await.unwrap_err();
+    assert!(matches!(err, RpcError::UnknownProtocol(_)));
+    assert_eq!(client.pending_calls(), 0);
+}
+
+// ============================================================================
+// Timeouts and cancellation
+// ============================================================================
+
+#[tokio::test(start_paused = true)]
+async fn test_client_timeout() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+    let sproto = schema();
+
+    let call = client
+        .call("echo", &encode_echo(&sproto, "slow"))
+        .with_timeout(Duration::from_secs(5));
+    let (_, late) = server.recv_echo().await;
+    let err = call.await.unwrap_err();
+    assert!(matches!(err, RpcError::Timeout(1)));
+    assert_eq!(client.pending_calls(), 0);
+
+    // The late response is dropped and the connection stays usable.
+    server.reply(&late, "too late").await;
+    let call = client.call("echo", &encode_echo(&sproto, "fast"));
+    let (_, responder) = server.recv_echo().await;
+    server.reply(&responder, "ok").await;
+    assert_eq!(decode_echo(&sproto, &call.await.unwrap()), "ok");
+    assert!(!client.is_closed());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_client_default_timeout() {
+    let (client, _incoming, _server) = connect(ClientOptions {
+        timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    });
+    let err = client.call("echo", &[]).await.unwrap_err();
+    assert_eq!(err.to_string(), "session 1 timed out");
+}
+
+#[tokio::test]
+async fn test_client_cancel_by_drop() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+    let sproto = schema();
+
+    let call = client.call("echo", &encode_echo(&sproto, "cancelled"));
+    assert_eq!(client.pending_calls(), 1);
+    drop(call);
+    assert_eq!(client.pending_calls(), 0);
+
+    let (data, late) = server.recv_echo().await;
+    assert_eq!(data, "cancelled");
+    server.reply(&late, "ignored").await;
+
+    let call = client.call("echo", &encode_echo(&sproto, "next"));
+    let (_, responder) = server.recv_echo().await;
+    server.reply(&responder, "next").await;
+    assert_eq!(decode_echo(&sproto, &call.await.unwrap()), "next");
+}
+
+// ============================================================================
+// Connection lifecycle
+// ============================================================================
+
+#[tokio::test]
+async fn test_client_closed_connection() {
+    let (client, mut incoming, mut server) = connect(ClientOptions::default());
+    let sproto = schema();
+
+    let call = client.call("echo", &encode_echo(&sproto, "lost"));
+    server.recv_echo().await;
+    drop(server);
+
+    assert!(matches!(call.await, Err(RpcError::Closed)));
+    assert!(incoming.recv().await.is_none());
+    assert!(client.is_closed());
+    assert!(matches!(
+        client.call("echo", &[]).await,
+        Err(RpcError::Closed)
+    ));
+}
+
+#[tokio::test]
+async fn test_client_skips_unknown_protocol() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+    let sproto = schema();
+
+    let call = client.call("echo", &encode_echo(&sproto, "still here"));
+    let (_, responder) = server.recv_echo().await;
+
+    // The server runs a newer schema and calls a protocol the client lacks.
+    let mut newer = schema();
+    let echo_type = newer.get_type_index("Echo").unwrap();
+    newer.add_protocol("added_later", 9, Some(echo_type), Some(echo_type), false);
+    let mut newer_sender = Host::new(newer.clone()).attach(newer.clone());
+    let packet = newer_sender
+        .request("added_later", &encode_echo(&newer, "?"), Some(40), None)
+        .unwrap();
+    server.send(&packet).await;
+
+    // The pending call is unaffected and the connection stays open.
+    server.reply(&responder, "answered").await;
+    assert_eq!(decode_echo(&sproto, &call.await.unwrap()), "answered");
+    assert!(!client.is_closed());
+
+    let call = client.call("echo", &encode_echo(&sproto, "next"));
+    let (_, responder) = server.recv_echo().await;
+    server.reply(&responder, "ok").await;
+    assert_eq!(decode_echo(&sproto, &call.await.unwrap()), "ok");
+}
+
+#[tokio::test]
+async fn test_client_strict_invalid_response() {
+    let host = Host::new(schema()).with_strict(true);
+    let (client, _incoming, mut server) = connect_with(host, ClientOptions::default());
+    let sproto = schema();
+
+    let call = client.call("echo", &encode_echo(&sproto, "bad reply"));
+    let (_, responder) = server.recv_echo().await;
+    // A string field whose length runs past the end of the data.
+    let packet = responder
+        .respond(&[1, 0, 0, 0, 0xff, 0, 0, 0], None)
+        .unwrap();
+    server.send(&packet).await;
+
+    let err = tokio::time::timeout(Duration::from_secs(2), call)
+        .await
+        .expect("the call should fail rather than wait")
+        .unwrap_err();
+    assert!(matches!(err, RpcError::InvalidResponse { session: 1, .. }));
+    assert_eq!(client.pending_calls(), 0);
+    assert!(!client.is_closed());
+}
+
+#[tokio::test]
+async fn test_client_dropped_fails_pending_calls() {
+    let (client, _incoming, mut server) = connect(ClientOptions::default());
+    let sproto = schema();
+
+    let call = client.call("echo", &encode_echo(&sproto, "orphaned"));
+    server.recv_echo().await;
+    drop(client);
+
+    let result = tokio::time::timeout(Duration::from_secs(2), call)
+        .await
+        .expect("call should resolve once the client is dropped");
+    assert!(matches!(result, Err(RpcError::Closed)));
+}
+
+#[tokio::test]
+async fn test_client_incoming_request() {
+    let (client, mut incoming, mut server) = connect(ClientOptions::default());
+    let sproto = schema();
+
+    // The server pushes a request to the client and waits for its answer.
+    let mut server_sender = server.host.attach(sproto.clone());
+    let packet = server_sender
+        .request("echo", &encode_echo(&sproto, "ping"), Some(77), Some(5))
+        .unwrap();
+    server.host.register_session(77);
+    server.send(&packet).await;
+
+    let req = incoming.recv().await.unwrap();
+    assert_eq!(req.name, "echo");
+    assert_eq!(req.ud, Some(5));
+    assert_eq!(decode_echo(&sproto, &req.body), "ping");
+    let responder = req.responder.unwrap();
+    client
+        .respond(&responder, &encode_echo(&sproto, "pong"))
+        .unwrap();
+
+    match server.recv().await {
+        DispatchResult::Response { session, body, .. } => {
+            assert_eq!(session, 77);
+            assert_eq!(decode_echo(&sproto, &body), "pong");
+        }
+        _ => panic!("expected Response"),
+    }
+}
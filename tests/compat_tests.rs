@@ -0,0 +1,243 @@
+//! Schema compatibility checker tests.
+//!
+//! Each test parses an old and a new schema and checks the changes
+//! `compat::check` reports between them.
+
+use sproto::compat::{self, Change};
+use sproto::parser;
+
+const BASE: &str = r#"
+.Phone {
+    number 0 : string
+    type 1 : integer
+}
+
+.Person {
+    name 0 : string
+    age 1 : integer
+    balance 2 : integer(2)
+    phones 3 : *Phone(number)
+}
+
+.LoginRequest {
+    name 0 : string
+}
+
+.LoginResponse {
+    ok 0 : boolean
+}
+
+login 1 {
+    request LoginRequest
+    response LoginResponse
+}
+
+logout 2 {
+    response nil
+}
+"#;
+
+/// Check `BASE` against `BASE` with `edit` applied.
+fn check_edit(edit: impl FnOnce(String) -> String) -> compat::Report {
+    let old = parser::parse(BASE).unwrap();
+    let new = parser::parse(&edit(BASE.to_string())).unwrap();
+    compat::check(&old, &new)
+}
+
+fn replace(from: &'static str, to: &'static str) -> impl FnOnce(String) -> String {
+    move |s| {
+        assert!(s.contains(from), "{:?} not in schema", from);
+        s.replacen(from, to, 1)
+    }
+}
+
+// =============================================================================
+// Compatible changes
+// =============================================================================
+
+#[test]
+fn test_compat_identical() {
+    let report = check_edit(|s| s);
+    assert!(report.is_compatible());
+    assert!(report.changes.is_empty());
+    assert_eq!(report.to_string(), "");
+}
+
+#[test]
+fn test_compat_additions() {
+    let report = check_edit(|s| {
+        s.replace(
+            "phones 3 : *Phone(number)",
+            "phones 3 : *Phone(number)\n    email 4 : string",
+        ) + ".Empty {}\nping 3 {}\n"
+    });
+    assert!(report.is_compatible());
+    assert_eq!(
+        report.changes,
+        vec![
+            Change::FieldAdded {
+                type_name: "Person".into(),
+                field: "email".into(),
+                tag: 4,
+            },
+            Change::TypeAdded {
+                name: "Empty".into()
+            },
+            Change::ProtocolAdded {
+                name: "ping".into(),
+                tag: 3,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_compat_removals_and_renames_are_safe() {
+    let report = check_edit(|s| {
+        let s = s.replace(
+            "name 0 : string\n    age 1 : integer",
+            "full_name 0 : string",
+        );
+        s.replace("logout 2", "signout 2")
+    });
+    assert!(report.is_compatible(), "{}", report);
+    assert_eq!(
+        report.to_string(),
+        "safe: Person (tag 0): field renamed from name to full_name\n\
+         safe: Person.age (tag 1) removed\n\
+         safe: protocol tag 2: renamed from logout to signout\n"
+    );
+    assert_eq!(report.safe().count(), 3);
+}
+
+// =============================================================================
+// Breaking field changes
+// =============================================================================
+
+#[test]
+fn test_compat_field_type_changed() {
+    let report = check_edit(replace("age 1 : integer", "age 1 : string"));
+    assert!(!report.is_compatible());
+    assert_eq!(
+        report.changes,
+        vec![Change::FieldTypeChanged {
+            type_name: "Person".into(),
+            field: "age".into(),
+            tag: 1,
+            old: "integer".into(),
+            new: "string".into(),
+        }]
+    );
+
+    // A different struct under the same tag.
+    let report = check_edit(replace(
+        "phones 3 : *Phone(number)",
+        "phones 3 : *LoginRequest(name)",
+    ));
+    assert_eq!(
+        report.to_string(),
+        "breaking: Person.phones (tag 3): type changed from *Phone(number) to *LoginRequest(name)\n"
+    );
+}
+
+#[test]
+fn test_compat_array_flip() {
+    let report = check_edit(replace(
+        "name 0 : string\n    age",
+        "name 0 : *string\n    age",
+    ));
+    assert!(matches!(
+        report.breaking().collect::<Vec<_>>()[..],
+        [Change::FieldArrayChanged { ref old, ref new, tag: 0, .. }] if old == "string" && new == "*string"
+    ));
+}
+
+#[test]
+fn test_compat_map_key_changes() {
+    let report = check_edit(replace("*Phone(number)", "*Phone(type)"));
+    assert_eq!(
+        report.changes,
+        vec![Change::KeyTagChanged {
+            type_name: "Person".into(),
+            field: "phones".into(),
+            tag: 3,
+            old: 0,
+            new: 1,
+        }]
+    );
+
+    // `*T(key)` read as a `*T()` pair map.
+    let report = check_edit(replace("*Phone(number)", "*Phone()"));
+    assert_eq!(
+        report.to_string(),
+        "breaking: Person.phones (tag 3): type changed from *Phone(number) to *Phone()\n"
+    );
+}
+
+#[test]
+fn test_compat_decimal_precision_changed() {
+    let report = check_edit(replace("integer(2)", "integer(3)"));
+    assert_eq!(
+        report.changes,
+        vec![Change::DecimalPrecisionChanged {
+            type_name: "Person".into(),
+            field: "balance".into(),
+            tag: 2,
+            old: 100,
+            new: 1000,
+        }]
+    );
+}
+
+#[test]
+fn test_compat_field_moved_to_new_tag() {
+    let report = check_edit(replace("balance 2 : integer(2)", "balance 5 : integer(2)"));
+    assert!(!report.is_compatible());
+    assert_eq!(
+        report.to_string(),
+        "breaking: Person.balance: tag changed from 2 to 5\n"
+    );
+}
+
+// =============================================================================
+// Breaking protocol changes
+// =============================================================================
+
+#[test]
+fn test_compat_protocol_removed_and_retagged() {
+    let report = check_edit(|s| {
+        let s = s.replace("login 1", "login 3");
+        let end = s.find("logout 2").unwrap();
+        s[..end].to_string()
+    });
+    assert_eq!(
+        report.changes,
+        vec![
+            Change::ProtocolTagChanged {
+                name: "login".into(),
+                old: 1,
+                new: 3,
+            },
+            Change::ProtocolRemoved {
+                name: "logout".into(),
+                tag: 2,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_compat_request_response_swapped() {
+    let report = check_edit(|s| {
+        s.replace("request LoginRequest", "request LoginResponse")
+            .replace("response LoginResponse", "response LoginRequest")
+            .replace("response nil", "")
+    });
+    assert!(!report.is_compatible());
+    assert_eq!(
+        report.to_string(),
+        "breaking: protocol login: request changed from LoginRequest to LoginResponse\n\
+         breaking: protocol login: response changed from LoginResponse to LoginRequest\n\
+         breaking: protocol logout: response changed from nil to none\n"
+    );
+}
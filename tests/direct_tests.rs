@@ -6,9 +6,14 @@
 //! - Full type coverage (all scalar and array types)
 //! - Edge cases and error handling
 
+use std::collections::{BTreeMap, HashMap};
+
 use sproto::binary_schema;
 use sproto::codec::decoder::{DecodedField, StructDecoder};
 use sproto::codec::encoder::StructEncoder;
+use sproto::codec::{DecodeLimits, MapKey};
+use sproto::error::{DecodeError, EncodeError};
+use sproto::types::FieldType;
 
 fn testdata(name: &str) -> Vec<u8> {
     let path = format!("{}/tests/testdata/{}", env!("CARGO_MANIFEST_DIR"), name);
@@ -248,6 +253,16 @@ fn test_direct_encode_fixed_point() {
     );
 }
 
+#[test]
+fn test_direct_encode_fixed_point_decimal() {
+    let sproto = load_sproto();
+    let encoded = direct_encode(&sproto, "Person", |enc| enc.set_decimal(5, 1.82));
+    assert_eq!(
+        hexdump(&encoded),
+        hexdump(&testdata("fixed_point_encoded.bin"))
+    );
+}
+
 #[test]
 fn test_direct_encode_full() {
     let sproto = load_sproto();
@@ -549,6 +564,7 @@ fn test_direct_decode_fixed_point() {
 
     let fpn_field = fields.iter().find(|f| f.tag() == 5).unwrap();
     assert_eq!(fpn_field.as_integer().unwrap(), 182);
+    assert_eq!(fpn_field.as_decimal().unwrap(), 1.82);
 }
 
 #[test]
@@ -1077,6 +1093,222 @@ fn test_direct_roundtrip_nested_struct() {
     assert!(dec.next_field().unwrap().is_none());
 }
 
+// =============================================================================
+// Fixed-point decimals: integer(N)
+// =============================================================================
+
+const DECIMAL_SCHEMA: &str = r#"
+.Invoice {
+    total 0 : integer(2)
+    lines 1 : *integer(3)
+    count 2 : integer
+}
+"#;
+
+#[test]
+fn test_direct_decimal_rounding() {
+    // Rounds half away from zero, like C's round().
+    let schema = sproto::parser::parse(DECIMAL_SCHEMA).unwrap();
+    let st = schema.get_type("Invoice").unwrap();
+    for (value, raw) in [
+        (0.125, 13),
+        (-0.125, -13),
+        (19.99, 1999),
+        (0.004, 0),
+        (-0.005, -1),
+        (1e12, 100_000_000_000_000),
+    ] {
+        let encoded = direct_encode(&schema, "Invoice", |enc| enc.set_decimal(0, value));
+        let mut dec = StructDecoder::new(&schema, st, &encoded).unwrap();
+        let f = dec.next_field().unwrap().unwrap();
+        assert_eq!(f.as_integer().unwrap(), raw, "{}", value);
+        assert_eq!(f.as_decimal().unwrap(), raw as f64 / 100.0, "{}", value);
+    }
+}
+
+#[test]
+fn test_direct_decimal_array_roundtrip() {
+    let schema = sproto::parser::parse(DECIMAL_SCHEMA).unwrap();
+    let st = schema.get_type("Invoice").unwrap();
+    let encoded = direct_encode(&schema, "Invoice", |enc| {
+        enc.set_decimal_array(1, &[1.5, -0.0005, 2.0004])
+    });
+    let mut dec = StructDecoder::new(&schema, st, &encoded).unwrap();
+    let f = dec.next_field().unwrap().unwrap();
+    assert_eq!(f.as_integer_array().unwrap(), vec![1500, -1, 2000]);
+    assert_eq!(f.as_decimal_array().unwrap(), vec![1.5, -0.001, 2.0]);
+}
+
+#[test]
+fn test_direct_decimal_on_non_decimal_field() {
+    let schema = sproto::parser::parse(DECIMAL_SCHEMA).unwrap();
+    let st = schema.get_type("Invoice").unwrap();
+    let mut buf = Vec::new();
+    let mut enc = StructEncoder::new(&schema, st, &mut buf);
+    assert_eq!(
+        enc.set_decimal(2, 1.5).unwrap_err().to_string(),
+        "type mismatch for field 'count': expected integer(N) decimal, got integer"
+    );
+    enc.set_integer(2, 3).unwrap();
+    enc.finish();
+
+    let mut dec = StructDecoder::new(&schema, st, &buf).unwrap();
+    let f = dec.next_field().unwrap().unwrap();
+    assert_eq!(
+        f.as_decimal().unwrap_err().to_string(),
+        "invalid data: field 'count' is not an integer(N) decimal"
+    );
+    assert!(f.as_decimal_array().is_err());
+}
+
+// =============================================================================
+// Maps: *T(key) and *T()
+// =============================================================================
+
+const MAP_SCHEMA: &str = r#"
+.Item {
+    id 0 : integer
+    name 1 : string
+}
+
+.Inventory {
+    items 0 : *Item(id)
+    counts 1 : *Count()
+    list 2 : *Item
+
+    .Count {
+        name 0 : string
+        count 1 : integer
+    }
+}
+"#;
+
+fn inventory_field<'a>(dec: &mut StructDecoder<'a>, tag: u16) -> DecodedField<'a> {
+    decode_fields(dec)
+        .into_iter()
+        .find(|f| f.tag() == tag)
+        .unwrap()
+}
+
+#[test]
+fn test_direct_struct_map_roundtrip() {
+    let schema = sproto::parser::parse(MAP_SCHEMA).unwrap();
+    let items = BTreeMap::from([(1, "sword"), (7, "shield")]);
+    let encoded = direct_encode(&schema, "Inventory", |enc| {
+        enc.encode_struct_map(0, &items, |e, (id, name)| {
+            e.set_integer(0, *id)?;
+            e.set_string(1, name)
+        })
+    });
+
+    let st = schema.get_type("Inventory").unwrap();
+    let mut dec = StructDecoder::new(&schema, st, &encoded).unwrap();
+    let map = inventory_field(&mut dec, 0).as_struct_map().unwrap();
+    assert_eq!(map.len(), 2);
+    let mut shield = map[&MapKey::Integer(7)].clone();
+    let fields = decode_fields(&mut shield);
+    assert_eq!(fields[1].as_string().unwrap(), "shield");
+}
+
+#[test]
+fn test_direct_pair_map_roundtrip() {
+    let schema = sproto::parser::parse(MAP_SCHEMA).unwrap();
+    let counts = HashMap::from([("arrow".to_string(), 20i64), ("potion".to_string(), 3)]);
+    let encoded = direct_encode(&schema, "Inventory", |enc| {
+        enc.encode_pair_map(1, &counts, |e, value_tag, count| {
+            e.set_integer(value_tag, *count)
+        })
+    });
+
+    let st = schema.get_type("Inventory").unwrap();
+    let mut dec = StructDecoder::new(&schema, st, &encoded).unwrap();
+    let map = inventory_field(&mut dec, 1).as_pair_map().unwrap();
+    let decoded: HashMap<String, i64> = map
+        .into_iter()
+        .map(|(k, v)| match k {
+            MapKey::String(name) => (name.to_string(), v.as_integer().unwrap()),
+            other => panic!("unexpected key {:?}", other),
+        })
+        .collect();
+    assert_eq!(decoded, counts);
+}
+
+#[test]
+fn test_direct_pair_map_wire_layout() {
+    // A `*T()` map is a plain array of two-field structs on the wire.
+    let schema = sproto::parser::parse(MAP_SCHEMA).unwrap();
+    let from_map = direct_encode(&schema, "Inventory", |enc| {
+        enc.encode_pair_map(1, [("arrow", 20)], |e, tag, v| e.set_integer(tag, v))
+    });
+    let from_array = direct_encode(&schema, "Inventory", |enc| {
+        enc.encode_struct_array(1, |arr| {
+            arr.encode_element(|e| {
+                e.set_string(0, "arrow")?;
+                e.set_integer(1, 20)
+            })
+        })
+    });
+    assert_eq!(hexdump(&from_map), hexdump(&from_array));
+}
+
+#[test]
+fn test_direct_map_duplicate_key_last_wins() {
+    let schema = sproto::parser::parse(MAP_SCHEMA).unwrap();
+    let encoded = direct_encode(&schema, "Inventory", |enc| {
+        enc.encode_struct_map(0, ["old", "new"], |e, name| {
+            e.set_integer(0, 1)?;
+            e.set_string(1, name)
+        })
+    });
+    let st = schema.get_type("Inventory").unwrap();
+    let mut dec = StructDecoder::new(&schema, st, &encoded).unwrap();
+    let map = inventory_field(&mut dec, 0).as_struct_map().unwrap();
+    let mut item = map[&MapKey::Integer(1)].clone();
+    assert_eq!(decode_fields(&mut item)[1].as_string().unwrap(), "new");
+}
+
+#[test]
+fn test_direct_map_errors() {
+    let schema = sproto::parser::parse(MAP_SCHEMA).unwrap();
+    let st = schema.get_type("Inventory").unwrap();
+    let mut buf = Vec::new();
+    let mut enc = StructEncoder::new(&schema, st, &mut buf);
+    let err = enc
+        .encode_pair_map(0, [(1, 2)], |e, tag, v| e.set_integer(tag, v))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type mismatch for field 'items': expected *T() map, got *Item"
+    );
+    let err = enc.encode_struct_map(2, [()], |_, _| Ok(())).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type mismatch for field 'list': expected *T(key) map, got *Item"
+    );
+    let err = enc
+        .encode_pair_map(1, [(true, 2)], |e, tag, v| e.set_integer(tag, v))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type mismatch for field 'name': expected string, got boolean"
+    );
+
+    // An element without its key field cannot be placed in the map.
+    let encoded = direct_encode(&schema, "Inventory", |enc| {
+        enc.encode_struct_map(0, ["nameless"], |e, name| e.set_string(1, name))
+    });
+    let mut dec = StructDecoder::new(&schema, st, &encoded).unwrap();
+    let items = inventory_field(&mut dec, 0);
+    assert!(items.as_pair_map().is_err());
+    match items.as_struct_map() {
+        Err(e) => assert_eq!(
+            e.to_string(),
+            "invalid data: map element in field 'items' is missing key tag 0"
+        ),
+        Ok(_) => panic!("expected a missing key error"),
+    }
+}
+
 // =============================================================================
 // Edge cases
 // =============================================================================
@@ -1276,6 +1508,186 @@ fn test_direct_encode_unknown_tag() {
     assert!(result.is_err());
 }
 
+// =============================================================================
+// Decode limits
+// =============================================================================
+
+const LIMITS_SCHEMA: &str = r#"
+.Node {
+    name 0 : string
+    child 1 : Node
+    children 2 : *Node
+    tags 3 : *string
+    scores 4 : *integer
+    flags 5 : *boolean
+}
+"#;
+
+fn limits_schema() -> sproto::Sproto {
+    sproto::parser::parse(LIMITS_SCHEMA).unwrap()
+}
+
+/// Helper: encode `depth` nodes, each the `child` of the one before.
+fn encode_chain(enc: &mut StructEncoder, depth: usize) -> Result<(), EncodeError> {
+    if depth > 0 {
+        enc.encode_nested(1, |child| encode_chain(child, depth - 1))?;
+    }
+    Ok(())
+}
+
+/// Helper: read every field of `dec` with the direct accessors.
+fn walk(dec: &mut StructDecoder) -> Result<(), DecodeError> {
+    while let Some(f) = dec.next_field()? {
+        match (&f.field().field_type, f.field().is_array) {
+            (FieldType::Struct(_), false) => walk(&mut f.as_struct()?)?,
+            (FieldType::Struct(_), true) => {
+                for elem in f.as_struct_iter()? {
+                    walk(&mut elem?)?;
+                }
+            }
+            (FieldType::String, true) => {
+                f.as_string_array()?;
+            }
+            (FieldType::Integer, true) => {
+                f.as_integer_array()?;
+            }
+            (FieldType::Boolean, true) => {
+                f.as_bool_array();
+            }
+            (FieldType::String, false) => {
+                f.as_string()?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn walk_with_limits(
+    sproto: &sproto::Sproto,
+    data: &[u8],
+    limits: DecodeLimits,
+) -> Result<(), DecodeError> {
+    let st = sproto.get_type("Node").unwrap();
+    walk(&mut StructDecoder::with_limits(sproto, st, data, limits)?)
+}
+
+fn limits(f: impl FnOnce(&mut DecodeLimits)) -> DecodeLimits {
+    let mut limits = DecodeLimits::unlimited();
+    f(&mut limits);
+    limits
+}
+
+#[test]
+fn test_direct_decode_depth_limit() {
+    let schema = limits_schema();
+    let deep = direct_encode(&schema, "Node", |enc| encode_chain(enc, 200));
+
+    // The default depth limit stops a deeply nested message.
+    let st = schema.get_type("Node").unwrap();
+    let err = walk(&mut StructDecoder::new(&schema, st, &deep).unwrap()).unwrap_err();
+    assert!(matches!(err, DecodeError::DepthExceeded { max: 128 }));
+    assert_eq!(
+        err.to_string(),
+        "struct nesting exceeds the depth limit of 128"
+    );
+    walk_with_limits(&schema, &deep, DecodeLimits::unlimited()).unwrap();
+
+    let chain = direct_encode(&schema, "Node", |enc| encode_chain(enc, 3));
+    walk_with_limits(&schema, &chain, limits(|l| l.max_depth = 3)).unwrap();
+    let err = walk_with_limits(&schema, &chain, limits(|l| l.max_depth = 2)).unwrap_err();
+    assert!(matches!(err, DecodeError::DepthExceeded { max: 2 }));
+
+    // Struct array elements are one level down.
+    let children = direct_encode(&schema, "Node", |enc| {
+        enc.encode_struct_array(2, |arr| arr.encode_element(|_| Ok(())))
+    });
+    walk_with_limits(&schema, &children, limits(|l| l.max_depth = 1)).unwrap();
+    let err = walk_with_limits(&schema, &children, limits(|l| l.max_depth = 0)).unwrap_err();
+    assert!(matches!(err, DecodeError::DepthExceeded { max: 0 }));
+}
+
+#[test]
+fn test_direct_decode_array_limit() {
+    let schema = limits_schema();
+    let cases = [
+        (
+            "children",
+            direct_encode(&schema, "Node", |enc| {
+                enc.encode_struct_array(2, |arr| {
+                    for _ in 0..3 {
+                        arr.encode_element(|_| Ok(()))?;
+                    }
+                    Ok(())
+                })
+            }),
+        ),
+        (
+            "tags",
+            direct_encode(&schema, "Node", |enc| {
+                enc.set_string_array(3, &["a", "b", "c"])
+            }),
+        ),
+        (
+            "scores",
+            direct_encode(&schema, "Node", |enc| {
+                enc.set_integer_array(4, &[1, 2, i64::MAX])
+            }),
+        ),
+        (
+            "flags",
+            direct_encode(&schema, "Node", |enc| {
+                enc.set_bool_array(5, &[true, false, true])
+            }),
+        ),
+    ];
+    for (name, data) in &cases {
+        walk_with_limits(&schema, data, limits(|l| l.max_array_len = 3)).unwrap();
+        let err = walk_with_limits(&schema, data, limits(|l| l.max_array_len = 2)).unwrap_err();
+        assert!(
+            matches!(&err, DecodeError::TooManyElements { field, max: 2 } if field == name),
+            "{}: {:?}",
+            name,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_direct_decode_string_limit() {
+    let schema = limits_schema();
+    let name = direct_encode(&schema, "Node", |enc| enc.set_string(0, "hello"));
+    walk_with_limits(&schema, &name, limits(|l| l.max_string_len = 5)).unwrap();
+    let err = walk_with_limits(&schema, &name, limits(|l| l.max_string_len = 4)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "field 'name' of 5 bytes exceeds the 4-byte string limit"
+    );
+
+    let tags = direct_encode(&schema, "Node", |enc| {
+        enc.set_string_array(3, &["ok", "too long"])
+    });
+    let err = walk_with_limits(&schema, &tags, limits(|l| l.max_string_len = 4)).unwrap_err();
+    assert!(matches!(
+        err,
+        DecodeError::StringTooLong { ref field, len: 8, max: 4 } if field == "tags"
+    ));
+}
+
+#[test]
+fn test_direct_decode_field_limit() {
+    let schema = limits_schema();
+    // Two fields in the message and one in its child.
+    let data = direct_encode(&schema, "Node", |enc| {
+        enc.set_string(0, "root")?;
+        enc.encode_nested(1, |child| child.set_string(0, "leaf"))
+    });
+    walk_with_limits(&schema, &data, limits(|l| l.max_fields = 3)).unwrap();
+    let err = walk_with_limits(&schema, &data, limits(|l| l.max_fields = 2)).unwrap_err();
+    assert!(matches!(err, DecodeError::TooManyFields { max: 2 }));
+    assert_eq!(err.to_string(), "message exceeds the limit of 2 fields");
+}
+
 // =============================================================================
 // Encode->decode roundtrip matching binary fixtures
 // =============================================================================
@@ -1,6 +1,10 @@
-//! Cross-validation: pack/unpack with C-generated fixture files.
+//! Cross-validation: pack/unpack with C-generated fixture files, plus the
+//! buffer-reusing `pack_into` / `unpack_into` and their size helpers, and
+//! the streaming `PackWriter` / `UnpackReader`.
 
-use sproto::pack;
+use std::io::{self, Read, Write};
+
+use sproto::pack::{self, PackWriter, UnpackReader};
 
 fn testdata(name: &str) -> Vec<u8> {
     let path = format!("{}/tests/testdata/{}", env!("CARGO_MANIFEST_DIR"), name);
@@ -29,6 +33,11 @@ macro_rules! pack_test {
                 "pack mismatch for {}",
                 $encoded,
             );
+            assert!(packed.len() <= pack::packed_len_bound(encoded.len()));
+
+            let mut out = vec![0xAA];
+            pack::pack_into(&encoded, &mut out);
+            assert_eq!(&out[1..], &expected_packed[..]);
         }
 
         #[test]
@@ -44,6 +53,11 @@ macro_rules! pack_test {
                 "unpack mismatch for {}",
                 $packed,
             );
+            assert_eq!(pack::unpacked_len(&packed).unwrap(), unpacked.len());
+
+            let mut out = vec![0xAA];
+            pack::unpack_into(&packed, &mut out).unwrap();
+            assert_eq!(&out[1..], &unpacked[..]);
         }
     };
 }
@@ -120,3 +134,303 @@ pack_test!(
     "full_encoded.bin",
     "full_packed.bin"
 );
+
+// ============================================================================
+// Buffer reuse and size helpers
+// ============================================================================
+
+/// Inputs that stress the packed size: full words, 0xFF runs broken after
+/// one word, runs longer than 256 words, and partial trailing words.
+fn worst_case_inputs() -> Vec<Vec<u8>> {
+    let mut inputs = vec![vec![], vec![1], vec![0xFF; 7], vec![0xFF; 8], vec![0xFF; 9]];
+    for words in [1usize, 255, 256, 257, 512, 1000] {
+        inputs.push(vec![0x8a; words * 8]);
+        inputs.push(vec![0x8a; words * 8 - 3]);
+        // Full word, then a word with 5 non-zero bytes, repeated.
+        let mut alternating = Vec::new();
+        for _ in 0..words {
+            alternating.extend_from_slice(&[1; 8]);
+            alternating.extend_from_slice(&[1, 1, 1, 1, 1, 0, 0, 0]);
+        }
+        inputs.push(alternating);
+        // Words with exactly 7 non-zero bytes.
+        inputs.push([1, 1, 1, 1, 1, 1, 1, 0].repeat(words));
+    }
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    for len in [13usize, 100, 4096] {
+        let random = (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                // Mostly non-zero, with some zero bytes.
+                if seed.is_multiple_of(5) {
+                    0
+                } else {
+                    seed as u8 | 1
+                }
+            })
+            .collect();
+        inputs.push(random);
+    }
+    inputs
+}
+
+#[test]
+fn test_packed_len_bound() {
+    assert_eq!(pack::packed_len_bound(0), 0);
+    for input in worst_case_inputs() {
+        let packed = pack::pack(&input);
+        assert!(
+            packed.len() <= pack::packed_len_bound(input.len()),
+            "packed {} bytes into {}, bound {}",
+            input.len(),
+            packed.len(),
+            pack::packed_len_bound(input.len())
+        );
+    }
+    // The bound is tight for a single full word.
+    assert_eq!(pack::pack(&[0xFF; 8]).len(), pack::packed_len_bound(8));
+}
+
+#[test]
+fn test_pack_into_reuses_buffer() {
+    let mut buf = Vec::new();
+    for input in worst_case_inputs() {
+        buf.clear();
+        pack::pack_into(&input, &mut buf);
+        assert_eq!(buf, pack::pack(&input));
+    }
+    // Reserving the bound means the largest input set the final capacity.
+    let capacity = buf.capacity();
+    for input in worst_case_inputs() {
+        buf.clear();
+        pack::pack_into(&input, &mut buf);
+        assert_eq!(buf.capacity(), capacity);
+    }
+}
+
+#[test]
+fn test_unpack_into_and_unpacked_len() {
+    let mut buf = Vec::new();
+    for input in worst_case_inputs() {
+        let packed = pack::pack(&input);
+        let len = pack::unpacked_len(&packed).unwrap();
+        assert_eq!(len, input.len().div_ceil(8) * 8);
+        buf.clear();
+        pack::unpack_into(&packed, &mut buf).unwrap();
+        assert_eq!(buf.len(), len);
+        assert_eq!(&buf[..input.len()], &input[..]);
+    }
+}
+
+#[test]
+fn test_unpack_into_error_leaves_buffer() {
+    let mut buf = vec![1, 2, 3];
+    for bad in [&[0xFF][..], &[0xFF, 0x00, 1, 2], &[0x00, 0x03, 0x11]] {
+        assert!(pack::unpacked_len(bad).is_err());
+        assert!(pack::unpack_into(bad, &mut buf).is_err());
+        assert_eq!(buf, vec![1, 2, 3]);
+    }
+}
+
+// ============================================================================
+// Streaming pack/unpack
+// ============================================================================
+
+const CHUNK_SIZES: [usize; 8] = [1, 2, 3, 7, 8, 9, 64, 1000];
+
+fn fixture_inputs() -> Vec<Vec<u8>> {
+    let mut inputs = worst_case_inputs();
+    inputs.push(testdata("full_encoded.bin"));
+    inputs.push(testdata("big_int_array_encoded.bin"));
+    inputs
+}
+
+/// A reader that returns at most `chunk` bytes per `read`.
+struct Trickle<'a> {
+    data: &'a [u8],
+    chunk: usize,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.chunk.min(buf.len()).min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+fn stream_pack(input: &[u8], chunk: usize) -> Vec<u8> {
+    let mut writer = PackWriter::new(Vec::new());
+    for part in input.chunks(chunk) {
+        writer.write_all(part).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+fn stream_unpack(packed: &[u8], in_chunk: usize, out_chunk: usize) -> io::Result<Vec<u8>> {
+    let mut reader = UnpackReader::new(Trickle {
+        data: packed,
+        chunk: in_chunk,
+    });
+    let mut out = Vec::new();
+    let mut buf = vec![0u8; out_chunk];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok(out);
+        }
+        out.extend_from_slice(&buf[..n]);
+    }
+}
+
+#[test]
+fn test_pack_writer_matches_pack() {
+    for input in fixture_inputs() {
+        let expected = pack::pack(&input);
+        for chunk in CHUNK_SIZES {
+            assert_eq!(
+                stream_pack(&input, chunk),
+                expected,
+                "{} bytes written in chunks of {}",
+                input.len(),
+                chunk
+            );
+        }
+    }
+}
+
+#[test]
+fn test_unpack_reader_matches_unpack() {
+    for input in fixture_inputs() {
+        let packed = pack::pack(&input);
+        let expected = pack::unpack(&packed).unwrap();
+        for in_chunk in CHUNK_SIZES {
+            for out_chunk in [1, 5, 8, 4096] {
+                assert_eq!(
+                    stream_unpack(&packed, in_chunk, out_chunk).unwrap(),
+                    expected,
+                    "{} packed bytes, read in {} / {}",
+                    packed.len(),
+                    in_chunk,
+                    out_chunk
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pack_writer_flush_and_drop() {
+    // flush() writes finished words but holds back the partial word.
+    let mut writer = PackWriter::new(Vec::new());
+    writer
+        .write_all(&[0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0])
+        .unwrap();
+    writer.flush().unwrap();
+    assert_eq!(writer.get_ref(), &vec![0x00]);
+    writer.write_all(&[6]).unwrap();
+    assert_eq!(writer.finish().unwrap(), vec![0x00, 0x09, 5, 6]);
+
+    // Dropping an unfinished writer finishes it.
+    let mut sink = Vec::new();
+    {
+        let mut writer = PackWriter::new(&mut sink);
+        writer.write_all(&[0x8a; 20]).unwrap();
+    }
+    assert_eq!(sink, pack::pack(&[0x8a; 20]));
+}
+
+/// Accepts at most `chunk` bytes per call and fails every other call until
+/// `failures` errors have been returned.
+struct Flaky {
+    data: Vec<u8>,
+    chunk: usize,
+    failures: usize,
+    calls: usize,
+}
+
+impl Write for Flaky {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.calls += 1;
+        if self.failures > 0 && self.calls.is_multiple_of(2) {
+            self.failures -= 1;
+            return Err(io::Error::other("flaky"));
+        }
+        let n = buf.len().min(self.chunk);
+        self.data.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_pack_writer_retries_after_inner_error() {
+    let input = testdata("full_encoded.bin").repeat(20);
+    let mut writer = PackWriter::new(Flaky {
+        data: Vec::new(),
+        chunk: 5,
+        failures: 50,
+        calls: 0,
+    });
+    let mut errors = 0;
+    // Retrying the same buffer after an error must not duplicate output.
+    for part in input.chunks(13) {
+        let mut rest = part;
+        while !rest.is_empty() {
+            match writer.write(rest) {
+                Ok(n) => rest = &rest[n..],
+                Err(_) => errors += 1,
+            }
+        }
+    }
+    while writer.flush().is_err() {
+        errors += 1;
+    }
+    assert!(errors > 0);
+    assert_eq!(writer.get_ref().failures, 0);
+    let flaky = writer.finish().unwrap();
+    assert_eq!(flaky.data, pack::pack(&input));
+}
+
+#[test]
+fn test_pack_writer_empty() {
+    assert!(PackWriter::new(Vec::new()).finish().unwrap().is_empty());
+    let mut reader = UnpackReader::new(&[][..]);
+    assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+}
+
+#[test]
+fn test_stream_roundtrip_with_io_copy() {
+    let input = testdata("full_encoded.bin").repeat(50);
+    let mut writer = PackWriter::new(Vec::new());
+    io::copy(&mut &input[..], &mut writer).unwrap();
+    let packed = writer.finish().unwrap();
+
+    let mut unpacked = Vec::new();
+    UnpackReader::new(&packed[..])
+        .read_to_end(&mut unpacked)
+        .unwrap();
+    assert_eq!(&unpacked[..input.len()], &input[..]);
+    assert!(unpacked[input.len()..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_unpack_reader_truncated() {
+    let packed = pack::pack(&[0x8a; 40]);
+    for cut in [1, 5, packed.len() - 1] {
+        let err = stream_unpack(&packed[..cut], 3, 16).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "cut at {}", cut);
+    }
+    let err = stream_unpack(&[0x03, 0x11], 8, 8).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        err.to_string(),
+        "invalid packed data: truncated packed data in normal segment"
+    );
+}
@@ -0,0 +1,336 @@
+//! Text schema parser tests.
+//!
+//! The parsed schemas are cross-validated against binary schemas compiled
+//! from the same source by the C/Lua toolchain.
+
+use sproto::binary_schema;
+use sproto::parser;
+use sproto::types::{FieldType, Sproto};
+
+fn testdata(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/testdata/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+/// Same source as the RPC schema in `testdata/generate.lua`.
+const RPC_SCHEMA: &str = r#"
+.package {
+    type 0 : integer
+    session 1 : integer
+    ud 2 : integer
+}
+
+.foobar_request {
+    what 0 : string
+}
+
+.foobar_response {
+    ok 0 : boolean
+}
+
+.foo_response {
+    ok 0 : boolean
+}
+
+foobar 1 {
+    request foobar_request
+    response foobar_response
+}
+
+foo 2 {
+    response foo_response
+}
+
+bar 3 {
+    response nil
+}
+
+blackhole 4 {
+}
+"#;
+
+/// Assert two schemas describe the same types and protocols, in the same order.
+fn assert_same_schema(actual: &Sproto, expected: &Sproto) {
+    assert_eq!(actual.types_list.len(), expected.types_list.len());
+    for (a, e) in actual.types_list.iter().zip(&expected.types_list) {
+        assert_eq!(a.name, e.name);
+        assert_eq!(a.fields.len(), e.fields.len(), "field count of {}", a.name);
+        for (af, ef) in a.fields.iter().zip(&e.fields) {
+            let ctx = format!("{}.{}", a.name, ef.name);
+            assert_eq!(af.name, ef.name, "{}", ctx);
+            assert_eq!(af.tag, ef.tag, "{}", ctx);
+            assert_eq!(af.field_type, ef.field_type, "{}", ctx);
+            assert_eq!(af.is_array, ef.is_array, "{}", ctx);
+            assert_eq!(af.key_tag, ef.key_tag, "{}", ctx);
+            assert_eq!(af.is_map, ef.is_map, "{}", ctx);
+            assert_eq!(af.decimal_precision, ef.decimal_precision, "{}", ctx);
+        }
+        assert_eq!(a.base_tag, e.base_tag);
+        assert_eq!(a.maxn, e.maxn);
+    }
+
+    assert_eq!(actual.protocols.len(), expected.protocols.len());
+    for (a, e) in actual.protocols.iter().zip(&expected.protocols) {
+        assert_eq!(a.name, e.name);
+        assert_eq!(a.tag, e.tag);
+        assert_eq!(a.request, e.request, "request of {}", a.name);
+        assert_eq!(a.response, e.response, "response of {}", a.name);
+        assert_eq!(a.confirm, e.confirm, "confirm of {}", a.name);
+    }
+}
+
+// =============================================================================
+// Cross-validation with C-compiled binary schemas
+// =============================================================================
+
+#[test]
+fn test_parse_matches_binary_schema() {
+    let text = String::from_utf8(testdata("schema.sproto")).unwrap();
+    let parsed = parser::parse(&text).unwrap();
+    let loaded = binary_schema::load_binary(&testdata("schema.bin")).unwrap();
+    assert_same_schema(&parsed, &loaded);
+}
+
+#[test]
+fn test_parse_matches_rpc_binary_schema() {
+    let parsed = parser::parse(RPC_SCHEMA).unwrap();
+    let loaded = binary_schema::load_binary(&testdata("rpc_schema.bin")).unwrap();
+    assert_same_schema(&parsed, &loaded);
+}
+
+#[test]
+fn test_parsed_and_loaded_schemas_validate() {
+    let text = String::from_utf8(testdata("schema.sproto")).unwrap();
+    parser::parse(&text).unwrap().validate().unwrap();
+    parser::parse(RPC_SCHEMA).unwrap().validate().unwrap();
+    for name in [
+        "schema.bin",
+        "rpc_schema.bin",
+        "lua_addressbook_schema.bin",
+        "lua_data_schema.bin",
+        "lua_human_schema.bin",
+        "map_schema.bin",
+    ] {
+        let loaded = binary_schema::load_binary(&testdata(name)).unwrap();
+        assert_eq!(loaded.validate(), Ok(()), "{}", name);
+    }
+}
+
+// =============================================================================
+// Grammar coverage
+// =============================================================================
+
+#[test]
+fn test_parse_full_grammar() {
+    let schema = parser::parse(
+        r#"
+        # Address book, as in the sproto README
+        .Person {
+            name 0 : string     # trailing comment
+            id 1 : integer
+            email 2 : string
+
+            .PhoneNumber {
+                number 0 : string
+                type 1 : integer
+            }
+
+            phone 3 : *PhoneNumber(number)
+            balance 4 : integer(2)
+            avatar 5 : binary
+            attrs 6 : *Attr()
+            ratio 7 : double
+        }
+
+        .Attr {
+            key 0 : string
+            value 1 : integer
+        }
+
+        .AddressBook {
+            person 0 : *Person(id)
+            others 1 : *Person
+        }
+
+        query 1 {
+            request { id 0 : integer }
+            response Person
+        }
+        "#,
+    )
+    .unwrap();
+
+    let names: Vec<&str> = schema.types_list.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "AddressBook",
+            "Attr",
+            "Person",
+            "Person.PhoneNumber",
+            "query.request"
+        ]
+    );
+
+    let person = schema.get_type("Person").unwrap();
+    let phone = person.find_field_by_name("phone").unwrap();
+    let phone_idx = schema.get_type_index("Person.PhoneNumber").unwrap();
+    assert_eq!(phone.field_type, FieldType::Struct(phone_idx));
+    assert!(phone.is_array);
+    assert_eq!(phone.key_tag, 0);
+    assert!(!phone.is_map);
+
+    let balance = person.find_field_by_name("balance").unwrap();
+    assert_eq!(balance.field_type, FieldType::Integer);
+    assert_eq!(balance.decimal_precision, 100);
+
+    assert_eq!(
+        person.find_field_by_name("avatar").unwrap().field_type,
+        FieldType::Binary
+    );
+    assert_eq!(
+        person.find_field_by_name("ratio").unwrap().field_type,
+        FieldType::Double
+    );
+
+    let attrs = person.find_field_by_name("attrs").unwrap();
+    assert!(attrs.is_map);
+    assert_eq!(attrs.key_tag, 0);
+
+    let book = schema.get_type("AddressBook").unwrap();
+    assert_eq!(book.fields[0].key_tag, 1);
+    assert_eq!(book.fields[1].key_tag, -1);
+
+    let query = schema.get_protocol("query").unwrap();
+    assert_eq!(query.tag, 1);
+    assert_eq!(query.request, schema.get_type_index("query.request"));
+    assert_eq!(query.response, schema.get_type_index("Person"));
+    assert!(!query.confirm);
+}
+
+#[test]
+fn test_parse_nested_name_resolution() {
+    // Inner scopes shadow outer ones, and dotted names reach into other types.
+    let schema = parser::parse(
+        r#"
+        .Item { id 0 : integer }
+        .Bag {
+            .Item { name 0 : string }
+            local 0 : Item
+            global 1 : *Item
+        }
+        .Shelf {
+            bag_item 0 : Bag.Item
+            item 1 : Item
+        }
+        "#,
+    )
+    .unwrap();
+
+    let inner = schema.get_type_index("Bag.Item").unwrap();
+    let outer = schema.get_type_index("Item").unwrap();
+    let bag = schema.get_type("Bag").unwrap();
+    assert_eq!(bag.fields[0].field_type, FieldType::Struct(inner));
+    assert_eq!(bag.fields[1].field_type, FieldType::Struct(inner));
+    let shelf = schema.get_type("Shelf").unwrap();
+    assert_eq!(shelf.fields[0].field_type, FieldType::Struct(inner));
+    assert_eq!(shelf.fields[1].field_type, FieldType::Struct(outer));
+}
+
+#[test]
+fn test_parse_sorts_fields_by_tag() {
+    let schema = parser::parse(".T { b 3 : integer\n a 1 : string }").unwrap();
+    let t = schema.get_type("T").unwrap();
+    assert_eq!(&*t.fields[0].name, "a");
+    assert_eq!(&*t.fields[1].name, "b");
+    assert_eq!(t.base_tag, -1);
+    assert_eq!(t.find_field_by_tag(3).unwrap().name.as_ref(), "b");
+}
+
+#[test]
+fn test_parse_empty_schema() {
+    let schema = parser::parse("  # nothing here\n").unwrap();
+    assert!(schema.types_list.is_empty());
+    assert!(schema.protocols.is_empty());
+}
+
+// =============================================================================
+// Diagnostics
+// =============================================================================
+
+fn parse_err(source: &str) -> (usize, usize, String) {
+    let err = parser::parse(source).unwrap_err();
+    (err.line, err.column, err.message)
+}
+
+#[test]
+fn test_parse_error_redefined_tag() {
+    let (line, col, msg) = parse_err(".T {\n  a 0 : integer\n  b 0 : string\n}");
+    assert_eq!((line, col), (3, 3));
+    assert!(msg.contains("redefined tag 0"), "{}", msg);
+}
+
+#[test]
+fn test_parse_error_redefined_field() {
+    let (line, _, msg) = parse_err(".T {\n  a 0 : integer\n  a 1 : string\n}");
+    assert_eq!(line, 3);
+    assert!(msg.contains("redefined field 'a'"), "{}", msg);
+}
+
+#[test]
+fn test_parse_error_redefined_type() {
+    let (line, _, msg) = parse_err(".T { a 0 : integer }\n.T { b 0 : integer }");
+    assert_eq!(line, 2);
+    assert!(msg.contains("redefined type 'T'"), "{}", msg);
+}
+
+#[test]
+fn test_parse_error_missing_colon() {
+    let (line, col, msg) = parse_err(".T {\n  a 0 integer\n}");
+    assert_eq!((line, col), (2, 7));
+    assert_eq!(msg, "expected ':', found 'integer'");
+}
+
+#[test]
+fn test_parse_error_bad_map_key() {
+    let (_, _, msg) = parse_err(".P { n 0 : string }\n.T { ps 0 : *P(id) }");
+    assert!(msg.contains("key 'id' not found in type 'P'"), "{}", msg);
+
+    let (_, _, msg) = parse_err(".P { n 0 : string }\n.T { p 0 : P(n) }");
+    assert!(msg.contains("requires an array"), "{}", msg);
+
+    let (_, _, msg) = parse_err(".P { n 0 : string }\n.T { ps 0 : *P() }");
+    assert!(msg.contains("exactly two fields"), "{}", msg);
+}
+
+#[test]
+fn test_parse_error_decimal_on_non_integer() {
+    let (line, col, msg) = parse_err(".T {\n  d 0 : double(2)\n}");
+    assert_eq!((line, col), (2, 16));
+    assert!(msg.contains("only valid for integer"), "{}", msg);
+}
+
+#[test]
+fn test_parse_error_protocol() {
+    let (_, _, msg) = parse_err("foo 1 { request nil }");
+    assert!(msg.contains("request type can't be nil"), "{}", msg);
+
+    let (_, _, msg) = parse_err("foo 1 { request Missing }");
+    assert!(msg.contains("undefined type 'Missing'"), "{}", msg);
+
+    let (line, _, msg) = parse_err("foo 1 {}\nbar 1 {}");
+    assert_eq!(line, 2);
+    assert!(msg.contains("redefined protocol tag 1"), "{}", msg);
+
+    let (_, _, msg) = parse_err("foo 1 { reply Missing }");
+    assert!(msg.contains("invalid protocol field 'reply'"), "{}", msg);
+}
+
+#[test]
+fn test_parse_error_display() {
+    let err = parser::parse(".T { a 0 : integer").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:19: expected field definition or '}', found end of input"
+    );
+}
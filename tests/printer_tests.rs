@@ -0,0 +1,170 @@
+//! Schema printer tests.
+//!
+//! Printed schemas are parsed back and compared with the original, for
+//! text schemas and for binary schemas compiled by the C/Lua toolchain.
+
+use sproto::types::{Field, FieldType, Sproto};
+use sproto::{binary_schema, compat, parser, printer};
+
+fn testdata(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/testdata/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+/// Parse the printed form of `schema` and check nothing changed.
+fn assert_round_trip(schema: &Sproto) {
+    let text = printer::to_text(schema);
+    let reparsed = parser::parse(&text).unwrap_or_else(|e| panic!("{}\n{}", e, text));
+
+    let names = |s: &Sproto| {
+        s.types_list
+            .iter()
+            .map(|t| t.name.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&reparsed), names(schema), "{}", text);
+    let report = compat::check(schema, &reparsed);
+    assert!(report.changes.is_empty(), "{}\n{}", report, text);
+    for (a, b) in schema.protocols.iter().zip(&reparsed.protocols) {
+        assert_eq!(
+            (&a.name, a.tag, a.request, a.response, a.confirm),
+            (&b.name, b.tag, b.request, b.response, b.confirm)
+        );
+    }
+    assert_eq!(reparsed.protocols.len(), schema.protocols.len());
+
+    // Printing is canonical.
+    assert_eq!(reparsed.to_string(), text);
+}
+
+// =============================================================================
+// Output format
+// =============================================================================
+
+#[test]
+fn test_print_canonical_text() {
+    let schema = parser::parse(
+        r#"
+        login 1 {
+            response { ok 0 : boolean }
+            request Person
+        }
+        .Person {
+            phones 3 : *PhoneNumber(number)
+            name 0 : string
+            .PhoneNumber { type 1 : integer number 0 : string }
+            balance 1 : integer(2)
+            photo 2 : binary
+            friends 4 : *Person
+            scores 5 : *Score()
+            ratios 6 : *double
+        }
+        .Score { subject 0 : string value 1 : integer }
+        .Empty {}
+        ping 3 {}
+        logout 2 { response nil }
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        schema.to_string(),
+        r#".Empty {}
+
+.Person {
+    .PhoneNumber {
+        number 0 : string
+        type 1 : integer
+    }
+    name 0 : string
+    balance 1 : integer(2)
+    photo 2 : binary
+    phones 3 : *PhoneNumber(number)
+    friends 4 : *Person
+    scores 5 : *Score()
+    ratios 6 : *double
+}
+
+.Score {
+    subject 0 : string
+    value 1 : integer
+}
+
+login 1 {
+    request Person
+    response {
+        ok 0 : boolean
+    }
+}
+
+logout 2 {
+    response nil
+}
+
+ping 3 {}
+"#
+    );
+    assert_round_trip(&schema);
+}
+
+#[test]
+fn test_print_scoped_type_references() {
+    let schema = parser::parse(
+        r#"
+        .Item { id 0 : integer }
+        .Order {
+            .Item { sku 0 : string }
+            .Line {
+                item 0 : Item
+                order 1 : Order
+            }
+            lines 0 : *Line
+            item 1 : Item
+        }
+        .Shelf { item 0 : Item  line 1 : Order.Line }
+        "#,
+    )
+    .unwrap();
+    let text = schema.to_string();
+    assert!(text.contains("    .Line {\n        item 0 : Item\n        order 1 : Order\n"));
+    assert!(text.contains("    lines 0 : *Line\n    item 1 : Item\n"));
+    assert!(text.contains(".Shelf {\n    item 0 : Item\n    line 1 : Order.Line\n}"));
+    assert_round_trip(&schema);
+}
+
+#[test]
+fn test_print_dotted_type_without_parent() {
+    let mut schema = Sproto::new();
+    let leaf = schema.add_type("A.B.C", vec![Field::new("x", 0, FieldType::Integer)]);
+    schema.add_type("D", vec![Field::new("c", 0, FieldType::Struct(leaf))]);
+
+    assert_eq!(
+        schema.to_string(),
+        ".A {\n    .B {\n        .C {\n            x 0 : integer\n        }\n    }\n}\n\n\
+         .D {\n    c 0 : A.B.C\n}\n"
+    );
+}
+
+// =============================================================================
+// Round trips
+// =============================================================================
+
+#[test]
+fn test_print_round_trips_text_schema() {
+    let text = String::from_utf8(testdata("schema.sproto")).unwrap();
+    assert_round_trip(&parser::parse(&text).unwrap());
+}
+
+#[test]
+fn test_print_round_trips_binary_schemas() {
+    for name in [
+        "schema.bin",
+        "rpc_schema.bin",
+        "lua_addressbook_schema.bin",
+        "lua_data_schema.bin",
+        "lua_human_schema.bin",
+    ] {
+        let loaded = binary_schema::load_binary(&testdata(name)).unwrap();
+        assert_round_trip(&loaded);
+    }
+}
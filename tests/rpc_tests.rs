@@ -5,9 +5,21 @@
 //! - RPC response encoding and decoding
 //! - Request-response round-trip
 //! - Various protocol configurations (with/without request/response types)
-
-use sproto::codec::{StructDecoder, StructEncoder};
-use sproto::rpc::{DispatchResult, Host};
+//! - Length-prefixed stream framing
+//! - Typed protocol handlers via `Router`
+//! - One-way / `response nil` rules and strict body checking
+//! - Session tracking shared between `Host` and its senders
+//! - Session deadlines and pending-session introspection
+//! - Buffer-reusing `dispatch_into` / `request_into` / `respond_into`
+//! - Unpack size limits for untrusted packets
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sproto::codec::{DecodeLimits, SprotoDecode, SprotoEncode, StructDecoder, StructEncoder};
+use sproto::error::{DecodeError, EncodeError, PackError, RpcError};
+use sproto::rpc::framing::{FrameDecoder, FrameEncoder, LengthPrefix};
+use sproto::rpc::{DispatchResult, Host, Router};
 use sproto::types::{Field, FieldType, Sproto};
 
 /// Create a test RPC schema programmatically.
@@ -169,7 +181,9 @@ fn test_rpc_roundtrip_with_request_response() {
             let response_result = client_host.dispatch(&response_packet).unwrap();
 
             match response_result {
-                DispatchResult::Response { session, body, ud } => {
+                DispatchResult::Response {
+                    session, body, ud, ..
+                } => {
                     assert_eq!(session, 1001);
                     assert!(ud.is_none());
                     assert_eq!(decode_bool(&sproto, "login_response", &body, 0), Some(true));
@@ -287,7 +301,9 @@ fn test_rpc_roundtrip_with_user_data() {
             let response_result = client_host.dispatch(&response_packet).unwrap();
 
             match response_result {
-                DispatchResult::Response { session, body, ud } => {
+                DispatchResult::Response {
+                    session, body, ud, ..
+                } => {
                     assert_eq!(session, 3001);
                     assert_eq!(ud, Some(99));
                     assert_eq!(
@@ -583,3 +599,1000 @@ fn test_rpc_empty_string_in_request() {
         _ => panic!("expected Request"),
     }
 }
+
+// ============================================================================
+// Stream Framing Tests
+// ============================================================================
+
+#[test]
+fn test_framing_dispatch_over_byte_stream() {
+    let sproto = create_rpc_schema();
+    let mut server_host = Host::new(sproto.clone());
+    let mut client_sender = server_host.attach(sproto.clone());
+    let framer = FrameEncoder::new();
+
+    // Three requests written back to back on one stream.
+    let mut stream = Vec::new();
+    for (session, data) in [(1, "first"), (2, ""), (3, "third")] {
+        let body = encode_struct(&sproto, "echo_request", |enc| enc.set_string(0, data));
+        let packet = client_sender
+            .request("echo", &body, Some(session), None)
+            .unwrap();
+        framer.encode_into(&packet, &mut stream).unwrap();
+    }
+
+    // Feed the stream one byte at a time, as the worst case of partial reads.
+    let mut decoder = FrameDecoder::new();
+    let mut received = Vec::new();
+    for byte in &stream {
+        decoder.extend(std::slice::from_ref(byte));
+        while let Some(packet) = decoder.next_frame().unwrap() {
+            match server_host.dispatch(&packet).unwrap() {
+                DispatchResult::Request {
+                    name,
+                    body,
+                    responder,
+                    ..
+                } => {
+                    assert_eq!(name, "echo");
+                    received.push((
+                        responder.unwrap().session(),
+                        decode_string(&sproto, "echo_request", &body, 0).unwrap(),
+                    ));
+                }
+                _ => panic!("expected Request"),
+            }
+        }
+    }
+    assert!(decoder.is_empty());
+    assert_eq!(
+        received,
+        vec![
+            (1, "first".to_string()),
+            (2, String::new()),
+            (3, "third".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_framing_partial_frame() {
+    let framed = FrameEncoder::new().encode(b"hello").unwrap();
+    assert_eq!(framed, b"\x00\x05hello");
+
+    let mut decoder = FrameDecoder::new();
+    decoder.extend(&framed[..1]);
+    assert_eq!(decoder.next_frame().unwrap(), None);
+    decoder.extend(&framed[1..4]);
+    assert_eq!(decoder.next_frame().unwrap(), None);
+    assert_eq!(decoder.buffered(), 4);
+    decoder.extend(&framed[4..]);
+    assert_eq!(
+        decoder.next_frame().unwrap().as_deref(),
+        Some(&b"hello"[..])
+    );
+    assert_eq!(decoder.next_frame().unwrap(), None);
+}
+
+#[test]
+fn test_framing_empty_frame() {
+    let framed = FrameEncoder::new().encode(&[]).unwrap();
+    assert_eq!(framed, vec![0, 0]);
+    let mut decoder = FrameDecoder::new();
+    decoder.extend(&framed);
+    assert_eq!(decoder.next_frame().unwrap(), Some(Vec::new()));
+}
+
+#[test]
+fn test_framing_u32_prefix() {
+    let packet = vec![7u8; 70_000];
+    let framer = FrameEncoder::with_prefix(LengthPrefix::U32);
+    let framed = framer.encode(&packet).unwrap();
+    assert_eq!(&framed[..4], &70_000u32.to_be_bytes());
+
+    let mut decoder = FrameDecoder::with_prefix(LengthPrefix::U32);
+    for chunk in framed.chunks(4096) {
+        decoder.extend(chunk);
+    }
+    assert_eq!(decoder.next_frame().unwrap(), Some(packet));
+}
+
+#[test]
+fn test_framing_too_large() {
+    let err = FrameEncoder::new().encode(&vec![0; 65_536]).unwrap_err();
+    assert!(matches!(
+        err,
+        RpcError::FrameTooLarge {
+            size: 65_536,
+            max: 65_535
+        }
+    ));
+
+    let mut out = vec![1, 2];
+    assert!(FrameEncoder::new()
+        .encode_into(&vec![0; 65_536], &mut out)
+        .is_err());
+    assert_eq!(out, vec![1, 2]);
+
+    let mut decoder = FrameDecoder::with_prefix(LengthPrefix::U32).with_max_frame_len(1024);
+    decoder.extend(&2048u32.to_be_bytes());
+    let err = decoder.next_frame().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "frame of 2048 bytes exceeds the 1024-byte limit"
+    );
+}
+
+// ============================================================================
+// Router Tests
+// ============================================================================
+
+#[derive(Debug, Default)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+impl SprotoDecode for LoginRequest {
+    fn decode_fields(dec: &mut StructDecoder) -> Result<Self, DecodeError> {
+        let mut req = LoginRequest::default();
+        while let Some(f) = dec.next_field()? {
+            match f.tag() {
+                0 => req.username = f.as_string()?.to_owned(),
+                1 => req.password = f.as_string()?.to_owned(),
+                _ => {}
+            }
+        }
+        Ok(req)
+    }
+}
+
+struct LoginResponse {
+    ok: bool,
+    user_id: i64,
+}
+
+impl SprotoEncode for LoginResponse {
+    fn encode_fields(&self, enc: &mut StructEncoder) -> Result<(), EncodeError> {
+        enc.set_bool(0, self.ok)?;
+        enc.set_integer(1, self.user_id)
+    }
+}
+
+struct PingResponse {
+    time: i64,
+}
+
+impl SprotoEncode for PingResponse {
+    fn encode_fields(&self, enc: &mut StructEncoder) -> Result<(), EncodeError> {
+        enc.set_integer(0, self.time)
+    }
+}
+
+fn create_router(sproto: &Sproto) -> Router {
+    let mut router = Router::new(sproto.clone());
+    router
+        .on("login", |req: LoginRequest| {
+            Ok(LoginResponse {
+                ok: req.password == "secret",
+                user_id: req.username.len() as i64,
+            })
+        })
+        .unwrap()
+        .on("ping", |_: ()| Ok(PingResponse { time: 1234 }))
+        .unwrap()
+        .on("logout", |_: ()| Ok(()))
+        .unwrap();
+    router
+}
+
+/// Send `protocol_name` through a host and route the dispatched request.
+fn route(
+    sproto: &Sproto,
+    router: &Router,
+    protocol_name: &str,
+    body: &[u8],
+    session: Option<u64>,
+) -> Result<Option<Vec<u8>>, RpcError> {
+    let mut host = Host::new(sproto.clone());
+    let packet = host
+        .attach(sproto.clone())
+        .request(protocol_name, body, session, None)
+        .unwrap();
+    match host.dispatch(&packet).unwrap() {
+        DispatchResult::Request {
+            name,
+            body,
+            responder,
+            ..
+        } => router.handle(&name, &body, responder.as_deref()),
+        _ => panic!("expected Request"),
+    }
+}
+
+/// Dispatch a response packet on the requesting side and return its body.
+fn response_body(sproto: &Sproto, packet: &[u8], session: u64) -> Vec<u8> {
+    let mut client = Host::new(sproto.clone());
+    client.register_session(session);
+    match client.dispatch(packet).unwrap() {
+        DispatchResult::Response {
+            session: s, body, ..
+        } => {
+            assert_eq!(s, session);
+            body
+        }
+        _ => panic!("expected Response"),
+    }
+}
+
+#[test]
+fn test_router_typed_handler() {
+    let sproto = create_rpc_schema();
+    let router = create_router(&sproto);
+
+    let body = encode_struct(&sproto, "login_request", |enc| {
+        enc.set_string(0, "alice")?;
+        enc.set_string(1, "secret")
+    });
+    let packet = route(&sproto, &router, "login", &body, Some(7))
+        .unwrap()
+        .expect("response packet");
+    let resp = response_body(&sproto, &packet, 7);
+    assert_eq!(decode_bool(&sproto, "login_response", &resp, 0), Some(true));
+    assert_eq!(decode_integer(&sproto, "login_response", &resp, 1), Some(5));
+}
+
+#[test]
+fn test_router_protocol_without_request_type() {
+    let sproto = create_rpc_schema();
+    let router = create_router(&sproto);
+
+    let packet = route(&sproto, &router, "ping", &[], Some(1))
+        .unwrap()
+        .unwrap();
+    let resp = response_body(&sproto, &packet, 1);
+    assert_eq!(
+        decode_integer(&sproto, "ping_response", &resp, 0),
+        Some(1234)
+    );
+
+    // No response type: the handler runs and an empty body is returned
+    // (only zero-packing padding remains after unpack).
+    let packet = route(&sproto, &router, "logout", &[], Some(2))
+        .unwrap()
+        .unwrap();
+    assert!(response_body(&sproto, &packet, 2).iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_router_without_session() {
+    let sproto = create_rpc_schema();
+    let router = create_router(&sproto);
+    assert!(route(&sproto, &router, "ping", &[], None)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_router_unhandled() {
+    let sproto = create_rpc_schema();
+    let mut router = create_router(&sproto);
+
+    let err = route(&sproto, &router, "notify", &[], None).unwrap_err();
+    assert!(matches!(&err, RpcError::Unhandled(name) if name == "notify"));
+    assert_eq!(err.to_string(), "no handler for protocol 'notify'");
+
+    router.fallback(|proto, body| {
+        assert_eq!(proto.name, "echo");
+        Ok(Some(body.to_vec()))
+    });
+    let body = encode_struct(&sproto, "echo_request", |enc| enc.set_string(0, "raw"));
+    let packet = route(&sproto, &router, "echo", &body, Some(3))
+        .unwrap()
+        .unwrap();
+    let resp = response_body(&sproto, &packet, 3);
+    assert_eq!(
+        decode_string(&sproto, "echo_response", &resp, 0).as_deref(),
+        Some("raw")
+    );
+}
+
+#[test]
+fn test_router_register_errors() {
+    let sproto = create_rpc_schema();
+    let mut router = Router::new(sproto.clone());
+    assert!(matches!(
+        router.on("missing", |_: ()| Ok(())),
+        Err(RpcError::UnknownProtocol(_))
+    ));
+    assert!(router.on_tag(99, |_: ()| Ok(())).is_err());
+
+    router.on_tag(4, |_: ()| Ok(())).unwrap();
+    assert!(router.has_handler(4));
+    assert!(!router.has_handler(5));
+}
+
+#[test]
+fn test_router_handler_error() {
+    let sproto = create_rpc_schema();
+    let mut router = Router::new(sproto.clone());
+    router
+        .on(
+            "login",
+            |req: LoginRequest| -> Result<LoginResponse, RpcError> {
+                Err(RpcError::Handler(format!(
+                    "user '{}' is banned",
+                    req.username
+                )))
+            },
+        )
+        .unwrap();
+
+    let body = encode_struct(&sproto, "login_request", |enc| enc.set_string(0, "mallory"));
+    let err = route(&sproto, &router, "login", &body, Some(1)).unwrap_err();
+    assert_eq!(err.to_string(), "handler error: user 'mallory' is banned");
+
+    // A malformed body fails to decode before the handler runs.
+    let err = router.handle("login", &[0xff], None).unwrap_err();
+    assert!(matches!(err, RpcError::Decode(_)));
+}
+
+#[test]
+fn test_router_decode_limits() {
+    let sproto = create_rpc_schema();
+    let router = create_router(&sproto).with_decode_limits(DecodeLimits {
+        max_string_len: 5,
+        ..Default::default()
+    });
+
+    let body = encode_struct(&sproto, "login_request", |enc| {
+        enc.set_string(0, "alice")?;
+        enc.set_string(1, "secret")
+    });
+    let err = route(&sproto, &router, "login", &body, Some(1)).unwrap_err();
+    assert!(matches!(
+        err,
+        RpcError::Decode(DecodeError::StringTooLong { ref field, len: 6, max: 5 })
+            if field == "password"
+    ));
+
+    let body = encode_struct(&sproto, "login_request", |enc| {
+        enc.set_string(0, "bob")?;
+        enc.set_string(1, "pass")
+    });
+    assert!(route(&sproto, &router, "login", &body, Some(2))
+        .unwrap()
+        .is_some());
+}
+
+// ============================================================================
+// Protocol Rule Tests
+// ============================================================================
+
+/// Dispatch a request packet and return its responder.
+fn dispatch_responder(host: &mut Host, packet: &[u8]) -> Box<sproto::rpc::Responder> {
+    match host.dispatch(packet).unwrap() {
+        DispatchResult::Request { responder, .. } => responder.expect("responder"),
+        _ => panic!("expected Request"),
+    }
+}
+
+#[test]
+fn test_rpc_expects_response() {
+    let sproto = create_rpc_schema();
+    assert!(sproto.get_protocol("login").unwrap().expects_response());
+    assert!(sproto.get_protocol("ping").unwrap().expects_response());
+    assert!(sproto.get_protocol("logout").unwrap().expects_response());
+    assert!(!sproto.get_protocol("notify").unwrap().expects_response());
+}
+
+#[test]
+fn test_rpc_one_way_rejects_session() {
+    let sproto = create_rpc_schema();
+    let host = Host::new(sproto.clone());
+    let mut sender = host.attach(sproto.clone());
+
+    let err = sender.request("notify", &[], Some(1), None).unwrap_err();
+    assert!(matches!(&err, RpcError::OneWay(name) if name == "notify"));
+    assert_eq!(
+        err.to_string(),
+        "protocol 'notify' is one-way and cannot carry a session"
+    );
+    assert!(sender.request("notify", &[], None, None).is_ok());
+    assert!(sender.request("logout", &[], Some(1), None).is_ok());
+}
+
+#[test]
+fn test_rpc_confirm_is_ack_only() {
+    let sproto = create_rpc_schema();
+    let mut server_host = Host::new(sproto.clone());
+    let mut sender = server_host.attach(sproto.clone());
+
+    let packet = sender.request("logout", &[], Some(9), None).unwrap();
+    let responder = dispatch_responder(&mut server_host, &packet);
+    assert!(responder.is_ack_only());
+    assert_eq!(responder.protocol(), "logout");
+
+    let err = responder.respond(b"\x01\x00", None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid response body for protocol 'logout': protocol has no response type"
+    );
+
+    let ack = responder.acknowledge(None).unwrap();
+    let body = response_body(&sproto, &ack, 9);
+    assert!(body.iter().all(|&b| b == 0));
+
+    // A zero-padded body is as empty as `acknowledge`'s.
+    let padded = responder.respond(&[0, 0], None).unwrap();
+    assert!(response_body(&sproto, &padded, 9).iter().all(|&b| b == 0));
+
+    // Protocols with a response type are not ack-only, and outside strict
+    // mode their bodies are not checked.
+    let packet = sender.request("ping", &[], Some(10), None).unwrap();
+    let responder = dispatch_responder(&mut server_host, &packet);
+    assert!(!responder.is_ack_only());
+    assert!(responder.respond(b"\x01\x00", None).is_ok());
+}
+
+#[test]
+fn test_rpc_strict_request_body() {
+    let sproto = create_rpc_schema();
+    let host = Host::new(sproto.clone()).with_strict(true);
+    assert!(host.is_strict());
+    let mut sender = host.attach(sproto.clone());
+
+    let good = encode_struct(&sproto, "login_request", |enc| enc.set_string(0, "alice"));
+    assert!(sender.request("login", &good, Some(1), None).is_ok());
+
+    // A string field whose length runs past the end of the data.
+    let bad = [1, 0, 0, 0, 0xff, 0, 0, 0];
+    let err = sender.request("login", &bad, Some(2), None).unwrap_err();
+    assert!(matches!(
+        &err,
+        RpcError::InvalidBody { protocol, kind: "request", .. } if protocol == "login"
+    ));
+
+    let err = sender.request("ping", &good, Some(3), None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid request body for protocol 'ping': protocol has no request type"
+    );
+
+    // Lenient senders pass bodies through unchecked.
+    let mut lenient = Host::new(sproto.clone()).attach(sproto.clone());
+    assert!(lenient.request("ping", &good, Some(3), None).is_ok());
+}
+
+#[test]
+fn test_rpc_strict_dispatch() {
+    let sproto = create_rpc_schema();
+    let mut server_host = Host::new(sproto.clone()).with_strict(true);
+
+    // A lenient peer sends a body that does not match the request type.
+    let mut peer = Host::new(sproto.clone()).attach(sproto.clone());
+    let login = encode_struct(&sproto, "login_request", |enc| enc.set_string(0, "bob"));
+    let packet = peer.request("ping", &login, Some(1), None).unwrap();
+    assert!(matches!(
+        server_host.dispatch(&packet),
+        Err(RpcError::InvalidBody {
+            kind: "request",
+            ..
+        })
+    ));
+
+    // A peer whose schema gives `notify` a response sends it with a session.
+    let mut remote = create_rpc_schema();
+    let idx = remote.get_type_index("ping_response").unwrap();
+    remote.add_protocol("notify", 4, None, Some(idx), false);
+    let mut peer = Host::new(remote.clone()).attach(remote);
+    let packet = peer.request("notify", &[], Some(2), None).unwrap();
+    assert!(matches!(
+        server_host.dispatch(&packet),
+        Err(RpcError::OneWay(_))
+    ));
+    // Without strict mode the request is accepted.
+    assert!(Host::new(sproto.clone()).dispatch(&packet).is_ok());
+}
+
+#[test]
+fn test_rpc_strict_response_body() {
+    let sproto = create_rpc_schema();
+    let mut server_host = Host::new(sproto.clone()).with_strict(true);
+    let mut sender = server_host.attach(sproto.clone());
+
+    let body = encode_struct(&sproto, "login_request", |enc| enc.set_string(0, "carol"));
+    let packet = sender.request("login", &body, Some(5), None).unwrap();
+    let responder = dispatch_responder(&mut server_host, &packet);
+
+    let err = responder
+        .respond(&[1, 0, 0, 0, 0xff, 0, 0, 0], None)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        RpcError::InvalidBody {
+            kind: "response",
+            ..
+        }
+    ));
+
+    let good = encode_struct(&sproto, "login_response", |enc| enc.set_bool(0, true));
+    assert!(responder.respond(&good, None).is_ok());
+}
+
+// ============================================================================
+// Session Tracking Tests
+// ============================================================================
+
+/// A client host with an attached sender, and a separate server host.
+fn client_and_server(sproto: &Sproto) -> (Host, sproto::rpc::RequestSender, Host) {
+    let client_host = Host::new(sproto.clone());
+    let sender = client_host.attach(sproto.clone());
+    (client_host, sender, Host::new(sproto.clone()))
+}
+
+#[test]
+fn test_rpc_attached_sender_registers_session() {
+    let sproto = create_rpc_schema();
+    let (mut client_host, mut sender, mut server_host) = client_and_server(&sproto);
+
+    let body = encode_struct(&sproto, "login_request", |enc| enc.set_string(0, "dave"));
+    let packet = sender.request("login", &body, Some(42), None).unwrap();
+    let responder = dispatch_responder(&mut server_host, &packet);
+    let reply = encode_struct(&sproto, "login_response", |enc| enc.set_integer(1, 7));
+    let packet = responder.respond(&reply, None).unwrap();
+
+    // No `register_session`: the sender opened session 42 on the client host.
+    match client_host.dispatch(&packet).unwrap() {
+        DispatchResult::Response {
+            session,
+            body,
+            protocol,
+            ..
+        } => {
+            assert_eq!(session, 42);
+            let protocol = protocol.expect("protocol");
+            assert_eq!(protocol.name, "login");
+            assert_eq!(protocol.tag, 1);
+            let resp_type = &sproto.types_list[protocol.response.unwrap()];
+            assert_eq!(resp_type.name, "login_response");
+            assert_eq!(decode_integer(&sproto, "login_response", &body, 1), Some(7));
+        }
+        _ => panic!("expected Response"),
+    }
+
+    // The session is closed once its response has arrived.
+    assert!(matches!(
+        client_host.dispatch(&packet),
+        Err(RpcError::UnknownSession(42))
+    ));
+}
+
+#[test]
+fn test_rpc_registered_session_has_no_protocol() {
+    let sproto = create_rpc_schema();
+    let (_, mut sender, mut server_host) = client_and_server(&sproto);
+    let packet = sender.request("ping", &[], Some(3), None).unwrap();
+    let responder = dispatch_responder(&mut server_host, &packet);
+    let packet = responder.respond(&[], None).unwrap();
+
+    let mut other_host = Host::new(sproto.clone());
+    other_host.register_session(3);
+    match other_host.dispatch(&packet).unwrap() {
+        DispatchResult::Response {
+            session, protocol, ..
+        } => {
+            assert_eq!(session, 3);
+            assert!(protocol.is_none());
+        }
+        _ => panic!("expected Response"),
+    }
+}
+
+#[test]
+fn test_rpc_duplicate_session() {
+    let sproto = create_rpc_schema();
+    let (mut client_host, mut sender, mut server_host) = client_and_server(&sproto);
+
+    let packet = sender.request("ping", &[], Some(8), None).unwrap();
+    let err = sender.request("ping", &[], Some(8), None).unwrap_err();
+    assert!(matches!(err, RpcError::DuplicateSession(8)));
+    assert_eq!(err.to_string(), "session 8 is already pending");
+
+    // A second sender attached to the same host shares its sessions.
+    let mut other = client_host.attach(sproto.clone());
+    assert!(matches!(
+        other.request("login", &[], Some(8), None),
+        Err(RpcError::DuplicateSession(8))
+    ));
+
+    // Once the response has been dispatched the session can be reused.
+    let responder = dispatch_responder(&mut server_host, &packet);
+    client_host
+        .dispatch(&responder.respond(&[], None).unwrap())
+        .unwrap();
+    assert!(sender.request("ping", &[], Some(8), None).is_ok());
+}
+
+#[test]
+fn test_rpc_cancel_attached_session() {
+    let sproto = create_rpc_schema();
+    let (mut client_host, mut sender, mut server_host) = client_and_server(&sproto);
+
+    let packet = sender.request("ping", &[], Some(11), None).unwrap();
+    assert!(client_host.cancel_session(11));
+    assert!(!client_host.cancel_session(11));
+
+    let responder = dispatch_responder(&mut server_host, &packet);
+    let packet = responder.respond(&[], None).unwrap();
+    assert!(matches!(
+        client_host.dispatch(&packet),
+        Err(RpcError::UnknownSession(11))
+    ));
+}
+
+#[test]
+fn test_rpc_strict_checks_incoming_response() {
+    let sproto = create_rpc_schema();
+    let mut client_host = Host::new(sproto.clone()).with_strict(true);
+    let mut sender = client_host.attach(sproto.clone());
+    let mut server_host = Host::new(sproto.clone());
+
+    let body = encode_struct(&sproto, "login_request", |enc| enc.set_string(0, "erin"));
+    let packet = sender.request("login", &body, Some(6), None).unwrap();
+    let responder = dispatch_responder(&mut server_host, &packet);
+
+    // The lenient server answers with a body that is not a login_response.
+    let packet = responder
+        .respond(&[1, 0, 0, 0, 0xff, 0, 0, 0], None)
+        .unwrap();
+    let err = client_host.dispatch(&packet).unwrap_err();
+    assert!(matches!(
+        &err,
+        RpcError::InvalidResponse {
+            session: 6,
+            protocol,
+            ..
+        } if protocol == "login"
+    ));
+    assert!(err
+        .to_string()
+        .starts_with("invalid response body for session 6 of protocol 'login': "));
+
+    // The session was answered, so it is no longer pending.
+    assert!(client_host.pending_sessions().is_empty());
+    assert!(matches!(
+        client_host.dispatch(&packet),
+        Err(RpcError::UnknownSession(6))
+    ));
+}
+
+// ============================================================================
+// Session Timeout Tests
+// ============================================================================
+
+/// A clock that only moves when the test advances it.
+#[derive(Clone)]
+struct ManualClock(Arc<Mutex<Instant>>);
+
+impl ManualClock {
+    fn new() -> Self {
+        ManualClock(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+
+    fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+fn host_with_clock(sproto: &Sproto) -> (Host, ManualClock) {
+    let clock = ManualClock::new();
+    let handle = clock.clone();
+    let host = Host::new(sproto.clone()).with_clock(move || handle.now());
+    (host, clock)
+}
+
+fn sessions(infos: &[sproto::rpc::SessionInfo]) -> Vec<u64> {
+    infos.iter().map(|info| info.session).collect()
+}
+
+#[test]
+fn test_rpc_poll_expired() {
+    let sproto = create_rpc_schema();
+    let (mut host, clock) = host_with_clock(&sproto);
+
+    host.register_session_with_timeout(1, Duration::from_secs(10));
+    host.register_session_with_timeout(2, Duration::from_secs(5));
+    host.register_session(3);
+    assert!(host.poll_expired(clock.now()).is_empty());
+
+    clock.advance(Duration::from_secs(5));
+    let expired = host.poll_expired(clock.now());
+    assert_eq!(sessions(&expired), vec![2]);
+    assert_eq!(
+        expired[0].deadline,
+        Some(clock.now()),
+        "deadline is registration time plus timeout"
+    );
+    assert_eq!(sessions(&host.pending_sessions()), vec![1, 3]);
+
+    // Sessions without a deadline never expire.
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(sessions(&host.poll_expired(clock.now())), vec![1]);
+    assert!(host.poll_expired(clock.now()).is_empty());
+    assert_eq!(sessions(&host.pending_sessions()), vec![3]);
+}
+
+#[test]
+fn test_rpc_poll_expired_order() {
+    let sproto = create_rpc_schema();
+    let (mut host, clock) = host_with_clock(&sproto);
+    host.register_session_with_timeout(7, Duration::from_secs(3));
+    host.register_session_with_timeout(4, Duration::from_secs(1));
+    host.register_session_with_timeout(9, Duration::from_secs(2));
+    host.register_session_with_timeout(5, Duration::from_secs(2));
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(sessions(&host.poll_expired(clock.now())), vec![4, 5, 9, 7]);
+}
+
+#[test]
+fn test_rpc_expired_response_rejected() {
+    let sproto = create_rpc_schema();
+    let (mut client_host, clock) = host_with_clock(&sproto);
+    let mut sender = client_host.attach(sproto.clone());
+    let mut server_host = Host::new(sproto.clone());
+
+    // Give a session opened by the sender a deadline; its protocol is kept.
+    let packet = sender.request("ping", &[], Some(12), None).unwrap();
+    client_host.register_session_with_timeout(12, Duration::from_millis(500));
+    let pending = client_host.pending_sessions();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].protocol.as_ref().unwrap().name, "ping");
+    assert!(pending[0].deadline.is_some());
+
+    clock.advance(Duration::from_secs(1));
+    let expired = client_host.poll_expired(clock.now());
+    assert_eq!(sessions(&expired), vec![12]);
+    assert_eq!(expired[0].protocol.as_ref().unwrap().name, "ping");
+    assert!(client_host.pending_sessions().is_empty());
+
+    let responder = dispatch_responder(&mut server_host, &packet);
+    let packet = responder.respond(&[], None).unwrap();
+    assert!(matches!(
+        client_host.dispatch(&packet),
+        Err(RpcError::UnknownSession(12))
+    ));
+}
+
+#[test]
+fn test_rpc_pending_sessions_after_response_and_cancel() {
+    let sproto = create_rpc_schema();
+    let (mut client_host, mut sender, mut server_host) = client_and_server(&sproto);
+
+    let first = sender.request("ping", &[], Some(1), None).unwrap();
+    sender.request("logout", &[], Some(2), None).unwrap();
+    client_host.register_session_with_timeout(3, Duration::from_secs(30));
+    let pending = client_host.pending_sessions();
+    assert_eq!(sessions(&pending), vec![1, 2, 3]);
+    assert_eq!(pending[1].protocol.as_ref().unwrap().name, "logout");
+    assert!(pending[2].protocol.is_none());
+    assert!(pending[0].deadline.is_none());
+
+    let responder = dispatch_responder(&mut server_host, &first);
+    client_host
+        .dispatch(&responder.respond(&[], None).unwrap())
+        .unwrap();
+    assert!(client_host.cancel_session(3));
+    assert_eq!(sessions(&client_host.pending_sessions()), vec![2]);
+}
+
+// ============================================================================
+// Buffer Reuse Tests
+// ============================================================================
+
+#[test]
+fn test_rpc_request_into_matches_request() {
+    let sproto = create_rpc_schema();
+    let mut sender = Host::new(sproto.clone()).attach(sproto.clone());
+    let body = encode_struct(&sproto, "login_request", |enc| {
+        enc.set_string(0, "frank")?;
+        enc.set_string(1, "hunter2")
+    });
+
+    let expected = sender.request("login", &body, Some(1), Some(-3)).unwrap();
+    let mut out = vec![0xAA, 0xBB];
+    sender
+        .request_into("login", &body, Some(2), Some(-3), &mut out)
+        .unwrap();
+    // Appended after the existing bytes; only the session differs.
+    assert_eq!(&out[..2], &[0xAA, 0xBB]);
+    let mut host = Host::new(sproto.clone());
+    let mut buf = Vec::new();
+    match host.dispatch_into(&out[2..], &mut buf).unwrap() {
+        DispatchResult::Request {
+            name,
+            body: got,
+            responder,
+            ud,
+        } => {
+            assert_eq!(name, "login");
+            assert_eq!(&got[..body.len()], &body[..]);
+            assert_eq!(responder.unwrap().session(), 2);
+            assert_eq!(ud, Some(-3));
+        }
+        _ => panic!("expected Request"),
+    }
+
+    let mut out = Vec::new();
+    sender
+        .request_into("login", &body, Some(3), Some(-3), &mut out)
+        .unwrap();
+    assert_eq!(out.len(), expected.len());
+
+    // Nothing is written on error.
+    let mut out = vec![1, 2, 3];
+    assert!(sender
+        .request_into("notify", &[], Some(4), None, &mut out)
+        .is_err());
+    assert!(sender
+        .request_into("missing", &[], None, None, &mut out)
+        .is_err());
+    assert_eq!(out, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_rpc_respond_into_matches_respond() {
+    let sproto = create_rpc_schema();
+    let mut server_host = Host::new(sproto.clone());
+    let mut sender = server_host.attach(sproto.clone());
+    let packet = sender.request("ping", &[], Some(77), None).unwrap();
+    let responder = dispatch_responder(&mut server_host, &packet);
+
+    let body = encode_struct(&sproto, "ping_response", |enc| enc.set_integer(0, 1 << 40));
+    let expected = responder.respond(&body, Some(9)).unwrap();
+    let mut out = vec![0xFF];
+    responder.respond_into(&body, Some(9), &mut out).unwrap();
+    assert_eq!(&out[1..], &expected[..]);
+
+    let logout = sender.request("logout", &[], Some(78), None).unwrap();
+    let responder = dispatch_responder(&mut server_host, &logout);
+    let mut out = Vec::new();
+    assert!(responder.respond_into(&body, None, &mut out).is_err());
+    assert!(out.is_empty());
+}
+
+#[test]
+fn test_rpc_dispatch_into_reuses_buffer() {
+    let sproto = create_rpc_schema();
+    let mut client_host = Host::new(sproto.clone());
+    let mut sender = client_host.attach(sproto.clone());
+    let mut server_host = Host::new(sproto.clone());
+
+    let mut buf = Vec::new();
+    let mut packets = Vec::new();
+    for (i, data) in ["short", "a somewhat longer payload", "x"]
+        .iter()
+        .enumerate()
+    {
+        let body = encode_struct(&sproto, "echo_request", |enc| enc.set_string(0, data));
+        packets.push(
+            sender
+                .request("echo", &body, Some(i as u64 + 1), None)
+                .unwrap(),
+        );
+    }
+
+    let mut replies = Vec::new();
+    for (packet, data) in packets
+        .iter()
+        .zip(["short", "a somewhat longer payload", "x"])
+    {
+        let owned = Host::new(sproto.clone()).dispatch(packet).unwrap();
+        let responder = match server_host.dispatch_into(packet, &mut buf).unwrap() {
+            DispatchResult::Request {
+                name,
+                body,
+                responder,
+                ..
+            } => {
+                assert_eq!(name, "echo");
+                assert_eq!(
+                    decode_string(&sproto, "echo_request", body, 0).as_deref(),
+                    Some(data)
+                );
+                match owned {
+                    DispatchResult::Request { body: owned, .. } => assert_eq!(owned, body),
+                    _ => panic!("expected Request"),
+                }
+                responder.unwrap()
+            }
+            _ => panic!("expected Request"),
+        };
+        let reply = encode_struct(&sproto, "echo_response", |enc| {
+            enc.set_string(0, &data.to_uppercase())
+        });
+        replies.push(responder.respond(&reply, None).unwrap());
+    }
+
+    for (i, packet) in replies.iter().enumerate() {
+        match client_host.dispatch_into(packet, &mut buf).unwrap() {
+            DispatchResult::Response {
+                session,
+                protocol,
+                body,
+                ..
+            } => {
+                assert_eq!(session, i as u64 + 1);
+                assert_eq!(protocol.unwrap().name, "echo");
+                let owned = DispatchResult::Response {
+                    session,
+                    protocol: None,
+                    body,
+                    ud: None,
+                }
+                .into_owned();
+                assert!(matches!(owned, DispatchResult::Response { body: b, .. } if b == body));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    // A bad packet is reported and the buffer stays usable.
+    assert!(client_host.dispatch_into(&[0xFF], &mut buf).is_err());
+}
+
+// ============================================================================
+// Unpack Limit Tests
+// ============================================================================
+
+#[test]
+fn test_rpc_unpack_limit() {
+    let sproto = create_rpc_schema();
+    let mut host = Host::new(sproto.clone()).with_unpack_limit(64);
+    assert_eq!(host.unpack_limit(), Some(64));
+    assert_eq!(Host::new(sproto.clone()).unpack_limit(), None);
+
+    let mut sender = Host::new(sproto.clone()).attach(sproto.clone());
+    let small = encode_struct(&sproto, "echo_request", |enc| enc.set_string(0, "hi"));
+    let packet = sender.request("echo", &small, None, None).unwrap();
+    assert!(host.dispatch(&packet).is_ok());
+
+    let large = encode_struct(&sproto, "echo_request", |enc| {
+        enc.set_string(0, &"x".repeat(100))
+    });
+    let packet = sender.request("echo", &large, None, None).unwrap();
+    assert!(matches!(
+        host.dispatch(&packet),
+        Err(RpcError::Pack(PackError::TooLarge { max: 64 }))
+    ));
+    let mut buf = Vec::new();
+    assert!(matches!(
+        host.dispatch_into(&packet, &mut buf),
+        Err(RpcError::Pack(PackError::TooLarge { max: 64 }))
+    ));
+    assert!(buf.is_empty());
+    assert!(Host::new(sproto.clone()).dispatch(&packet).is_ok());
+}
+
+#[test]
+fn test_rpc_unpack_limit_rejects_zero_bomb() {
+    let sproto = create_rpc_schema();
+    let mut host = Host::new(sproto.clone()).with_unpack_limit(1024);
+
+    // A `notify` header followed by a megabyte of packed zero words.
+    let mut packet = Host::new(sproto.clone())
+        .attach(sproto.clone())
+        .request("notify", &[], None, None)
+        .unwrap();
+    packet.resize(packet.len() + 128 * 1024, 0);
+    let err = host.dispatch(&packet).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "pack error: unpacked data exceeds the limit of 1024 bytes"
+    );
+}
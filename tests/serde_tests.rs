@@ -0,0 +1,468 @@
+//! Serde adapter tests.
+//!
+//! Derived structs are encoded through `sproto::serde` and compared against
+//! the C/Lua binary fixtures, then decoded back.
+
+#![cfg(feature = "serde")]
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use sproto::binary_schema;
+use sproto::codec::DecodeLimits;
+use sproto::error::{DecodeError, EncodeError};
+use sproto::parser;
+
+fn testdata(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/testdata/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+fn load_sproto() -> sproto::Sproto {
+    binary_schema::load_binary(&testdata("schema.bin")).unwrap()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct PhoneNumber {
+    number: Option<String>,
+    r#type: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct Person {
+    name: Option<String>,
+    age: Option<i64>,
+    active: Option<bool>,
+    score: Option<f64>,
+    photo: Option<Vec<u8>>,
+    fpn: Option<f64>,
+    id: Option<i64>,
+    phone: Option<PhoneNumber>,
+    phones: Option<Vec<PhoneNumber>>,
+    children: Option<Vec<Person>>,
+    tags: Option<Vec<String>>,
+    numbers: Option<Vec<i64>>,
+    flags: Option<Vec<bool>>,
+    values: Option<Vec<f64>>,
+}
+
+fn phone(number: &str, kind: i64) -> PhoneNumber {
+    PhoneNumber {
+        number: Some(number.into()),
+        r#type: Some(kind),
+    }
+}
+
+fn full_person() -> Person {
+    Person {
+        name: Some("Alice".into()),
+        age: Some(30),
+        active: Some(true),
+        score: Some(0.01171875),
+        photo: Some(vec![0xDE, 0xAD, 0xBE, 0xEF]),
+        fpn: Some(1.82),
+        id: Some(10000),
+        phone: Some(phone("123456789", 1)),
+        phones: Some(vec![phone("123456789", 1), phone("87654321", 2)]),
+        children: Some(vec![Person {
+            name: Some("Bob".into()),
+            age: Some(5),
+            ..Default::default()
+        }]),
+        tags: Some(vec![
+            "hello".into(),
+            "world".into(),
+            "\u{4f60}\u{597d}".into(),
+        ]),
+        numbers: Some(vec![1, 2, 3, 4, 5]),
+        flags: Some(vec![false, true, false]),
+        values: Some(vec![0.01171875, 23.0, 4.0]),
+    }
+}
+
+/// Encode `value`, compare with the fixture, then decode the fixture back.
+fn assert_fixture(value: &Person, fixture: &str) {
+    let sproto = load_sproto();
+    let expected = testdata(fixture);
+    let encoded = sproto::serde::to_vec(&sproto, "Person", value).unwrap();
+    assert_eq!(encoded, expected, "serde encode {} mismatch", fixture);
+    let decoded: Person = sproto::serde::from_slice(&sproto, "Person", &expected).unwrap();
+    assert_eq!(&decoded, value, "serde decode {} mismatch", fixture);
+}
+
+// =============================================================================
+// Binary fixture cross-validation
+// =============================================================================
+
+#[test]
+fn test_serde_simple_struct() {
+    let person = Person {
+        name: Some("Alice".into()),
+        age: Some(13),
+        active: Some(false),
+        ..Default::default()
+    };
+    assert_fixture(&person, "simple_struct_encoded.bin");
+}
+
+#[test]
+fn test_serde_nested_struct() {
+    let person = Person {
+        name: Some("Alice".into()),
+        phone: Some(phone("123456789", 1)),
+        ..Default::default()
+    };
+    assert_fixture(&person, "nested_struct_encoded.bin");
+}
+
+#[test]
+fn test_serde_struct_array() {
+    let child = |name: &str, age| Person {
+        name: Some(name.into()),
+        age: Some(age),
+        ..Default::default()
+    };
+    let person = Person {
+        name: Some("Bob".into()),
+        age: Some(40),
+        children: Some(vec![child("Alice", 13), child("Carol", 5)]),
+        ..Default::default()
+    };
+    assert_fixture(&person, "struct_array_encoded.bin");
+}
+
+#[test]
+fn test_serde_big_int_array() {
+    let person = Person {
+        numbers: Some(vec![(1 << 32) + 1, (1 << 32) + 2, (1 << 32) + 3]),
+        ..Default::default()
+    };
+    assert_fixture(&person, "big_int_array_encoded.bin");
+}
+
+#[test]
+fn test_serde_fixed_point() {
+    let person = Person {
+        fpn: Some(1.82),
+        ..Default::default()
+    };
+    assert_fixture(&person, "fixed_point_encoded.bin");
+}
+
+#[test]
+fn test_serde_full() {
+    assert_fixture(&full_person(), "full_encoded.bin");
+}
+
+// =============================================================================
+// Borrowing, decimals and unknown fields
+// =============================================================================
+
+#[test]
+fn test_serde_borrowed_decode() {
+    #[derive(Deserialize)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        #[serde(default)]
+        tags: Vec<&'a str>,
+    }
+
+    let sproto = load_sproto();
+    let data = testdata("full_encoded.bin");
+    let p: Borrowed = sproto::serde::from_slice(&sproto, "Person", &data).unwrap();
+    assert_eq!(p.name, "Alice");
+    assert_eq!(p.tags, vec!["hello", "world", "\u{4f60}\u{597d}"]);
+}
+
+#[test]
+fn test_serde_decimal_raw_integer() {
+    // An integer on a decimal field is the raw, already-scaled wire value.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Raw {
+        fpn: i64,
+    }
+
+    let sproto = load_sproto();
+    let encoded = sproto::serde::to_vec(&sproto, "Person", &Raw { fpn: 182 }).unwrap();
+    assert_eq!(encoded, testdata("fixed_point_encoded.bin"));
+    let decoded: Raw = sproto::serde::from_slice(&sproto, "Person", &encoded).unwrap();
+    assert_eq!(decoded, Raw { fpn: 182 });
+}
+
+#[test]
+fn test_serde_ignores_fields_missing_from_rust_type() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct NameOnly {
+        name: String,
+    }
+
+    let sproto = load_sproto();
+    let data = testdata("full_encoded.bin");
+    let p: NameOnly = sproto::serde::from_slice(&sproto, "Person", &data).unwrap();
+    assert_eq!(p.name, "Alice");
+}
+
+// =============================================================================
+// Maps
+// =============================================================================
+
+const MAP_SCHEMA: &str = r#"
+.Item {
+    id 0 : integer
+    name 1 : string
+}
+
+.Inventory {
+    items 0 : *Item(id)
+    counts 1 : *Count()
+    list 2 : *Item(id)
+
+    .Count {
+        name 0 : string
+        count 1 : integer
+    }
+}
+"#;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Item {
+    id: i64,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Inventory {
+    items: HashMap<i64, Item>,
+    counts: HashMap<String, i64>,
+    #[serde(default)]
+    list: Vec<Item>,
+}
+
+#[test]
+fn test_serde_map_roundtrip() {
+    let schema = parser::parse(MAP_SCHEMA).unwrap();
+    let item = |id, name: &str| Item {
+        id,
+        name: name.into(),
+    };
+    let inventory = Inventory {
+        items: HashMap::from([(1, item(1, "sword")), (7, item(7, "shield"))]),
+        counts: HashMap::from([("arrow".to_string(), 20), ("potion".to_string(), 3)]),
+        list: vec![item(2, "bow")],
+    };
+
+    let encoded = sproto::serde::to_vec(&schema, "Inventory", &inventory).unwrap();
+    let decoded: Inventory = sproto::serde::from_slice(&schema, "Inventory", &encoded).unwrap();
+    assert_eq!(decoded, inventory);
+}
+
+#[test]
+fn test_serde_keyed_map_checks_keys() {
+    #[derive(Serialize)]
+    struct Items {
+        items: BTreeMap<i64, Item>,
+    }
+    #[derive(Serialize)]
+    struct Unkeyed {
+        name: String,
+    }
+    #[derive(Serialize)]
+    struct UnkeyedItems {
+        items: BTreeMap<i64, Unkeyed>,
+    }
+
+    let schema = parser::parse(MAP_SCHEMA).unwrap();
+    let item = |id, name: &str| Item {
+        id,
+        name: name.into(),
+    };
+    let items = |entries: Vec<(i64, Item)>| Items {
+        items: entries.into_iter().collect(),
+    };
+    sproto::serde::to_vec(&schema, "Inventory", &items(vec![(1, item(1, "sword"))])).unwrap();
+
+    // The element would decode back under its own id, not the map key.
+    let err = sproto::serde::to_vec(&schema, "Inventory", &items(vec![(1, item(2, "sword"))]))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: map field 'items': element key field does not match the map key"
+    );
+
+    let unkeyed = UnkeyedItems {
+        items: BTreeMap::from([(
+            1,
+            Unkeyed {
+                name: "sword".into(),
+            },
+        )]),
+    };
+    let err = sproto::serde::to_vec(&schema, "Inventory", &unkeyed).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: map field 'items': element lacks the map key"
+    );
+}
+
+#[test]
+fn test_serde_pair_map_wire_layout() {
+    // `*Count()` elements are plain two-field structs on the wire.
+    #[derive(Serialize)]
+    struct Count<'a> {
+        name: &'a str,
+        count: i64,
+    }
+    #[derive(Serialize)]
+    struct AsList<'a> {
+        counts: Vec<Count<'a>>,
+    }
+
+    #[derive(Serialize)]
+    struct Counts {
+        counts: BTreeMap<&'static str, i64>,
+    }
+
+    let schema = parser::parse(MAP_SCHEMA).unwrap();
+    let from_map = sproto::serde::to_vec(
+        &schema,
+        "Inventory",
+        &Counts {
+            counts: BTreeMap::from([("arrow", 20), ("potion", 3)]),
+        },
+    )
+    .unwrap();
+    let from_list = sproto::serde::to_vec(
+        &schema,
+        "Inventory",
+        &AsList {
+            counts: vec![
+                Count {
+                    name: "arrow",
+                    count: 20,
+                },
+                Count {
+                    name: "potion",
+                    count: 3,
+                },
+            ],
+        },
+    )
+    .unwrap();
+    assert_eq!(from_map, from_list);
+}
+
+// =============================================================================
+// Errors
+// =============================================================================
+
+#[test]
+fn test_serde_unknown_type() {
+    let sproto = load_sproto();
+    let err = sproto::serde::to_vec(&sproto, "Nope", &Person::default()).unwrap_err();
+    assert!(matches!(err, EncodeError::UnknownType(ref t) if t == "Nope"));
+    let err = sproto::serde::from_slice::<Person>(&sproto, "Nope", &[0, 0]).unwrap_err();
+    assert!(matches!(err, DecodeError::UnknownType(ref t) if t == "Nope"));
+}
+
+#[test]
+fn test_serde_decode_limits() {
+    let sproto = load_sproto();
+    let person = Person {
+        name: Some("Alice".into()),
+        numbers: Some(vec![1, 2, 3]),
+        ..Default::default()
+    };
+    let bytes = sproto::serde::to_vec(&sproto, "Person", &person).unwrap();
+
+    let limits = DecodeLimits {
+        max_array_len: 2,
+        ..Default::default()
+    };
+    let err = sproto::serde::from_slice_with_limits::<Person>(&sproto, "Person", &bytes, limits)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "array field 'numbers' exceeds the limit of 2 elements"
+    );
+
+    let limits = DecodeLimits {
+        max_array_len: 3,
+        ..Default::default()
+    };
+    let decoded: Person =
+        sproto::serde::from_slice_with_limits(&sproto, "Person", &bytes, limits).unwrap();
+    assert_eq!(decoded, person);
+}
+
+#[test]
+fn test_serde_top_level_must_be_struct() {
+    let sproto = load_sproto();
+    let err = sproto::serde::to_vec(&sproto, "Person", &BTreeMap::from([("age", 1)])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: type 'Person' must be serialized from a struct, got map"
+    );
+}
+
+#[test]
+fn test_serde_unknown_field() {
+    #[derive(Serialize)]
+    struct Extra {
+        name: String,
+        nickname: String,
+    }
+
+    let sproto = load_sproto();
+    let value = Extra {
+        name: "Alice".into(),
+        nickname: "Al".into(),
+    };
+    let err = sproto::serde::to_vec(&sproto, "Person", &value).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: unknown field 'nickname' in type 'Person'"
+    );
+}
+
+#[test]
+fn test_serde_type_mismatch() {
+    #[derive(Serialize)]
+    struct Wrong {
+        age: String,
+    }
+    #[derive(Serialize)]
+    struct WrongArray {
+        numbers: Vec<String>,
+    }
+
+    let sproto = load_sproto();
+    let err = sproto::serde::to_vec(&sproto, "Person", &Wrong { age: "x".into() }).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type mismatch for field 'age': expected integer, got string"
+    );
+
+    let value = WrongArray {
+        numbers: vec!["x".into()],
+    };
+    let err = sproto::serde::to_vec(&sproto, "Person", &value).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type mismatch for field 'numbers': expected *integer, got string"
+    );
+}
+
+#[test]
+fn test_serde_decode_missing_required_field() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Required {
+        name: String,
+        age: i64,
+    }
+
+    let sproto = load_sproto();
+    let data = testdata("string_array_encoded.bin");
+    let err = sproto::serde::from_slice::<Required>(&sproto, "Person", &data).unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidData(ref m) if m.contains("missing field")));
+}
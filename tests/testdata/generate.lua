@@ -234,4 +234,27 @@ assert(type3 == "REQUEST" and name3 == "bar")
 local resp_bar = response_fn3()
 write_file("rpc_bar_response.bin", resp_bar)
 
+-- =============================================================================
+-- Map schema: *T(key) and two-field *T() maps
+-- =============================================================================
+
+local map_schema_text = [[
+.Item {
+    id 0 : integer
+    name 1 : string
+}
+
+.Count {
+    key 0 : string
+    value 1 : integer
+}
+
+.Inventory {
+    items 0 : *Item(id)
+    counts 1 : *Count()
+}
+]]
+
+write_file("map_schema.bin", sprotoparser.parse(map_schema_text))
+
 print("\nAll test fixtures generated successfully.")
@@ -0,0 +1,261 @@
+//! Dynamic `Value` tests.
+//!
+//! Values are encoded with `encode_value` and compared against the C/Lua
+//! binary fixtures, then decoded back with `decode_value`.
+
+use std::collections::BTreeMap;
+
+use sproto::binary_schema;
+use sproto::parser;
+use sproto::value::{decode_value, encode_value, Value};
+
+fn testdata(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/testdata/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+fn load_sproto() -> sproto::Sproto {
+    binary_schema::load_binary(&testdata("schema.bin")).unwrap()
+}
+
+fn object<const N: usize>(fields: [(&str, Value); N]) -> Value {
+    Value::Struct(
+        fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+fn phone(number: &str, kind: i64) -> Value {
+    object([("number", number.into()), ("type", kind.into())])
+}
+
+fn full_person() -> Value {
+    object([
+        ("name", "Alice".into()),
+        ("age", 30.into()),
+        ("active", true.into()),
+        ("score", 0.01171875.into()),
+        ("photo", Value::Binary(vec![0xDE, 0xAD, 0xBE, 0xEF])),
+        ("fpn", Value::Decimal(1.82)),
+        ("id", 10000.into()),
+        ("phone", phone("123456789", 1)),
+        (
+            "phones",
+            Value::Array(vec![phone("123456789", 1), phone("87654321", 2)]),
+        ),
+        (
+            "children",
+            Value::Array(vec![object([("name", "Bob".into()), ("age", 5.into())])]),
+        ),
+        (
+            "tags",
+            Value::Array(vec![
+                "hello".into(),
+                "world".into(),
+                "\u{4f60}\u{597d}".into(),
+            ]),
+        ),
+        (
+            "numbers",
+            Value::Array(vec![1.into(), 2.into(), 3.into(), 4.into(), 5.into()]),
+        ),
+        (
+            "flags",
+            Value::Array(vec![false.into(), true.into(), false.into()]),
+        ),
+        (
+            "values",
+            Value::Array(vec![0.01171875.into(), 23.0.into(), 4.0.into()]),
+        ),
+    ])
+}
+
+// =============================================================================
+// Binary fixture cross-validation
+// =============================================================================
+
+#[test]
+fn test_value_encode_full() {
+    let sproto = load_sproto();
+    let st = sproto.get_type("Person").unwrap();
+    let encoded = encode_value(&sproto, st, &full_person()).unwrap();
+    assert_eq!(encoded, testdata("full_encoded.bin"));
+}
+
+#[test]
+fn test_value_decode_full() {
+    let sproto = load_sproto();
+    let st = sproto.get_type("Person").unwrap();
+    let decoded = decode_value(&sproto, st, &testdata("full_encoded.bin")).unwrap();
+    assert_eq!(decoded, full_person());
+    assert_eq!(decoded.get("fpn"), Some(&Value::Decimal(1.82)));
+    assert_eq!(decoded.get("missing"), None);
+}
+
+#[test]
+fn test_value_fixture_roundtrip() {
+    let sproto = load_sproto();
+    let st = sproto.get_type("Person").unwrap();
+    for fixture in [
+        "simple_struct_encoded.bin",
+        "all_scalars_encoded.bin",
+        "nested_struct_encoded.bin",
+        "struct_array_encoded.bin",
+        "int_array_encoded.bin",
+        "big_int_array_encoded.bin",
+        "bool_array_encoded.bin",
+        "number_encoded.bin",
+        "double_encoded.bin",
+        "string_array_encoded.bin",
+        "fixed_point_encoded.bin",
+    ] {
+        let expected = testdata(fixture);
+        let value = decode_value(&sproto, st, &expected).unwrap();
+        let encoded = encode_value(&sproto, st, &value).unwrap();
+        assert_eq!(encoded, expected, "{}", fixture);
+    }
+}
+
+#[test]
+fn test_value_decimal_accepts_raw_integer() {
+    let sproto = load_sproto();
+    let st = sproto.get_type("Person").unwrap();
+    let from_decimal = encode_value(&sproto, st, &object([("fpn", Value::Decimal(1.82))])).unwrap();
+    let from_raw = encode_value(&sproto, st, &object([("fpn", 182.into())])).unwrap();
+    assert_eq!(from_decimal, from_raw);
+}
+
+// =============================================================================
+// Maps
+// =============================================================================
+
+const MAP_SCHEMA: &str = r#"
+.Item {
+    id 0 : integer
+    name 1 : string
+}
+
+.Inventory {
+    items 0 : *Item(id)
+    counts 1 : *Count()
+    list 2 : *Item(id)
+
+    .Count {
+        name 0 : string
+        count 1 : integer
+    }
+}
+"#;
+
+fn item(id: i64, name: &str) -> Value {
+    object([("id", id.into()), ("name", name.into())])
+}
+
+#[test]
+fn test_value_maps() {
+    let schema = parser::parse(MAP_SCHEMA).unwrap();
+    let st = schema.get_type("Inventory").unwrap();
+    let inventory = object([
+        (
+            "items",
+            Value::Map(vec![
+                (1.into(), item(1, "sword")),
+                (7.into(), item(7, "shield")),
+            ]),
+        ),
+        (
+            "counts",
+            Value::Map(vec![
+                ("arrow".into(), 20.into()),
+                ("potion".into(), 3.into()),
+            ]),
+        ),
+        // A keyed field may also be written as a plain array of elements.
+        ("list", Value::Array(vec![item(2, "bow")])),
+    ]);
+
+    let encoded = encode_value(&schema, st, &inventory).unwrap();
+    let decoded = decode_value(&schema, st, &encoded).unwrap();
+    assert_eq!(decoded.get("items"), inventory.get("items"));
+    assert_eq!(decoded.get("counts"), inventory.get("counts"));
+    assert_eq!(
+        decoded.get("list"),
+        Some(&Value::Map(vec![(2.into(), item(2, "bow"))]))
+    );
+}
+
+#[test]
+fn test_value_pair_map_wire_layout() {
+    let schema = parser::parse(MAP_SCHEMA).unwrap();
+    let st = schema.get_type("Inventory").unwrap();
+    let from_map = encode_value(
+        &schema,
+        st,
+        &object([("counts", Value::Map(vec![("arrow".into(), 20.into())]))]),
+    )
+    .unwrap();
+    let from_list = encode_value(
+        &schema,
+        st,
+        &object([(
+            "counts",
+            Value::Array(vec![object([
+                ("name", "arrow".into()),
+                ("count", 20.into()),
+            ])]),
+        )]),
+    )
+    .unwrap();
+    assert_eq!(from_map, from_list);
+}
+
+// =============================================================================
+// Errors
+// =============================================================================
+
+#[test]
+fn test_value_encode_errors() {
+    let sproto = load_sproto();
+    let st = sproto.get_type("Person").unwrap();
+
+    let err = encode_value(&sproto, st, &object([("age", "thirty".into())])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type mismatch for field 'age': expected integer, got string"
+    );
+
+    let err = encode_value(
+        &sproto,
+        st,
+        &object([("numbers", Value::Array(vec![true.into()]))]),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type mismatch for field 'numbers': expected *integer, got array of boolean"
+    );
+
+    let err = encode_value(&sproto, st, &object([("nickname", "Al".into())])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: unknown field 'nickname' in type 'Person'"
+    );
+
+    let err = encode_value(&sproto, st, &Value::Array(Vec::new())).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "encode error: type 'Person' must be encoded from a struct, got array"
+    );
+}
+
+#[test]
+fn test_value_empty_struct() {
+    let sproto = load_sproto();
+    let st = sproto.get_type("Person").unwrap();
+    let empty = Value::Struct(BTreeMap::new());
+    let encoded = encode_value(&sproto, st, &empty).unwrap();
+    assert_eq!(encoded, vec![0, 0]);
+    assert_eq!(decode_value(&sproto, st, &encoded).unwrap(), empty);
+}